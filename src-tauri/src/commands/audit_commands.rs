@@ -0,0 +1,63 @@
+use crate::modules::audit::{self, AuditReport, BreachedRecord};
+use crate::modules::kakadu_file_module::unix_time;
+use crate::state::AppState;
+use crate::utils::CommandError;
+#[cfg(feature = "audit")]
+use crate::modules::audit::hibp::{self, HibpDatabase};
+#[cfg(feature = "audit")]
+use crate::modules::kakadu_file_module::KakaduError;
+#[cfg(feature = "audit")]
+use std::path::Path;
+
+/// Проверяет «здоровье» хранилища для панели безопасности
+///
+/// # Возвращает
+/// Записи со слабыми, повторяющимися, устаревшими или пустыми паролями, паролями,
+/// равными логину, и записи без URL, а также сводные показатели (`health_score`, `average_score`)
+///
+/// # Ошибки
+/// * `no_data` - данные не загружены
+#[tauri::command]
+pub async fn audit_vault(state: tauri::State<'_, AppState>) -> Result<AuditReport, CommandError> {
+    let data = state.vault()?;
+
+    Ok(audit::audit_vault(&data, unix_time()))
+}
+
+/// Проверяет пароли записей по локально загруженной базе утечек HIBP
+///
+/// # Аргументы
+/// * `hibp_path` - отсортированный файл `SHA1:COUNT` или каталог файлов диапазонов.
+///   Для файла при первом вызове рядом строится индекс `<файл>.kkidx`
+///
+/// # Возвращает
+/// Записи, пароли которых найдены в базе, с числом появлений в утечках
+///
+/// # Ошибки
+/// * `no_data` - данные не загружены
+/// * `io` - файл базы не найден, не читается или имеет неверный формат
+/// * `feature_disabled` - приложение собрано без функции `audit`
+#[cfg(feature = "audit")]
+#[tauri::command]
+pub async fn check_breached_passwords(
+    hibp_path: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<BreachedRecord>, CommandError> {
+    // Индекс строится до блокировки данных: для полной базы это долгая операция
+    let db = HibpDatabase::open(Path::new(&hibp_path)).map_err(KakaduError::from)?;
+
+    let data = state.vault()?;
+
+    Ok(hibp::check_breaches(&data, &db).map_err(KakaduError::from)?)
+}
+
+/// Заглушка для сборки без функции `audit`: всегда возвращает `feature_disabled`
+#[cfg(not(feature = "audit"))]
+#[tauri::command]
+pub async fn check_breached_passwords(hibp_path: String) -> Result<Vec<BreachedRecord>, CommandError> {
+    let _ = hibp_path;
+    Err(CommandError::new(
+        "feature_disabled",
+        "Проверка по базе утечек недоступна: приложение собрано без функции audit",
+    ))
+}
@@ -1,62 +1,360 @@
-use crate::modules::kakadu_file_module::{KakaduProvider, PasswordData, Record, Group};
+use crate::modules::kakadu_file_module::attachments::{self, Attachment, AttachmentStore, MAX_VAULT_ATTACHMENTS_SIZE};
+use crate::modules::kakadu_file_module::secret::{MasterPassword, SecretField};
+use crate::modules::kakadu_file_module::integrity::{self, VaultReport};
+use crate::modules::kakadu_file_module::kinds::RecordKind;
+use crate::modules::kakadu_file_module::search::{self, SearchOptions, SearchResult};
+use crate::modules::kakadu_file_module::storage::{self, BackupInfo};
+use crate::modules::kakadu_file_module::tree::{GroupNode, GroupTree};
+use crate::modules::kakadu_file_module::expiry::{self, ExpiringRecord};
+use crate::modules::totp::{OtpInput, OtpKind};
+use crate::modules::kakadu_file_module::{
+    unix_time, CustomField, DeleteGroupMode, Group, InputSymbol, KakaduError, KakaduProvider, PasswordData,
+    PasswordHistoryEntry, Record, Timestamps, MAX_BACKUP_COUNT,
+};
 use crate::state::AppState;
-use crate::utils::emit_event;
+use crate::utils::{emit_event, CommandError};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
 use tauri::{AppHandle, Manager};
+use zeroize::Zeroizing;
+
+/// Данные записи, приходящие с фронтенда при создании и редактировании
+///
+/// Символы ввода принимаются строками и проверяются явно,
+/// чтобы вернуть понятную ошибку вместо ошибки десериализации.
+/// При редактировании отсутствующие `kind`, `expiresAt`, `otp`, `customFields`, `notes`
+/// и `tags` оставляют сохраненные значения; `"expiresAt": null` снимает срок действия,
+/// `"otp": null` удаляет параметры OTP
+#[derive(Deserialize)]
+pub struct RecordInput {
+    pub pid: u32,
+    pub name: String,
+    pub login: String,
+    pub password: SecretField,
+    pub url: String,
+
+    #[serde(rename = "loginSymbol")]
+    pub login_symbol: String,
+
+    #[serde(rename = "passwordSymbol")]
+    pub password_symbol: String,
+
+    #[serde(rename = "urlSymbol")]
+    pub url_symbol: String,
+
+    #[serde(default)]
+    pub kind: Option<RecordKind>,
+
+    #[serde(rename = "expiresAt", default, deserialize_with = "present")]
+    pub expires_at: Option<Option<u64>>, // None - поле не передано, Some(None) - явный null
+
+    #[serde(default, deserialize_with = "present")]
+    pub otp: Option<Option<OtpInput>>, // None - поле не передано, Some(None) - явный null
+
+    #[serde(rename = "customFields", default)]
+    pub custom_fields: Option<Vec<CustomField>>,
+
+    #[serde(default)]
+    pub notes: Option<String>,
+
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+}
+
+/// Десериализует присутствующее поле в `Some`, в том числе `null` - в `Some(None)`;
+/// отсутствующее поле остается `None` благодаря `#[serde(default)]`
+fn present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl RecordInput {
+    /// Проверяет данные и собирает из них запись с указанным ID
+    ///
+    /// `previous` - редактируемая запись: из нее берутся непереданные `kind`, `expires_at`,
+    /// `otp`, `custom_fields`, `notes` и `tags`, а также счетчик HOTP. Счетчик меняет только
+    /// `get_otp_code`, поэтому устаревшая копия параметров с фронтенда не может
+    /// откатить его назад; для нового токена OTP сначала удаляется (`null`)
+    fn into_record(self, id: u32, data: &PasswordData, previous: Option<&Record>) -> Result<Record, CommandError> {
+        if self.name.trim().is_empty() {
+            return Err(CommandError::new("empty_name", "Название записи не может быть пустым"));
+        }
+        if !data.has_group(self.pid) {
+            return Err(CommandError::new("no_group", "Родительская группа с указанным ID не найдена"));
+        }
+
+        let custom_fields = match self.custom_fields {
+            Some(fields) => fields,
+            None => previous.map(|r| r.custom_fields.clone()).unwrap_or_default(),
+        };
+        if custom_fields.iter().any(|f| f.name.trim().is_empty()) {
+            return Err(CommandError::new("empty_name", "Название дополнительного поля не может быть пустым"));
+        }
+
+        // Метки без пробелов по краям; пустые и повторяющиеся отбрасываются
+        let tags = match self.tags {
+            Some(tags) => tags
+                .iter()
+                .map(|t| t.trim())
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect(),
+            None => previous.map(|r| r.tags.clone()).unwrap_or_default(),
+        };
+
+        let previous_otp = previous.and_then(|r| r.otp.as_ref());
+        let mut otp = match self.otp {
+            Some(otp) => otp
+                .map(OtpInput::into_config)
+                .transpose()
+                .map_err(CommandError::invalid_input)?,
+            None => previous_otp.cloned(),
+        };
+        if let (Some(otp), Some(previous_otp)) = (otp.as_mut(), previous_otp) {
+            if otp.kind == OtpKind::Hotp && previous_otp.kind == OtpKind::Hotp {
+                otp.counter = previous_otp.counter;
+            }
+        }
+
+        let notes = match self.notes {
+            Some(notes) => notes,
+            None => previous.map(|r| r.notes.clone()).unwrap_or_default(),
+        };
+
+        let expires_at = match self.expires_at {
+            Some(expires_at) => expires_at,
+            None => previous.and_then(|r| r.times.expires_at),
+        };
+
+        let mut kind = match self.kind {
+            Some(kind) => kind,
+            None => previous.map(|r| r.kind.clone()).unwrap_or_default(),
+        };
+        kind.validate().map_err(CommandError::invalid_input)?;
+
+        Ok(Record {
+            id,
+            pid: self.pid,
+            name: self.name,
+            login: self.login,
+            password: self.password,
+            url: self.url,
+            login_symbol: self.login_symbol.parse::<InputSymbol>().map_err(CommandError::invalid_input)?,
+            password_symbol: self.password_symbol.parse::<InputSymbol>().map_err(CommandError::invalid_input)?,
+            url_symbol: self.url_symbol.parse::<InputSymbol>().map_err(CommandError::invalid_input)?,
+            kind,
+            times: Timestamps {
+                expires_at,
+                ..Timestamps::created()
+            },
+            otp,
+            custom_fields,
+            notes,
+            tags,
+            attachments: Vec::new(),
+            history: Vec::new(),
+        })
+    }
+}
+
+/// Проверяет только что загруженные данные
+///
+/// Повторяющиеся ID исправляются сразу, и на фронтенд отправляется событие `ids_repaired`
+/// со списком переназначений. Остальные проблемы структуры отправляются событием
+/// `vault_problems`, исправить их можно командой `repair_vault`.
+/// Исправления остаются несохраненными изменениями.
+/// Записи с истекшим сроком действия отправляются событием `records_expired`
+pub(crate) fn check_loaded_vault(app: &AppHandle, data: &mut PasswordData) -> Result<(), CommandError> {
+    let repairs = data.repair_ids()?;
+    if !repairs.is_empty() {
+        emit_event(app, "ids_repaired", &repairs, "Ошибка отправки исправлений ID")?;
+    }
+
+    let report = integrity::validate_vault(data);
+    if !report.is_ok() {
+        emit_event(app, "vault_problems", &report, "Ошибка отправки отчета о проверке")?;
+    }
+
+    let expired = expiry::expired_records(data, unix_time());
+    if !expired.is_empty() {
+        emit_event(app, "records_expired", &expired, "Ошибка отправки истекших записей")?;
+    }
+
+    Ok(())
+}
+
+/// Отправляет на фронтенд записи указанной группы
+fn emit_group_records(app: &AppHandle, data: &PasswordData, group_id: u32) -> Result<(), String> {
+    let records = data
+        .records
+        .iter()
+        .filter(|r| r.pid == group_id)
+        .collect::<Vec<&Record>>();
+
+    emit_event(app, "get_records_listen", &records, "Ошибка отправки записей")
+}
 
 /// Открывает и парсит файл с данными, сохраняет в состоянии и отправляет группы на фронтенд
 ///
 /// # Аргументы
 /// * `app` - экземпляр AppHandle для взаимодействия с Tauri
 /// * `path` - путь к файлу для открытия
+/// * `password` - мастер-пароль; в состоянии сохраняется только полученный из него ключ
 /// * `state` - глобальное состояние приложения
 ///
+/// # Поведение
+/// Для файлов устаревшего формата ключ сессии не сохраняется: `save_current` вернет
+/// `no_session`, и первое сохранение выполняется через `save_file` с паролем
+///
 /// # Ошибки
-/// Возвращает String с описанием ошибки при проблемах с чтением файла или отправкой событий
+/// Возвращает `CommandError` со стабильным кодом (`wrong_password`, `truncated`,
+/// `bad_padding`, `unsupported_version`, `corrupted`, `io`, `json`, ...) для локализации на фронтенде
 #[tauri::command]
 pub async fn open_file(
     app: AppHandle,
     path: &str,
-    password: &str,
+    password: MasterPassword,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
-    let provider = KakaduProvider;
-    println!("OPEN");
-    match provider.open_file(path, password) {
-        Ok(data) => {
-            // Блокируем и обновляем состояние атомарно
-            *state.password_data.lock().unwrap() = Some(data.clone());
+) -> Result<(), CommandError> {
+    let provider = KakaduProvider::default();
+    let (data, session) = provider.open_session(path, &password)?;
 
-            // Отправка групп через Tauri-событие на фронтенд
-            emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+    // Блокируем и обновляем состояние атомарно, без копирования расшифрованных данных
+    let mut data_guard = state.password_data.lock().unwrap();
+    let data = data_guard.insert(data);
+    *state.session_key.lock().unwrap() = session;
+    *state.vault_path.lock().unwrap() = Some(path.to_string());
+    state.mark_saved(data);
+    state.touch();
 
-            Ok(())
-        }
-        Err(e) => Err(format!("Ошибка обработки файла: {}", e)),
-    }
+    // Отпечаток снят до исправления ID, поэтому исправления считаются несохраненными
+    check_loaded_vault(&app, data)?;
+
+    // Отправка групп через Tauri-событие на фронтенд
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+
+    Ok(())
 }
 
-/// Сохраняет текущие данные в указанный файл
+/// Сохраняет текущие данные в указанный файл ("Сохранить как")
 ///
 /// # Аргументы
 /// * `path` - целевой путь для сохранения
+/// * `password` - новый мастер-пароль; если не передан, используется ключ текущей сессии
 /// * `state` - глобальное состояние с данными
 ///
+/// # Поведение
+/// После успешной записи ключ и путь запоминаются в состоянии для `save_current`
+///
 /// # Ошибки
-/// Возвращает ошибку если: нет данных для сохранения или произошла ошибка записи
+/// * `no_data` - нет данных для сохранения
+/// * `no_session` - не передан пароль и нет ключа сессии
+/// * коды `KakaduError` - ошибка получения ключа, шифрования или записи
 #[tauri::command]
-pub async fn save_file(app: AppHandle, path: String, password: &str, state: tauri::State<'_, AppState>) -> Result<(), String> {
-    let provider = KakaduProvider;
-    let data = state.password_data.lock().unwrap();
+pub async fn save_file(app: AppHandle, path: String, password: Option<MasterPassword>, state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    let provider = state.provider();
+    let data = state.vault()?;
+    let mut session_key = state.session_key.lock().unwrap();
 
-    match &*data {
-        Some(data) => {
-            let res = provider
-                .save_file(path.clone(), password, data)
-                .map_err(|e| format!("Ошибка сохранения файла: {}", e));
-            res
-        },
-        None => Err("Отсутствуют данные для сохранения".to_string()),
+    // Новый пароль порождает новый ключ; иначе повторно используем ключ сессии
+    let new_key = password.as_ref().map(|p| provider.derive_session_key(p)).transpose()?;
+    let key = new_key
+        .as_ref()
+        .or(session_key.as_ref())
+        .ok_or_else(|| CommandError::new("no_session", "Не задан пароль для сохранения"))?;
+
+    provider.save_with_key(path.clone(), key, &data)?;
+
+    if new_key.is_some() {
+        *session_key = new_key;
     }
+    *state.vault_path.lock().unwrap() = Some(path);
+    state.mark_saved(&data);
+
+    Ok(())
+}
+
+/// Сохраняет текущие данные в открытый файл, используя ключ сессии ("Сохранить")
+///
+/// # Ошибки
+/// * `no_data` - данные не загружены
+/// * `no_path` - хранилище еще ни разу не сохранялось, нужен `save_file`
+/// * `no_session` - нет ключа сессии, нужен `save_file` с паролем
+/// * коды `KakaduError` - ошибка шифрования или записи
+#[tauri::command]
+pub async fn save_current(state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    let provider = state.provider();
+    let data = state.vault()?;
+    let session_key = state.session_key.lock().unwrap();
+    let key = session_key
+        .as_ref()
+        .ok_or_else(|| CommandError::new("no_session", "Ключ сессии не задан"))?;
+    let path = state
+        .vault_path
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| CommandError::new("no_path", "Путь к файлу не задан"))?;
+
+    provider.save_with_key(path, key, &data)?;
+    state.mark_saved(&data);
+
+    Ok(())
+}
+
+/// Возвращает список резервных копий файла `.kkd.bak.N`
+///
+/// # Аргументы
+/// * `path` - путь к основному файлу
+///
+/// # Возвращает
+/// Копии с номерами до текущего `backup_count`, от самой свежей (`index == 1`) к самой старой
+#[tauri::command]
+pub async fn list_backups(path: &str, state: tauri::State<'_, AppState>) -> Result<Vec<BackupInfo>, CommandError> {
+    Ok(state.provider().list_backups(path)?)
+}
+
+/// Восстанавливает файл из резервной копии
+///
+/// # Аргументы
+/// * `path` - путь к основному файлу
+/// * `index` - номер копии из `list_backups`
+///
+/// # Поведение
+/// Текущая версия файла сохраняется как `.bak.1`. Данные в состоянии не меняются:
+/// для загрузки восстановленного файла фронтенд вызывает `open_file`
+#[tauri::command]
+pub async fn restore_backup(path: &str, index: usize, state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    Ok(state.provider().restore_backup(path, index)?)
+}
+
+/// Задает количество резервных копий, сохраняемых при записи файла
+///
+/// # Аргументы
+/// * `count` - число копий `.bak.N`; `0` отключает резервное копирование
+///
+/// # Поведение
+/// Копии с номерами больше нового значения удаляются не сразу, а при ротации
+/// следующего сохранения
+///
+/// # Ошибки
+/// * `invalid_input` - значение больше `MAX_BACKUP_COUNT`
+#[tauri::command]
+pub async fn set_backup_count(count: usize, state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    if count > MAX_BACKUP_COUNT {
+        return Err(CommandError::invalid_input(format!(
+            "Количество резервных копий не может превышать {}",
+            MAX_BACKUP_COUNT
+        )));
+    }
+    *state.backup_count.lock().unwrap() = count;
+
+    Ok(())
 }
 
 /// Получает записи по ID группы и отправляет на фронтенд
@@ -64,6 +362,7 @@ pub async fn save_file(app: AppHandle, path: String, password: &str, state: taur
 /// # Аргументы
 /// * `app` - экземпляр AppHandle для эмита событий
 /// * `group_id` - идентификатор группы для фильтрации
+/// * `include_descendants` - включить записи всех вложенных групп (по умолчанию нет)
 /// * `state` - глобальное состояние приложения
 ///
 /// # Ошибки
@@ -72,20 +371,45 @@ pub async fn save_file(app: AppHandle, path: String, password: &str, state: taur
 pub async fn get_records_by_group(
     app: AppHandle,
     group_id: u32,
+    include_descendants: Option<bool>,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
-    let data = state.password_data.lock().unwrap();
-
-    let records = match &*data {
-        Some(data) => data
-            .records
-            .iter()
-            .filter(|r| r.pid == group_id)
-            .cloned()
-            .collect::<Vec<Record>>(),
-        None => return Err("Данные не загружены в систему".to_string()),
+) -> Result<(), CommandError> {
+    let data = state.vault()?;
+
+    let group_ids: HashSet<u32> = if include_descendants.unwrap_or(false) {
+        GroupTree::new(&data).subtree(group_id).into_iter().collect()
+    } else {
+        HashSet::from([group_id])
     };
+    let records = data
+        .records
+        .iter()
+        .filter(|r| group_ids.contains(&r.pid))
+        .collect::<Vec<&Record>>();
+
+    emit_event(&app, "get_records_listen", &records, "Ошибка отправки записей")?;
+
+    Ok(())
+}
+
+/// Получает записи с указанной меткой и отправляет их на фронтенд
+///
+/// # Аргументы
+/// * `app` - экземпляр AppHandle для эмита событий
+/// * `tag` - метка; регистр и пробелы по краям не учитываются
+/// * `state` - глобальное состояние приложения
+///
+/// # Ошибки
+/// Возвращает ошибку если данные не загружены
+#[tauri::command]
+pub async fn get_records_by_tag(
+    app: AppHandle,
+    tag: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let data = state.vault()?;
 
+    let records: Vec<&Record> = data.records.iter().filter(|r| r.has_tag(&tag)).collect();
     emit_event(&app, "get_records_listen", &records, "Ошибка отправки записей")?;
 
     Ok(())
@@ -106,23 +430,386 @@ pub async fn get_records_by_group(
 pub async fn delete_record(
     record_id: u32,
     state: tauri::State<'_, AppState>,
-) -> Result<u32, String> {
-    let mut data = state.password_data.lock().unwrap();
+) -> Result<u32, CommandError> {
+    let mut data = state.vault()?;
+
+    let index = data
+        .records
+        .iter()
+        .position(|r| r.id == record_id)
+        .ok_or_else(|| CommandError::new("no_record", "Запись с указанным ID не найдена"))?;
+    data.records.remove(index);
+
+    Ok(record_id)
+}
+
+/// Создает новую запись и отправляет обновленный список записей ее группы
+///
+/// # Аргументы
+/// * `record` - данные записи; `pid` должен указывать на существующую группу
+///
+/// # Возвращает
+/// Созданную запись с выделенным ID
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены, группа не найдена,
+/// название пустое или символ ввода недопустим
+#[tauri::command]
+pub async fn create_record(
+    app: AppHandle,
+    record: RecordInput,
+    state: tauri::State<'_, AppState>,
+) -> Result<Record, CommandError> {
+    let mut data = state.vault()?;
+    let data = &mut *data;
+
+    // ID выделяется только после проверки данных, чтобы ошибка не расходовала счетчик
+    let mut new_record = record.into_record(0, data, None)?;
+    new_record.id = data.allocate_id()?;
+    data.records.push(new_record.clone());
+
+    emit_group_records(&app, data, new_record.pid)?;
+    Ok(new_record)
+}
+
+/// Обновляет существующую запись и отправляет обновленный список записей ее группы
+///
+/// # Аргументы
+/// * `record_id` - ID изменяемой записи
+/// * `record` - новые данные записи
+///
+/// # Поведение
+/// Непереданные `kind`, `expiresAt`, `otp`, `customFields`, `notes` и `tags` сохраняются, счетчик HOTP не меняется
+/// (см. `RecordInput`). Если запись перенесена в другую группу, списки отправляются
+/// для старой и новой групп
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены, запись или группа не найдены,
+/// название пустое или символ ввода недопустим
+#[tauri::command]
+pub async fn update_record(
+    app: AppHandle,
+    record_id: u32,
+    record: RecordInput,
+    state: tauri::State<'_, AppState>,
+) -> Result<Record, CommandError> {
+    let mut data = state.vault()?;
+    let data = &mut *data;
+
+    let index = data
+        .records
+        .iter()
+        .position(|r| r.id == record_id)
+        .ok_or_else(|| CommandError::new("no_record", "Запись с указанным ID не найдена"))?;
+
+    let mut updated = record.into_record(record_id, data, Some(&data.records[index]))?;
+    let previous = &data.records[index];
+    updated.history = previous.history.clone();
+    updated.attachments = previous.attachments.clone();
+    updated.remember_password(&previous.password);
+    updated.times = Timestamps {
+        expires_at: updated.times.expires_at,
+        modified_at: updated.times.modified_at,
+        ..previous.times.clone()
+    };
+    let old_pid = std::mem::replace(&mut data.records[index], updated.clone()).pid;
+
+    if old_pid != updated.pid {
+        emit_group_records(&app, data, old_pid)?;
+    }
+    emit_group_records(&app, data, updated.pid)?;
+    Ok(updated)
+}
+
+/// Возвращает историю паролей записи
+///
+/// # Аргументы
+/// * `record_id` - ID записи
+/// * `state` - глобальное состояние приложения
+///
+/// # Возвращает
+/// Прежние пароли с временем замены, от свежих к старым
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены или запись не найдена
+#[tauri::command]
+pub async fn get_password_history(
+    record_id: u32,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<PasswordHistoryEntry>, CommandError> {
+    let data = state.vault()?;
+
+    data.records
+        .iter()
+        .find(|r| r.id == record_id)
+        .map(|r| r.history.clone())
+        .ok_or_else(|| CommandError::new("no_record", "Запись с указанным ID не найдена"))
+}
+
+/// Восстанавливает пароль записи из истории и отправляет записи группы на фронтенд
+///
+/// # Аргументы
+/// * `record_id` - ID записи
+/// * `index` - индекс в истории (0 - последний замененный пароль)
+/// * `state` - глобальное состояние приложения
+///
+/// # Поведение
+/// Текущий пароль не теряется: он становится первой записью истории
+///
+/// # Возвращает
+/// Обновленную запись
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены, запись или запись истории не найдены
+#[tauri::command]
+pub async fn restore_password_from_history(
+    app: AppHandle,
+    record_id: u32,
+    index: usize,
+    state: tauri::State<'_, AppState>,
+) -> Result<Record, CommandError> {
+    let mut data = state.vault()?;
+    let data = &mut *data;
+
+    let record = data
+        .records
+        .iter_mut()
+        .find(|r| r.id == record_id)
+        .ok_or_else(|| CommandError::new("no_record", "Запись с указанным ID не найдена"))?;
+
+    if !record.restore_password(index) {
+        return Err(CommandError::new("no_history_entry", "Запись истории с указанным индексом не найдена"));
+    }
+    let restored = record.clone();
+
+    emit_group_records(&app, data, restored.pid)?;
+    Ok(restored)
+}
+
+/// Отмечает использование записи (например, отправку на устройство или копирование)
+///
+/// # Аргументы
+/// * `record_id` - ID записи
+/// * `state` - глобальное состояние приложения
+///
+/// # Поведение
+/// Обновляет `lastUsedAt` записи и ее группы
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены или запись не найдена
+#[tauri::command]
+pub async fn mark_record_used(record_id: u32, state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    let mut data = state.vault()?;
+    let data = &mut *data;
+
+    let record = data
+        .records
+        .iter_mut()
+        .find(|r| r.id == record_id)
+        .ok_or_else(|| CommandError::new("no_record", "Запись с указанным ID не найдена"))?;
+    record.times.touch_used();
+    let pid = record.pid;
+
+    if let Some(group) = data.groups.iter_mut().find(|g| g.id == pid) {
+        group.times.touch_used();
+    }
+
+    Ok(())
+}
+
+/// Прикрепляет файл к записи и отправляет записи группы на фронтенд
+///
+/// # Аргументы
+/// * `record_id` - ID записи
+/// * `path` - путь к прикрепляемому файлу; имя вложения берется из имени файла
+/// * `state` - глобальное состояние приложения
+///
+/// # Поведение
+/// Содержимое хранится один раз для всех записей (дедупликация по SHA-256),
+/// поэтому повторное прикрепление того же файла не увеличивает размер хранилища
+///
+/// # Возвращает
+/// Ссылку на вложение
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены, запись не найдена, файл не читается,
+/// такое содержимое уже прикреплено к записи или превышен лимит `MAX_VAULT_ATTACHMENTS_SIZE`
+#[tauri::command]
+pub async fn add_attachment(
+    app: AppHandle,
+    record_id: u32,
+    path: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<Attachment, CommandError> {
+    let mut data = state.vault()?;
+    let data = &mut *data;
+
+    let index = data
+        .records
+        .iter()
+        .position(|r| r.id == record_id)
+        .ok_or_else(|| CommandError::new("no_record", "Запись с указанным ID не найдена"))?;
+
+    let path = Path::new(&path);
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| CommandError::new("invalid_input", "Не указано имя файла"))?;
+
+    // Размер проверяется до чтения, чтобы не загружать в память заведомо слишком большой файл
+    let size = fs::metadata(path).map_err(KakaduError::from)?.len();
+    if size > MAX_VAULT_ATTACHMENTS_SIZE {
+        return Err(attachment_limit_error());
+    }
+    let content = Zeroizing::new(fs::read(path).map_err(KakaduError::from)?);
+
+    let id = attachments::content_id(&content);
+    if data.records[index].attachments.iter().any(|a| a.id == id) {
+        return Err(CommandError::new("attachment_exists", "Этот файл уже прикреплен к записи"));
+    }
+
+    // Содержимое удаленных вложений и записей не учитывается в лимите
+    data.attachments.retain_referenced(&data.records);
+    if !data.attachments.fits(&id, content.len() as u64) {
+        return Err(attachment_limit_error());
+    }
+
+    let attachment = Attachment {
+        id,
+        name,
+        size: content.len() as u64,
+        added_at: unix_time(),
+    };
+    data.attachments.insert(content);
+
+    let record = &mut data.records[index];
+    record.attachments.push(attachment.clone());
+    record.times.touch_modified();
+    let pid = record.pid;
+
+    emit_group_records(&app, data, pid)?;
+    Ok(attachment)
+}
+
+/// Сохраняет содержимое вложения в файл
+///
+/// # Аргументы
+/// * `record_id` - ID записи
+/// * `attachment_id` - ID вложения
+/// * `path` - путь к создаваемому файлу
+/// * `state` - глобальное состояние приложения
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены, запись или вложение не найдены
+/// или произошла ошибка записи
+#[tauri::command]
+pub async fn export_attachment(
+    record_id: u32,
+    attachment_id: String,
+    path: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let data = state.vault()?;
 
-    match data.as_mut() {
-        Some(data) => data
-            .records
-            .iter()
-            .position(|r| r.id == record_id)
-            .map(|index| {
-                data.records.remove(index);
-                record_id
-            })
-            .ok_or_else(|| "Запись с указанным ID не найдена".to_string()),
-        None => Err("Данные не инициализированы".to_string()),
+    let record = data
+        .records
+        .iter()
+        .find(|r| r.id == record_id)
+        .ok_or_else(|| CommandError::new("no_record", "Запись с указанным ID не найдена"))?;
+    if !record.attachments.iter().any(|a| a.id == attachment_id) {
+        return Err(CommandError::new("no_attachment", "Вложение с указанным ID не найдено"));
     }
+    let content = data
+        .attachments
+        .get(&attachment_id)
+        .ok_or_else(|| CommandError::new("no_attachment", "Содержимое вложения отсутствует в хранилище"))?;
+
+    storage::write_atomic(Path::new(&path), content, 0).map_err(KakaduError::from)?;
+
+    Ok(())
 }
 
+/// Удаляет вложение из записи и отправляет записи группы на фронтенд
+///
+/// # Аргументы
+/// * `record_id` - ID записи
+/// * `attachment_id` - ID вложения
+/// * `state` - глобальное состояние приложения
+///
+/// # Поведение
+/// Содержимое удаляется из хранилища, только если на него не ссылаются другие записи
+///
+/// # Возвращает
+/// Обновленную запись
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены, запись или вложение не найдены
+#[tauri::command]
+pub async fn remove_attachment(
+    app: AppHandle,
+    record_id: u32,
+    attachment_id: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<Record, CommandError> {
+    let mut data = state.vault()?;
+    let data = &mut *data;
+
+    let record = data
+        .records
+        .iter_mut()
+        .find(|r| r.id == record_id)
+        .ok_or_else(|| CommandError::new("no_record", "Запись с указанным ID не найдена"))?;
+
+    let before = record.attachments.len();
+    record.attachments.retain(|a| a.id != attachment_id);
+    if record.attachments.len() == before {
+        return Err(CommandError::new("no_attachment", "Вложение с указанным ID не найдено"));
+    }
+    record.times.touch_modified();
+    let updated = record.clone();
+
+    data.attachments.retain_referenced(&data.records);
+
+    emit_group_records(&app, data, updated.pid)?;
+    Ok(updated)
+}
+
+/// Ошибка превышения лимита размера вложений (`attachment_limit`)
+fn attachment_limit_error() -> CommandError {
+    CommandError::new(
+        "attachment_limit",
+        &format!(
+            "Превышен допустимый размер вложений хранилища ({} МБ)",
+            MAX_VAULT_ATTACHMENTS_SIZE / (1024 * 1024)
+        ),
+    )
+}
+
+/// Возвращает записи, срок действия которых истекает в ближайшие дни
+///
+/// # Аргументы
+/// * `within_days` - горизонт в днях; `0` - только уже истекшие записи
+/// * `state` - глобальное состояние приложения
+///
+/// # Возвращает
+/// Записи по возрастанию срока действия, включая уже истекшие.
+/// Срок группы распространяется на все вложенные записи
+///
+/// # Ошибки
+/// Возвращает ошибку если данные не загружены
+#[tauri::command]
+pub async fn expiring_records(
+    within_days: u32,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<ExpiringRecord>, CommandError> {
+    let data = state.vault()?;
+
+    Ok(expiry::expiring_records(
+        &data,
+        unix_time(),
+        u64::from(within_days) * expiry::SECONDS_PER_DAY,
+    ))
+}
 
 //Test comment
 #[tauri::command]
@@ -131,113 +818,307 @@ pub async fn new_group_command(
     parent_group_id: u32,
     group_name: String,
     state: tauri::State<'_, AppState>,
-) -> Result<Group, String> {
-    let mut data = state.password_data.lock().unwrap();
-    match data.as_mut() {
-        Some(data) => {
-            // Объединяем ID групп и записей и находим максимальный
-            let new_id = data.groups
-                .iter()
-                .map(|g| g.id)
-                .max()
-                .unwrap_or(0) + 1;
+) -> Result<Group, CommandError> {
+    let mut data = state.vault()?;
 
-            let new_group_obj = Group {
-                id: new_id,
-                pid: parent_group_id,
-                name: group_name,
-            };
+    // ID выдается общим для групп и записей счетчиком
+    let new_id = data.allocate_id()?;
 
-            data.groups.push(new_group_obj.clone());
-            // Отправка групп через Tauri-событие на фронтенд
-            emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
-            Ok(new_group_obj)
-        },
-        None => Err("Данные не инициализированы".to_string()),
-    }
+    let new_group_obj = Group::new(new_id, parent_group_id, group_name);
+
+    data.groups.push(new_group_obj.clone());
+    // Отправка групп через Tauri-событие на фронтенд
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+    Ok(new_group_obj)
 }
 
+/// Переименовывает группу и отправляет обновленный список групп на фронтенд
+///
+/// # Аргументы
+/// * `group_id` - ID группы
+/// * `new_name` - новое название
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены, группа не найдена или название пустое
 #[tauri::command]
 pub async fn edit_group(
+    app: AppHandle,
     group_id: u32,
     new_name: String,
     state: tauri::State<'_, AppState>,
-) -> Result<Group, String> {
-    let mut data = state.password_data.lock().unwrap();
-    match data.as_mut() {
-        Some(data) => {
-            data.groups
-                .iter_mut()
-                .find(|g| g.id == group_id)
-                .map(|group| {
-                    group.name = new_name.clone();
-                    group.clone()
-                })
-                .ok_or_else(|| "Группа с указанным ID не найдена".to_string())
-        },
-        None => Err("Данные не инициализированы".to_string()),
+) -> Result<Group, CommandError> {
+    if new_name.trim().is_empty() {
+        return Err(CommandError::new("empty_name", "Название группы не может быть пустым"));
     }
+
+    let mut data = state.vault()?;
+    let group = data
+        .groups
+        .iter_mut()
+        .find(|g| g.id == group_id)
+        .map(|group| {
+            group.name = new_name.clone();
+            group.times.touch_modified();
+            group.clone()
+        })
+        .ok_or_else(|| CommandError::new("no_group", "Группа с указанным ID не найдена"))?;
+
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+    Ok(group)
 }
 
+/// Задает или снимает срок действия группы
+///
+/// # Аргументы
+/// * `group_id` - ID группы
+/// * `expires_at` - срок (секунды с UNIX-эпохи); `null` снимает срок
+/// * `state` - глобальное состояние приложения
+///
+/// # Поведение
+/// Срок группы распространяется на все вложенные записи (см. `expiring_records`)
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены или группа не найдена
+#[tauri::command]
+pub async fn set_group_expiry(
+    app: AppHandle,
+    group_id: u32,
+    expires_at: Option<u64>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Group, CommandError> {
+    let mut data = state.vault()?;
+    let data = &mut *data;
+
+    let group = data
+        .groups
+        .iter_mut()
+        .find(|g| g.id == group_id)
+        .ok_or_else(|| CommandError::new("no_group", "Группа с указанным ID не найдена"))?;
+    group.times.expires_at = expires_at;
+    group.times.touch_modified();
+    let group = group.clone();
+
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+    Ok(group)
+}
+
+/// Удаляет группу и отправляет обновленный список групп на фронтенд
+///
+/// # Аргументы
+/// * `group_id` - ID удаляемой группы
+/// * `mode` - что делать с содержимым: `cascade`, `reparent` или `refuse` (по умолчанию)
+///
+/// # Возвращает
+/// ID удаленной группы
+///
+/// # Ошибки
+/// `no_data`, а также `no_group`, `root_group` и `group_not_empty` (режим `refuse`) -
+/// см. `PasswordData::delete_group`
 #[tauri::command]
 pub async fn delete_group(
+    app: AppHandle,
+    group_id: u32,
+    mode: Option<DeleteGroupMode>,
+    state: tauri::State<'_, AppState>,
+) -> Result<u32, CommandError> {
+    let mut data = state.vault()?;
+    data.delete_group(group_id, mode.unwrap_or_default())?;
+
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+    Ok(group_id)
+}
+
+/// Переносит группу в другую родительскую группу (drag-and-drop в дереве)
+///
+/// # Аргументы
+/// * `group_id` - ID переносимой группы
+/// * `new_parent_id` - ID новой родительской группы
+///
+/// # Поведение
+/// Отправляет на фронтенд обновленный список групп и записи перенесенной группы
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены, группа или новый родитель не найдены,
+/// группа корневая или перенос создает цикл (группа становится потомком самой себя)
+#[tauri::command]
+pub async fn move_group(
+    app: AppHandle,
     group_id: u32,
+    new_parent_id: u32,
     state: tauri::State<'_, AppState>,
-) -> Result<u32, String> {
-    let mut data = state.password_data.lock().unwrap();
-    match data.as_mut() {
-        Some(data) => data
-            .groups
-            .iter()
-            .position(|g| g.id == group_id)
-            .map(|index| {
-                data.groups.remove(index);
-                group_id
-            })
-            .ok_or_else(|| "Группа с указанным ID не найдена".to_string()),
-        None => Err("Данные не инициализированы".to_string()),
+) -> Result<Group, CommandError> {
+    let mut data = state.vault()?;
+    let data = &mut *data;
+    let group = data.move_group(group_id, new_parent_id)?.clone();
+
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+    emit_group_records(&app, data, group_id)?;
+    Ok(group)
+}
+
+/// Переносит записи в другую группу (drag-and-drop из таблицы в дерево)
+///
+/// # Аргументы
+/// * `ids` - ID переносимых записей
+/// * `new_group_id` - ID целевой группы
+///
+/// # Поведение
+/// Перенос выполняется целиком или не выполняется вовсе. Отправляет на фронтенд
+/// обновленный список групп и записи каждой затронутой группы: сначала целевой,
+/// затем исходных. Последними отправляются записи группы, из которой перенесена
+/// первая запись, - обычно именно она открыта в таблице
+///
+/// # Возвращает
+/// ID перенесенных записей
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены, целевая группа или одна из записей не найдены
+#[tauri::command]
+pub async fn move_records(
+    app: AppHandle,
+    ids: Vec<u32>,
+    new_group_id: u32,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<u32>, CommandError> {
+    let mut data = state.vault()?;
+    let data = &mut *data;
+    let source_groups = data.move_records(&ids, new_group_id)?;
+
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+    if !source_groups.contains(&new_group_id) {
+        emit_group_records(&app, data, new_group_id)?;
     }
+    for group_id in source_groups.iter().rev() {
+        emit_group_records(&app, data, *group_id)?;
+    }
+    Ok(ids)
 }
 
+/// Проверяет целостность загруженного хранилища
+///
+/// # Возвращает
+/// Отчет с проблемами: повторяющиеся ID, отсутствие или несколько корней,
+/// группы и записи без родителя, циклы в иерархии групп
+///
+/// # Ошибки
+/// Возвращает ошибку если данные не загружены
 #[tauri::command]
-pub async fn get_groups(
+pub async fn validate_vault(state: tauri::State<'_, AppState>) -> Result<VaultReport, CommandError> {
+    let data = state.vault()?;
+
+    Ok(integrity::validate_vault(&data))
+}
+
+/// Исправляет структуру загруженного хранилища и отправляет группы на фронтенд
+///
+/// # Поведение
+/// Потерянные группы и записи переносятся в группу `Recovered` внутри корня.
+/// Подробности порядка исправлений описаны в `integrity::repair_vault`
+///
+/// # Возвращает
+/// Отчет с исправленными проблемами
+///
+/// # Ошибки
+/// Возвращает ошибку если данные не загружены
+#[tauri::command]
+pub async fn repair_vault(
     app: AppHandle,
-    state: tauri::State<'_, AppState>) -> Result<(), String>{
-    let data = state.password_data.lock().unwrap();
+    state: tauri::State<'_, AppState>,
+) -> Result<VaultReport, CommandError> {
+    let mut data = state.vault()?;
+    let data = &mut *data;
 
-    let groups = match &*data {
-        Some(data) => data
-            .groups
-            .iter()
-            .cloned()
-            .collect::<Vec<Group>>(),
-        None => return Err("Данные не загружены в систему".to_string()),
-    };
+    let report = integrity::repair_vault(data)?;
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+
+    Ok(report)
+}
+
+/// Возвращает иерархию групп с количеством записей и полными путями
+///
+/// # Возвращает
+/// Вложенные узлы начиная с корневых групп. Каждый узел содержит путь (breadcrumb)
+/// от корня, число записей в самой группе и во всем поддереве. Группы, замкнутые
+/// в цикл, возвращаются под синтетическим узлом с `id == 0` (см. `GroupTree::to_nodes`)
+///
+/// # Ошибки
+/// Возвращает ошибку если данные не загружены
+#[tauri::command]
+pub async fn get_group_tree(state: tauri::State<'_, AppState>) -> Result<Vec<GroupNode>, CommandError> {
+    let data = state.vault()?;
+
+    Ok(GroupTree::new(&data).to_nodes())
+}
+
+/// Ищет записи по названию, логину и URL с нечетким сопоставлением
+///
+/// # Аргументы
+/// * `query` - строка поиска; регистр и диакритические знаки не учитываются
+/// * `options` - область поиска (`group_id` - группа с потомками), лимит результатов
+///   и `include_passwords` для поиска по паролям (по умолчанию пароли не просматриваются)
+/// * `state` - глобальное состояние приложения
+///
+/// # Возвращает
+/// Записи без паролей с путем группы (breadcrumb), по убыванию релевантности
+///
+/// # Ошибки
+/// Возвращает ошибку если: данные не загружены или группа для области поиска не найдена
+#[tauri::command]
+pub async fn search_records(
+    query: String,
+    options: Option<SearchOptions>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<SearchResult>, CommandError> {
+    let data = state.vault()?;
 
-    emit_event(&app, "get_groups_listen", &groups, "Ошибка отправки записей")?;
+    let options = options.unwrap_or_default();
+    if let Some(group_id) = options.group_id {
+        if !data.has_group(group_id) {
+            return Err(CommandError::new("no_group", "Группа с указанным ID не найдена"));
+        }
+    }
+
+    Ok(search::search_records(&data, &query, &options))
+}
+
+#[tauri::command]
+pub async fn get_groups(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>) -> Result<(), CommandError>{
+    let data = state.vault()?;
+
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки записей")?;
 
     Ok(())
 }
 
 /// Создает новую пустую базу данных в состоянии и отправляет группы на фронтенд
 /// Очищает `records` и оставляет одну корневую группу `NewDatabase`
+///
+/// Если передан `password`, сразу получает ключ сессии; путь к файлу сбрасывается
+/// до первого `save_file`
 #[tauri::command]
 pub async fn new_file(
     app: AppHandle,
+    password: Option<MasterPassword>,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
-    let mut data_guard = state
-        .password_data
-        .lock()
-        .map_err(|e| format!("Ошибка блокировки Mutex: {}", e))?;
+) -> Result<(), CommandError> {
+    let session = password
+        .as_ref()
+        .map(|p| KakaduProvider::default().derive_session_key(p))
+        .transpose()?;
+
+    let mut data_guard = state.password_data.lock().unwrap();
+    *state.session_key.lock().unwrap() = session;
+    *state.vault_path.lock().unwrap() = None;
+    *state.saved_digest.lock().unwrap() = None;
+    state.touch();
 
     *data_guard = Some(PasswordData {
-        groups: vec![Group {
-            id: 1,
-            pid: 0,
-            name: "NewDatabase".to_string(),
-        }],
+        groups: vec![Group::new(1, 0, "NewDatabase".to_string())],
         records: Vec::new(),
+        next_id: 2,
+        attachments: AttachmentStore::default(),
     });
 
     // Безопасно брать ссылку после установки значения
@@ -249,4 +1130,130 @@ pub async fn new_file(
     let empty_records: Vec<Record> = Vec::new();
     emit_event(&app, "get_records_listen", &empty_records, "Ошибка отправки записей")?;
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const SECRET: &str = "GEZDGNBVGY3TQOJQ";
+
+    fn vault() -> PasswordData {
+        PasswordData {
+            groups: vec![Group::new(1, 0, "Root".to_string())],
+            records: Vec::new(),
+            next_id: 2,
+            attachments: AttachmentStore::default(),
+        }
+    }
+
+    fn input(extra: Value) -> RecordInput {
+        let mut value = json!({
+            "pid": 1, "name": "Почта", "login": "bob", "password": "hunter2", "url": "",
+            "loginSymbol": "TAB", "passwordSymbol": "ENTER", "urlSymbol": "NONE"
+        });
+        value.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// Запись вида Wi-Fi с HOTP, скрытым дополнительным полем, заметками и метками,
+    /// как после нескольких выдач кода
+    fn stored(data: &PasswordData) -> Record {
+        let mut record = input(json!({
+            "otp": format!("otpauth://hotp/bob?secret={}&counter=0", SECRET),
+            "customFields": [{ "name": "PIN", "value": "1234", "hidden": true }],
+            "kind": { "type": "wifi", "ssid": "Office" },
+            "notes": "Резервные коды в сейфе",
+            "tags": ["work", " mail "],
+            "expiresAt": 1_900_000_000u64
+        }))
+        .into_record(2, data, None)
+        .unwrap();
+        record.otp.as_mut().unwrap().counter = 7;
+        record
+    }
+
+    #[test]
+    fn omitted_fields_keep_stored_values() {
+        let data = vault();
+        let previous = stored(&data);
+
+        let updated = input(json!({ "name": "Новая почта" })).into_record(2, &data, Some(&previous)).unwrap();
+
+        assert_eq!(updated.name, "Новая почта");
+        assert_eq!(updated.kind, previous.kind);
+        assert_eq!(updated.otp, previous.otp);
+        assert_eq!(updated.custom_fields, previous.custom_fields);
+        assert!(updated.custom_fields[0].hidden);
+        assert_eq!(updated.notes, "Резервные коды в сейфе");
+        assert_eq!(updated.tags, previous.tags);
+        assert_eq!(updated.times.expires_at, Some(1_900_000_000));
+    }
+
+    #[test]
+    fn explicit_values_replace_stored_ones() {
+        let data = vault();
+        let previous = stored(&data);
+
+        let updated = input(json!({ "kind": { "type": "login" }, "otp": null, "customFields": [], "notes": "", "tags": [], "expiresAt": null }))
+            .into_record(2, &data, Some(&previous))
+            .unwrap();
+
+        assert!(updated.kind.is_login());
+        assert_eq!(updated.otp, None);
+        assert_eq!(updated.times.expires_at, None);
+        assert!(updated.custom_fields.is_empty());
+        assert!(updated.notes.is_empty());
+        assert!(updated.tags.is_empty());
+
+        let extended = input(json!({ "expiresAt": 2_000_000_000u64 })).into_record(2, &data, Some(&previous)).unwrap();
+        assert_eq!(extended.times.expires_at, Some(2_000_000_000));
+    }
+
+    #[test]
+    fn hotp_counter_is_never_taken_from_input() {
+        let data = vault();
+        let previous = stored(&data);
+
+        let uri = format!("otpauth://hotp/bob?secret={}&counter=0&digits=8", SECRET);
+        let updated = input(json!({ "otp": uri })).into_record(2, &data, Some(&previous)).unwrap();
+        let otp = updated.otp.unwrap();
+        assert_eq!(otp.digits, 8);
+        assert_eq!(otp.counter, 7);
+
+        // Переход на TOTP и новая запись не наследуют счетчик
+        let totp = input(json!({ "otp": format!("otpauth://totp/bob?secret={}", SECRET) }))
+            .into_record(2, &data, Some(&previous))
+            .unwrap();
+        assert_eq!(totp.otp.unwrap().kind, OtpKind::Totp);
+
+        let created = input(json!({ "otp": format!("otpauth://hotp/bob?secret={}&counter=3", SECRET) }))
+            .into_record(3, &data, None)
+            .unwrap();
+        assert_eq!(created.otp.unwrap().counter, 3);
+    }
+
+    #[test]
+    fn new_records_default_missing_fields() {
+        let record = input(json!({})).into_record(2, &vault(), None).unwrap();
+
+        assert_eq!(record.otp, None);
+        assert!(record.custom_fields.is_empty());
+        assert!(record.kind.is_login());
+        assert!(record.notes.is_empty());
+        assert!(record.tags.is_empty());
+        assert_eq!(record.times.expires_at, None);
+    }
+
+    #[test]
+    fn invalid_input_has_stable_codes() {
+        let data = vault();
+        let code = |extra: Value| input(extra).into_record(2, &data, None).unwrap_err().code;
+
+        assert_eq!(code(json!({ "name": " " })), "empty_name");
+        assert_eq!(code(json!({ "pid": 42 })), "no_group");
+        assert_eq!(code(json!({ "customFields": [{ "name": "", "value": "" }] })), "empty_name");
+        assert_eq!(code(json!({ "loginSymbol": "SPACEBAR" })), "invalid_input");
+        assert_eq!(code(json!({ "otp": "otpauth://totp/x?secret=1" })), "invalid_input");
+    }
+}
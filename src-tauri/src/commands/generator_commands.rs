@@ -0,0 +1,19 @@
+use crate::modules::generator::{self, GeneratedPassword, PasswordPolicy};
+use crate::utils::CommandError;
+
+/// Генерирует пароль по политике
+///
+/// # Аргументы
+/// * `policy` - режим (`random`, `pronounceable`, `passphrase`) и его параметры;
+///   без политики генерируется случайный пароль из 20 символов всех классов
+///
+/// # Возвращает
+/// Пароль, его длину и оценку энтропии в битах
+///
+/// # Ошибки
+/// * `invalid_input` - политика противоречива или пароль не помещается
+///   в поле устройства (`DEVICE_MAX_FIELD_LEN`)
+#[tauri::command]
+pub async fn generate_password(policy: Option<PasswordPolicy>) -> Result<GeneratedPassword, CommandError> {
+    generator::generate_password(&policy.unwrap_or_default()).map_err(CommandError::invalid_input)
+}
@@ -0,0 +1,105 @@
+use crate::modules::kakadu_file_module::secret::{ExposeSecret, MasterPassword};
+use crate::modules::kakadu_file_module::storage;
+use crate::modules::kakadu_file_module::KakaduError;
+use crate::modules::kdbx::{self, KdbxImportSummary, KdbxKey, KdbxOptions};
+use crate::state::AppState;
+use crate::utils::{emit_event, CommandError};
+use std::fs;
+use std::path::Path;
+use tauri::AppHandle;
+use zeroize::Zeroizing;
+
+/// Импортирует базу KeePass (KDBX 4) в открытое хранилище и отправляет группы на фронтенд
+///
+/// # Аргументы
+/// * `app` - экземпляр AppHandle для взаимодействия с Tauri
+/// * `path` - путь к файлу .kdbx
+/// * `password` - мастер-пароль базы KeePass
+/// * `key_file` - путь к файлу-ключу KeePass
+/// * `group_id` - группа, в которую импортируется содержимое; если не задана,
+///   создается новая корневая группа с именем базы
+/// * `state` - глобальное состояние приложения
+///
+/// # Поведение
+/// Группам и записям выдаются новые ID, существующие данные не изменяются.
+/// При любой ошибке хранилище остается в прежнем состоянии
+///
+/// # Ошибки
+/// Возвращает `CommandError`: `no_data`, `no_key` (не задан ни пароль, ни файл-ключ),
+/// `no_group`, а также коды ошибок чтения файла (`wrong_password`, `corrupted`, `io`, ...)
+#[tauri::command]
+pub async fn import_kdbx(
+    app: AppHandle,
+    path: String,
+    password: Option<MasterPassword>,
+    key_file: Option<String>,
+    group_id: Option<u32>,
+    state: tauri::State<'_, AppState>,
+) -> Result<KdbxImportSummary, CommandError> {
+    let mut data = state.vault()?;
+    let data = &mut *data;
+
+    if let Some(id) = group_id {
+        if !data.groups.iter().any(|g| g.id == id) {
+            return Err(CommandError::new("no_group", "Группа с указанным ID не найдена"));
+        }
+    }
+
+    let key = composite_key(password.as_ref(), key_file.as_deref())?;
+    let file = Zeroizing::new(fs::read(&path).map_err(KakaduError::from)?);
+    let summary = kdbx::import_kdbx(data, &file, &key, group_id)?;
+
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+
+    Ok(summary)
+}
+
+/// Экспортирует открытое хранилище в базу KeePass (KDBX 4)
+///
+/// # Аргументы
+/// * `path` - путь к создаваемому файлу .kdbx
+/// * `password` - мастер-пароль новой базы
+/// * `key_file` - путь к файлу-ключу для новой базы
+/// * `options` - шифр и KDF; по умолчанию ChaCha20 и Argon2id
+/// * `state` - глобальное состояние приложения
+///
+/// # Поведение
+/// Символы автоввода и вид записи сохраняются в `CustomData` записей KeePass,
+/// поэтому повторный импорт восстанавливает их без потерь
+///
+/// # Ошибки
+/// Возвращает `CommandError`: `no_data`, `no_key`, `io`
+#[tauri::command]
+pub async fn export_kdbx(
+    path: String,
+    password: Option<MasterPassword>,
+    key_file: Option<String>,
+    options: Option<KdbxOptions>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let data = state.vault()?;
+
+    let key = composite_key(password.as_ref(), key_file.as_deref())?;
+    let bytes = kdbx::export_kdbx(&data, &key, options.unwrap_or_default())?;
+
+    storage::write_atomic(Path::new(&path), &bytes, 0).map_err(KakaduError::from)?;
+
+    Ok(())
+}
+
+/// Собирает составной ключ KeePass из пароля и файла-ключа
+fn composite_key(password: Option<&MasterPassword>, key_file: Option<&str>) -> Result<KdbxKey, CommandError> {
+    if password.is_none() && key_file.is_none() {
+        return Err(CommandError::new("no_key", "Не задан ни пароль, ни файл-ключ"));
+    }
+
+    let key_file = key_file
+        .map(|path| fs::read(path).map(Zeroizing::new))
+        .transpose()
+        .map_err(KakaduError::from)?;
+
+    Ok(KdbxKey::new(
+        password.map(|p| p.expose_secret()),
+        key_file.as_ref().map(|k| k.as_slice()),
+    )?)
+}
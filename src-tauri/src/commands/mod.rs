@@ -1,2 +1,7 @@
 pub mod app_commands;
+pub mod audit_commands;
 pub mod file_commands;
+pub mod generator_commands;
+pub mod kdbx_commands;
+pub mod totp_commands;
+pub mod vault_commands;
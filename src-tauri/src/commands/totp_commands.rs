@@ -0,0 +1,71 @@
+use crate::modules::kakadu_file_module::secret::SecretField;
+use crate::modules::kakadu_file_module::unix_time;
+use crate::modules::totp::{OtpCode, OtpKind};
+use crate::state::AppState;
+use crate::utils::CommandError;
+
+/// Возвращает текущий одноразовый пароль записи
+///
+/// # Аргументы
+/// * `record_id` - ID записи с параметрами OTP
+/// * `state` - глобальное состояние приложения
+///
+/// # Поведение
+/// Для TOTP код вычисляется по текущему времени. Для HOTP используется сохраненный
+/// счетчик, который затем увеличивается (изменение остается несохраненным)
+///
+/// # Возвращает
+/// Код и для TOTP - количество секунд до его смены
+///
+/// # Ошибки
+/// * `no_data` - данные не загружены
+/// * `no_record` - запись не найдена
+/// * `no_otp` - у записи нет параметров OTP
+/// * `invalid_otp` - сохраненные параметры OTP некорректны
+#[tauri::command]
+pub async fn get_otp_code(record_id: u32, state: tauri::State<'_, AppState>) -> Result<OtpCode, CommandError> {
+    let mut data = state.vault()?;
+
+    let record = data
+        .records
+        .iter_mut()
+        .find(|r| r.id == record_id)
+        .ok_or_else(|| CommandError::new("no_record", "Запись с указанным ID не найдена"))?;
+    let otp = record
+        .otp
+        .as_mut()
+        .ok_or_else(|| CommandError::new("no_otp", "Для записи не заданы параметры OTP"))?;
+
+    let code = otp
+        .code_at(unix_time())
+        .map_err(|e| CommandError::new("invalid_otp", &e))?;
+    if otp.kind == OtpKind::Hotp {
+        otp.counter += 1;
+        record.times.touch_modified();
+    }
+    record.times.touch_used();
+
+    Ok(code)
+}
+
+/// Возвращает URI `otpauth://` записи для экспорта или отображения QR-кода
+///
+/// # Ошибки
+/// * `no_data` - данные не загружены
+/// * `no_record` - запись не найдена
+/// * `no_otp` - у записи нет параметров OTP
+#[tauri::command]
+pub async fn get_otp_uri(record_id: u32, state: tauri::State<'_, AppState>) -> Result<SecretField, CommandError> {
+    let data = state.vault()?;
+
+    let otp = data
+        .records
+        .iter()
+        .find(|r| r.id == record_id)
+        .ok_or_else(|| CommandError::new("no_record", "Запись с указанным ID не найдена"))?
+        .otp
+        .as_ref()
+        .ok_or_else(|| CommandError::new("no_otp", "Для записи не заданы параметры OTP"))?;
+
+    Ok(SecretField::from(otp.to_uri().to_string()))
+}
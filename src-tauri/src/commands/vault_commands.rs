@@ -0,0 +1,218 @@
+use crate::modules::kakadu_file_module::secret::{ExposeSecret, MasterPassword};
+use crate::modules::kakadu_file_module::storage::BackupInfo;
+use crate::modules::kakadu_file_module::{KakaduProvider, PasswordData};
+use crate::commands::file_commands::check_loaded_vault;
+use crate::state::AppState;
+use crate::utils::{emit_event, CommandError};
+use std::time::Duration;
+use tauri::AppHandle;
+
+/// Блокирует хранилище по запросу пользователя
+///
+/// # Поведение
+/// Сохраняет несохраненные изменения ключом сессии, затирает расшифрованные данные
+/// и ключ, отправляет событие `vault_locked`. Путь к файлу сохраняется для `unlock_vault`
+///
+/// # Ошибки
+/// `no_path`, `no_session` и коды `KakaduError` - см. `AppState::lock_vault`
+#[tauri::command]
+pub async fn lock_vault(app: AppHandle, state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    state.lock_vault(&app)
+}
+
+/// Разблокирует хранилище, повторно открывая файл по сохраненному пути
+///
+/// # Аргументы
+/// * `password` - мастер-пароль
+///
+/// # Ошибки
+/// * `not_locked` - хранилище уже разблокировано
+/// * `no_path` - нет сохраненного пути (хранилище не открывалось из файла)
+/// * коды `KakaduError` - как у `open_file`
+#[tauri::command]
+pub async fn unlock_vault(
+    app: AppHandle,
+    password: MasterPassword,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), CommandError> {
+    if state.password_data.lock().unwrap().is_some() {
+        return Err(CommandError::new("not_locked", "Хранилище уже разблокировано"));
+    }
+
+    let path = state
+        .vault_path
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| CommandError::new("no_path", "Путь к файлу хранилища не задан"))?;
+
+    let provider = KakaduProvider::default();
+    let (data, session) = provider.open_session(&path, &password)?;
+
+    let mut data_guard = state.password_data.lock().unwrap();
+    let data = data_guard.insert(data);
+    *state.session_key.lock().unwrap() = session;
+    state.mark_saved(data);
+    state.touch();
+    check_loaded_vault(&app, data)?;
+
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+
+    Ok(())
+}
+
+/// Задает таймаут автоблокировки
+///
+/// # Аргументы
+/// * `seconds` - время бездействия в секундах; `0` отключает автоблокировку
+#[tauri::command]
+pub async fn set_lock_timeout(seconds: u64, state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    *state.lock_timeout.lock().unwrap() = match seconds {
+        0 => None,
+        s => Some(Duration::from_secs(s)),
+    };
+    state.touch();
+
+    Ok(())
+}
+
+/// Сообщает о действии пользователя (ввод, клик) и сбрасывает таймер автоблокировки
+#[tauri::command]
+pub async fn report_activity(state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    state.touch();
+    Ok(())
+}
+
+/// Меняет мастер-пароль открытого хранилища
+///
+/// # Аргументы
+/// * `old_password` - текущий пароль; проверяется по файлу на диске
+/// * `new_password` - новый пароль
+///
+/// # Поведение
+/// Файл перешифровывается с новым паролем и параметрами KDF по умолчанию
+/// (обновляя устаревшие параметры) и записывается атомарно с резервной копией.
+/// Если в памяти нет несохраненных изменений, перешифровывается содержимое файла;
+/// иначе - данные из памяти, при условии что файл не менялся с момента открытия.
+/// Резервные копии, открывающиеся старым паролем, перешифровываются новым
+/// (см. `KakaduProvider::reencrypt_backups`)
+///
+/// # Возвращает
+/// Резервные копии, оставшиеся под другим паролем: их стоит удалить или восстановить вручную
+///
+/// # Ошибки
+/// * `no_path` / `no_data` - хранилище не открыто из файла или заблокировано
+/// * `empty_password` - новый пароль пуст
+/// * `conflict` - есть несохраненные изменения, а файл на диске изменился
+/// * коды `KakaduError` - например, `wrong_password` для неверного старого пароля
+#[tauri::command]
+pub async fn change_master_password(
+    app: AppHandle,
+    old_password: MasterPassword,
+    new_password: MasterPassword,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<BackupInfo>, CommandError> {
+    if new_password.expose_secret().is_empty() {
+        return Err(CommandError::new("empty_password", "Новый пароль не может быть пустым"));
+    }
+
+    let path = state
+        .vault_path
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| CommandError::new("no_path", "Путь к файлу хранилища не задан"))?;
+
+    // Открытие файла старым паролем одновременно проверяет пароль
+    let provider = state.provider();
+    let (disk_data, _) = provider.open_session(&path, &old_password)?;
+
+    let mut data_guard = state.password_data.lock().unwrap();
+    let data = data_guard
+        .as_ref()
+        .ok_or_else(|| CommandError::new("no_data", "Данные не загружены"))?;
+    state.touch();
+
+    let from_memory = rekey_from_memory(data, &disk_data, *state.saved_digest.lock().unwrap())?;
+    let session = provider.derive_session_key(&new_password)?;
+
+    if from_memory {
+        provider.save_with_key(path.clone(), &session, data)?;
+    } else {
+        provider.save_with_key(path.clone(), &session, &disk_data)?;
+        *data_guard = Some(disk_data);
+    }
+
+    let data = data_guard.as_ref().unwrap();
+    state.mark_saved(data);
+    emit_event(&app, "get_groups_listen", &data.groups, "Ошибка отправки групп")?;
+
+    // Ключ сессии остается доступным и для перешифровки копий; данные отпускаются,
+    // чтобы долгий перебор копий не блокировал остальные команды
+    let mut session_guard = state.session_key.lock().unwrap();
+    let session = session_guard.insert(session);
+    drop(data_guard);
+
+    Ok(provider.reencrypt_backups(&path, &old_password, session)?)
+}
+
+/// Выбирает данные для перешифровки при смене мастер-пароля
+///
+/// # Аргументы
+/// * `data` - данные в памяти
+/// * `disk_data` - содержимое файла, открытого старым паролем
+/// * `saved_digest` - отпечаток данных на момент открытия или последнего сохранения
+///
+/// # Возвращает
+/// `true` - сохранить данные из памяти (есть несохраненные изменения),
+/// `false` - перешифровать содержимое файла
+///
+/// # Ошибки
+/// `conflict`, если есть несохраненные изменения, а файл на диске изменился
+fn rekey_from_memory(data: &PasswordData, disk_data: &PasswordData, saved_digest: Option<[u8; 32]>) -> Result<bool, CommandError> {
+    let is_dirty = Some(data.digest()?) != saved_digest;
+    let disk_changed = Some(disk_data.digest()?) != saved_digest;
+
+    if is_dirty && disk_changed {
+        return Err(CommandError::new(
+            "conflict",
+            "Файл на диске изменился, а в хранилище есть несохраненные изменения",
+        ));
+    }
+
+    Ok(is_dirty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::kakadu_file_module::attachments::AttachmentStore;
+    use crate::modules::kakadu_file_module::Group;
+
+    fn vault(name: &str) -> PasswordData {
+        PasswordData {
+            groups: vec![Group::new(1, 0, name.to_string())],
+            records: Vec::new(),
+            next_id: 2,
+            attachments: AttachmentStore::default(),
+        }
+    }
+
+    #[test]
+    fn rekey_source_depends_on_unsaved_changes_and_disk() {
+        let saved = vault("Root");
+        let digest = Some(saved.digest().unwrap());
+        let (edited, changed_on_disk) = (vault("Edited"), vault("Disk"));
+
+        // Нет изменений: перешифровывается файл, даже если он изменился на диске
+        assert!(!rekey_from_memory(&saved, &saved, digest).unwrap());
+        assert!(!rekey_from_memory(&saved, &changed_on_disk, digest).unwrap());
+
+        // Несохраненные изменения при неизменном файле сохраняются из памяти
+        assert!(rekey_from_memory(&edited, &saved, digest).unwrap());
+
+        // Изменения есть и файл изменился - конфликт
+        assert_eq!(rekey_from_memory(&edited, &changed_on_disk, digest).unwrap_err().code, "conflict");
+        assert_eq!(rekey_from_memory(&edited, &saved, None).unwrap_err().code, "conflict");
+    }
+}
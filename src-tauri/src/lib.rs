@@ -17,17 +17,53 @@ pub fn run() {
         .setup(|app| {
             let state = app.state::<ComPortState>();
             modules::com_port::start_com_port_monitor(state.clone());
+            modules::auto_lock::start_auto_lock_monitor(app.handle().clone());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::app_commands::exit_app,
             commands::file_commands::open_file,
             commands::file_commands::save_file,
+            commands::file_commands::save_current,
+            commands::file_commands::list_backups,
+            commands::file_commands::restore_backup,
+            commands::file_commands::set_backup_count,
             commands::file_commands::get_records_by_group,
+            commands::file_commands::get_records_by_tag,
             commands::file_commands::delete_record,
+            commands::file_commands::create_record,
+            commands::file_commands::update_record,
+            commands::file_commands::get_password_history,
+            commands::file_commands::restore_password_from_history,
+            commands::file_commands::mark_record_used,
+            commands::file_commands::add_attachment,
+            commands::file_commands::export_attachment,
+            commands::file_commands::remove_attachment,
+            commands::file_commands::expiring_records,
             commands::file_commands::get_groups,
+            commands::file_commands::get_group_tree,
+            commands::file_commands::search_records,
             commands::file_commands::new_group_command,
+            commands::file_commands::edit_group,
+            commands::file_commands::set_group_expiry,
+            commands::file_commands::delete_group,
+            commands::file_commands::move_group,
+            commands::file_commands::move_records,
+            commands::file_commands::validate_vault,
+            commands::file_commands::repair_vault,
             commands::file_commands::new_file,
+            commands::audit_commands::audit_vault,
+            commands::audit_commands::check_breached_passwords,
+            commands::generator_commands::generate_password,
+            commands::kdbx_commands::import_kdbx,
+            commands::kdbx_commands::export_kdbx,
+            commands::totp_commands::get_otp_code,
+            commands::totp_commands::get_otp_uri,
+            commands::vault_commands::lock_vault,
+            commands::vault_commands::unlock_vault,
+            commands::vault_commands::set_lock_timeout,
+            commands::vault_commands::report_activity,
+            commands::vault_commands::change_master_password,
             modules::com_port::is_com_connected,
             modules::com_port::get_connected_port_name
         ])
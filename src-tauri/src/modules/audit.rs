@@ -0,0 +1,297 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::modules::kakadu_file_module::expiry::SECONDS_PER_DAY;
+use crate::modules::kakadu_file_module::tree::GroupTree;
+use crate::modules::kakadu_file_module::{PasswordData, Record};
+
+#[cfg(feature = "audit")]
+pub mod hibp;
+pub mod strength;
+
+use strength::estimate_strength;
+
+/// Пароли с оценкой ниже этой считаются слабыми
+pub const WEAK_SCORE_THRESHOLD: u8 = 3;
+
+/// Пароли, не менявшиеся дольше этого срока, считаются устаревшими
+pub const MAX_PASSWORD_AGE_DAYS: u64 = 365;
+
+/// Проблема безопасности записи
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditFinding {
+    /// Пароль легко подобрать
+    WeakPassword { score: u8 },
+    /// Тот же пароль используется в других записях
+    ReusedPassword { record_ids: Vec<u32> },
+    /// Пароль не менялся дольше `MAX_PASSWORD_AGE_DAYS`
+    OldPassword { age_days: u64 },
+    /// Пароль не задан
+    EmptyPassword,
+    /// Не указан URL, поэтому запись нельзя сопоставить с сайтом
+    MissingUrl,
+    /// Пароль совпадает с логином
+    PasswordEqualsLogin,
+}
+
+impl AuditFinding {
+    /// Относится ли проблема к самому паролю (а не к заполненности записи)
+    fn is_password_issue(&self) -> bool {
+        !matches!(self, AuditFinding::MissingUrl)
+    }
+}
+
+/// Результат проверки одной записи
+#[derive(Debug, Serialize, Clone)]
+pub struct RecordAudit {
+    pub record_id: u32,              // ID записи
+    pub name: String,                // Название записи
+    pub path: Vec<String>,           // Путь (breadcrumb) группы записи
+    pub score: Option<u8>,           // Оценка стойкости 0..4 (нет для пустого пароля)
+    pub findings: Vec<AuditFinding>, // Найденные проблемы
+}
+
+/// Отчет о «здоровье» хранилища
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct AuditReport {
+    pub records: Vec<RecordAudit>,    // Записи, в которых найдены проблемы
+    pub total: usize,                 // Всего записей
+    pub weak: usize,                  // Записей со слабым паролем
+    pub reused: usize,                // Записей с повторяющимся паролем
+    pub old: usize,                   // Записей с устаревшим паролем
+    pub empty: usize,                 // Записей без пароля
+    pub missing_url: usize,           // Записей без URL
+    pub password_equals_login: usize, // Записей с паролем, равным логину
+    pub average_score: f64,           // Средняя оценка стойкости непустых паролей
+    pub health_score: u8,             // Доля записей без проблем с паролем, 0..100
+}
+
+/// Запись, пароль которой найден в базе утечек
+#[derive(Debug, Serialize, Clone)]
+pub struct BreachedRecord {
+    pub record_id: u32,    // ID записи
+    pub name: String,      // Название записи
+    pub path: Vec<String>, // Путь (breadcrumb) группы записи
+    pub count: u64,        // Сколько раз пароль встречался в утечках
+}
+
+/// Проверяет все записи хранилища на момент `now` (секунды с UNIX-эпохи)
+///
+/// # Поведение
+/// Пароли сравниваются по SHA-256, поэтому для поиска повторов не создаются копии секретов.
+/// Возраст пароля отсчитывается от последней смены по истории, а без истории - от создания записи.
+/// `health_score` учитывает только проблемы пароля: отсутствие URL на него не влияет
+///
+/// # Возвращает
+/// Отчет с записями, в которых есть проблемы, и сводными показателями
+pub fn audit_vault(data: &PasswordData, now: u64) -> AuditReport {
+    let tree = GroupTree::new(data);
+
+    let mut by_password: HashMap<[u8; 32], Vec<u32>> = HashMap::new();
+    for record in data.records.iter().filter(|r| !r.password.is_empty()) {
+        let digest: [u8; 32] = Sha256::digest(record.password.expose().as_bytes()).into();
+        by_password.entry(digest).or_default().push(record.id);
+    }
+
+    let mut report = AuditReport {
+        total: data.records.len(),
+        ..Default::default()
+    };
+    let mut score_sum = 0u32;
+    let mut scored = 0u32;
+    let mut healthy = 0usize;
+
+    for record in &data.records {
+        let mut findings = Vec::new();
+        let password = record.password.expose();
+
+        let score = if password.is_empty() {
+            // Пароль и URL обязательны только для логинов: у карты или заметки их может не быть
+            if record.kind.is_login() {
+                findings.push(AuditFinding::EmptyPassword);
+            }
+            None
+        } else {
+            let score = estimate_strength(password).score;
+            if score < WEAK_SCORE_THRESHOLD {
+                findings.push(AuditFinding::WeakPassword { score });
+            }
+
+            let digest: [u8; 32] = Sha256::digest(password.as_bytes()).into();
+            let others: Vec<u32> = by_password[&digest]
+                .iter()
+                .copied()
+                .filter(|&id| id != record.id)
+                .collect();
+            if !others.is_empty() {
+                findings.push(AuditFinding::ReusedPassword { record_ids: others });
+            }
+
+            if let Some(age_days) = password_age_days(record, now).filter(|&d| d > MAX_PASSWORD_AGE_DAYS) {
+                findings.push(AuditFinding::OldPassword { age_days });
+            }
+
+            let lowercase = |s: &str| s.chars().flat_map(char::to_lowercase).collect::<Vec<_>>();
+            if !record.login.is_empty() && lowercase(password) == lowercase(&record.login) {
+                findings.push(AuditFinding::PasswordEqualsLogin);
+            }
+
+            score_sum += u32::from(score);
+            scored += 1;
+            Some(score)
+        };
+
+        if record.kind.is_login() && record.url.trim().is_empty() {
+            findings.push(AuditFinding::MissingUrl);
+        }
+
+        for finding in &findings {
+            match finding {
+                AuditFinding::WeakPassword { .. } => report.weak += 1,
+                AuditFinding::ReusedPassword { .. } => report.reused += 1,
+                AuditFinding::OldPassword { .. } => report.old += 1,
+                AuditFinding::EmptyPassword => report.empty += 1,
+                AuditFinding::MissingUrl => report.missing_url += 1,
+                AuditFinding::PasswordEqualsLogin => report.password_equals_login += 1,
+            }
+        }
+        if !findings.iter().any(AuditFinding::is_password_issue) {
+            healthy += 1;
+        }
+
+        if !findings.is_empty() {
+            report.records.push(RecordAudit {
+                record_id: record.id,
+                name: record.name.clone(),
+                path: tree.path(record.pid),
+                score,
+                findings,
+            });
+        }
+    }
+
+    if scored > 0 {
+        report.average_score = f64::from(score_sum) / f64::from(scored);
+    }
+    report.health_score = (healthy * 100).checked_div(report.total).map_or(100, |v| v as u8);
+
+    report
+}
+
+/// Сколько полных суток пароль записи не менялся, если это известно
+fn password_age_days(record: &Record, now: u64) -> Option<u64> {
+    let changed_at = record
+        .history
+        .first()
+        .map(|entry| entry.changed_at)
+        .or(record.times.created_at)?;
+    Some(now.saturating_sub(changed_at) / SECONDS_PER_DAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::kakadu_file_module::attachments::AttachmentStore;
+    use crate::modules::kakadu_file_module::Group;
+    use serde_json::json;
+
+    const NOW: u64 = 1000 * SECONDS_PER_DAY;
+
+    fn record(id: u32, login: &str, password: &str, url: &str, created_days_ago: u64) -> Record {
+        serde_json::from_value(json!({
+            "id": id, "pid": 1, "name": format!("Запись {}", id), "login": login, "password": password, "url": url,
+            "loginSymbol": "TAB", "passwordSymbol": "ENTER", "urlSymbol": "NONE",
+            "createdAt": NOW - created_days_ago * SECONDS_PER_DAY
+        }))
+        .unwrap()
+    }
+
+    fn vault(records: Vec<Record>) -> PasswordData {
+        PasswordData {
+            groups: vec![Group::new(1, 0, "Root".to_string())],
+            records,
+            next_id: 100,
+            attachments: AttachmentStore::default(),
+        }
+    }
+
+    fn findings(report: &AuditReport, record_id: u32) -> Vec<AuditFinding> {
+        report
+            .records
+            .iter()
+            .find(|r| r.record_id == record_id)
+            .map_or_else(Vec::new, |r| r.findings.clone())
+    }
+
+    #[test]
+    fn findings_and_aggregates() {
+        let strong = "xK9#mQ2$vL7&nR4!pT8w";
+        let mut changed = record(14, "bob", "бЖ7щ#Ъп2эЮ!ф", "https://d.example", 800);
+        changed.history = serde_json::from_value(json!([
+            { "password": "old-1", "changedAt": NOW - 10 * SECONDS_PER_DAY },
+            { "password": "old-2", "changedAt": NOW - 500 * SECONDS_PER_DAY }
+        ]))
+        .unwrap();
+
+        let data = vault(vec![
+            record(10, "alice", "password", "https://a.example", 1),
+            record(11, "alice", strong, "https://b.example", 1),
+            record(12, "alice", strong, "", 1),
+            record(13, "alice", "k7#Vp2!xQm9zR", "https://c.example", 400),
+            changed,
+            record(15, "alice", "", "https://e.example", 1),
+            record(16, "Zx8#qLm2!vR7", "zx8#qlm2!vr7", "https://f.example", 1),
+        ]);
+        let report = audit_vault(&data, NOW);
+
+        assert_eq!(findings(&report, 10), [AuditFinding::WeakPassword { score: 0 }]);
+        assert_eq!(findings(&report, 11), [AuditFinding::ReusedPassword { record_ids: vec![12] }]);
+        assert_eq!(
+            findings(&report, 12),
+            [AuditFinding::ReusedPassword { record_ids: vec![11] }, AuditFinding::MissingUrl]
+        );
+        assert_eq!(findings(&report, 13), [AuditFinding::OldPassword { age_days: 400 }]);
+        assert_eq!(findings(&report, 14), []);
+        assert_eq!(findings(&report, 15), [AuditFinding::EmptyPassword]);
+        assert_eq!(findings(&report, 16), [AuditFinding::PasswordEqualsLogin]);
+
+        assert_eq!(report.records.iter().map(|r| r.record_id).collect::<Vec<_>>(), [10, 11, 12, 13, 15, 16]);
+        assert_eq!(report.records[0].path, ["Root"]);
+        assert_eq!(report.records[4].score, None);
+
+        assert_eq!(report.total, 7);
+        assert_eq!(report.weak, 1);
+        assert_eq!(report.reused, 2);
+        assert_eq!(report.old, 1);
+        assert_eq!(report.empty, 1);
+        assert_eq!(report.missing_url, 1);
+        assert_eq!(report.password_equals_login, 1);
+        // Непустые пароли: 0 + 4 * 5
+        assert!((report.average_score - 20.0 / 6.0).abs() < 1e-9);
+        // Без проблем с паролем только запись 14
+        assert_eq!(report.health_score, 14);
+    }
+
+    #[test]
+    fn password_age_boundary() {
+        let data = vault(vec![
+            record(10, "", "xK9#mQ2$vL7&nR4!pT8w", "https://a.example", MAX_PASSWORD_AGE_DAYS),
+            record(11, "", "k7#Vp2!xQm9zR", "https://b.example", MAX_PASSWORD_AGE_DAYS + 1),
+        ]);
+        let report = audit_vault(&data, NOW);
+
+        assert_eq!(findings(&report, 10), []);
+        assert_eq!(findings(&report, 11), [AuditFinding::OldPassword { age_days: MAX_PASSWORD_AGE_DAYS + 1 }]);
+    }
+
+    #[test]
+    fn empty_vault_is_healthy() {
+        let report = audit_vault(&vault(Vec::new()), NOW);
+
+        assert_eq!(report.total, 0);
+        assert_eq!(report.health_score, 100);
+        assert_eq!(report.average_score, 0.0);
+    }
+}
@@ -0,0 +1,412 @@
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::modules::kakadu_file_module::storage::write_atomic;
+use crate::modules::kakadu_file_module::tree::GroupTree;
+use crate::modules::kakadu_file_module::PasswordData;
+
+use super::BreachedRecord;
+
+/// Длина префикса хеша (в hex-символах), по которому строится индекс, как в HIBP range API
+const PREFIX_LEN: usize = 5;
+
+/// Количество корзин индекса: 16^5
+const BUCKETS: usize = 1 << (4 * PREFIX_LEN);
+
+/// Заголовок файла индекса: магия, версия, размер и время изменения исходного файла
+const INDEX_MAGIC: &[u8; 4] = b"KKHI";
+const INDEX_VERSION: u8 = 1;
+const INDEX_HEADER_LEN: u64 = 4 + 1 + 8 + 8;
+
+/// Расширение файла индекса рядом с файлом хешей
+const INDEX_EXTENSION: &str = "kkidx";
+
+/// Локальная база хешей HIBP (Have I Been Pwned)
+///
+/// Поддерживаются два формата загрузки:
+/// * один файл `SHA1:COUNT`, отсортированный по хешу. При первом открытии рядом
+///   создается индекс `<файл>.kkidx` со смещениями корзин по первым 5 символам хеша;
+///   индекс пересобирается, только если файл изменился
+/// * каталог файлов диапазонов `<PREFIX>` или `<PREFIX>.txt` со строками `SUFFIX:COUNT`,
+///   как их сохраняет загрузчик range API
+///
+/// Файл хешей никогда не читается в память целиком: поиск читает одну корзину
+pub enum HibpDatabase {
+    SortedFile { path: PathBuf, index_path: PathBuf },
+    RangeDirectory { path: PathBuf },
+}
+
+impl HibpDatabase {
+    /// Открывает базу, при необходимости строя индекс для отсортированного файла
+    ///
+    /// # Ошибки
+    /// Ошибка ввода-вывода или `InvalidData`, если файл не в формате HIBP или не отсортирован
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if path.is_dir() {
+            return Ok(HibpDatabase::RangeDirectory { path: path.to_path_buf() });
+        }
+
+        let index_path = index_path(path);
+        let (size, modified) = file_stamp(path)?;
+        if !index_is_current(&index_path, size, modified)? {
+            build_index(path, &index_path, size, modified)?;
+        }
+
+        Ok(HibpDatabase::SortedFile {
+            path: path.to_path_buf(),
+            index_path,
+        })
+    }
+
+    /// Ищет SHA-1 хеш пароля в базе
+    ///
+    /// # Возвращает
+    /// Количество появлений в утечках или `None`, если хеш не найден
+    pub fn lookup(&self, hash: &[u8; 20]) -> io::Result<Option<u64>> {
+        let hex = to_hex(hash);
+        let prefix = &hex[..PREFIX_LEN];
+
+        match self {
+            HibpDatabase::SortedFile { path, index_path } => {
+                let bucket = usize::from_str_radix(prefix, 16).expect("префикс хеша в hex");
+                let (start, end) = read_bucket_range(index_path, bucket)?;
+
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(start))?;
+                find_in_lines(BufReader::new(file.take(end - start)), &hex, 0)
+            }
+            HibpDatabase::RangeDirectory { path } => {
+                let candidates = [path.join(prefix), path.join(format!("{}.txt", prefix))];
+                match candidates.iter().find(|p| p.is_file()) {
+                    Some(range_path) => find_in_lines(BufReader::new(File::open(range_path)?), &hex, PREFIX_LEN),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+}
+
+/// Проверяет пароли всех записей по базе утечек
+///
+/// # Поведение
+/// Одинаковые пароли проверяются один раз; хеши перебираются по возрастанию,
+/// чтобы чтение файла шло последовательно. Пустые пароли пропускаются
+///
+/// # Возвращает
+/// Записи, пароли которых найдены в базе, в порядке следования в хранилище
+pub fn check_breaches(data: &PasswordData, db: &HibpDatabase) -> io::Result<Vec<BreachedRecord>> {
+    let mut by_hash: BTreeMap<[u8; 20], Vec<u32>> = BTreeMap::new();
+    for record in data.records.iter().filter(|r| !r.password.is_empty()) {
+        let hash: [u8; 20] = Sha1::digest(record.password.expose().as_bytes()).into();
+        by_hash.entry(hash).or_default().push(record.id);
+    }
+
+    let mut counts = BTreeMap::new();
+    for (hash, ids) in &by_hash {
+        if let Some(count) = db.lookup(hash)? {
+            counts.extend(ids.iter().map(|&id| (id, count)));
+        }
+    }
+
+    let tree = GroupTree::new(data);
+    Ok(data
+        .records
+        .iter()
+        .filter_map(|r| {
+            counts.get(&r.id).map(|&count| BreachedRecord {
+                record_id: r.id,
+                name: r.name.clone(),
+                path: tree.path(r.pid),
+                count,
+            })
+        })
+        .collect())
+}
+
+/// Путь к индексу: `<файл>.kkidx`
+fn index_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", INDEX_EXTENSION));
+    PathBuf::from(name)
+}
+
+/// Размер и время изменения файла для проверки актуальности индекса
+fn file_stamp(path: &Path) -> io::Result<(u64, u64)> {
+    let meta = fs::metadata(path)?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    Ok((meta.len(), modified))
+}
+
+/// Проверяет, что индекс существует и построен для текущей версии файла
+fn index_is_current(index_path: &Path, size: u64, modified: u64) -> io::Result<bool> {
+    let mut header = [0u8; INDEX_HEADER_LEN as usize];
+    match File::open(index_path).and_then(|mut f| f.read_exact(&mut header)) {
+        Ok(()) => {}
+        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::UnexpectedEof) => return Ok(false),
+        Err(e) => return Err(e),
+    }
+
+    Ok(&header[..4] == INDEX_MAGIC
+        && header[4] == INDEX_VERSION
+        && header[5..13] == size.to_le_bytes()
+        && header[13..21] == modified.to_le_bytes())
+}
+
+/// Строит индекс за один последовательный проход по файлу
+///
+/// Для каждой корзины (первые 5 символов хеша) сохраняется смещение ее первой строки;
+/// в конце добавляется размер файла, поэтому корзина `b` занимает `offsets[b]..offsets[b + 1]`
+fn build_index(path: &Path, index_path: &Path, size: u64, modified: u64) -> io::Result<()> {
+    let mut reader = BufReader::with_capacity(1 << 20, File::open(path)?);
+    let mut offsets = vec![0u64; BUCKETS + 1];
+    let mut next_bucket = 0usize;
+    let mut offset = 0u64;
+    let mut line = Vec::new();
+    let mut line_number = 0usize;
+
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        line_number += 1;
+
+        let trimmed = line.trim_ascii();
+        if !trimmed.is_empty() {
+            let bucket = std::str::from_utf8(trimmed.get(..PREFIX_LEN).unwrap_or_default())
+                .ok()
+                .and_then(|p| usize::from_str_radix(p, 16).ok())
+                .ok_or_else(|| invalid_data(format!("строка {}: ожидается SHA-1 хеш", line_number)))?;
+
+            if bucket + 1 < next_bucket {
+                return Err(invalid_data(format!("строка {}: файл не отсортирован по хешу", line_number)));
+            }
+            while next_bucket <= bucket {
+                offsets[next_bucket] = offset;
+                next_bucket += 1;
+            }
+        }
+
+        offset += read as u64;
+    }
+
+    for entry in &mut offsets[next_bucket..] {
+        *entry = offset;
+    }
+
+    let mut bytes = Vec::with_capacity(INDEX_HEADER_LEN as usize + offsets.len() * 8);
+    bytes.extend_from_slice(INDEX_MAGIC);
+    bytes.push(INDEX_VERSION);
+    bytes.extend_from_slice(&size.to_le_bytes());
+    bytes.extend_from_slice(&modified.to_le_bytes());
+    for entry in &offsets {
+        bytes.extend_from_slice(&entry.to_le_bytes());
+    }
+
+    write_atomic(index_path, &bytes, 0)
+}
+
+/// Читает из индекса границы корзины, не загружая индекс целиком
+fn read_bucket_range(index_path: &Path, bucket: usize) -> io::Result<(u64, u64)> {
+    let mut index = File::open(index_path)?;
+    index.seek(SeekFrom::Start(INDEX_HEADER_LEN + bucket as u64 * 8))?;
+
+    let mut range = [0u8; 16];
+    index.read_exact(&mut range)?;
+    let start = u64::from_le_bytes(range[..8].try_into().unwrap());
+    let end = u64::from_le_bytes(range[8..].try_into().unwrap());
+
+    if end < start {
+        return Err(invalid_data("поврежден индекс базы утечек".to_string()));
+    }
+    Ok((start, end))
+}
+
+/// Ищет хеш среди строк `HASH:COUNT`
+///
+/// `skip` - сколько первых символов полного хеша отсутствует в строках (префикс файла диапазона)
+fn find_in_lines(reader: impl BufRead, hash: &str, skip: usize) -> io::Result<Option<u64>> {
+    let wanted = &hash[skip..];
+
+    for line in reader.lines() {
+        let line = line?;
+        let (candidate, count) = line.trim().split_once(':').unwrap_or((line.trim(), "1"));
+        if candidate.eq_ignore_ascii_case(wanted) {
+            return Ok(Some(count.trim().parse().unwrap_or(1)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// SHA-1 в верхнем регистре, как в файлах HIBP
+fn to_hex(hash: &[u8; 20]) -> String {
+    hash.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Ошибка формата файла базы утечек
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::kakadu_file_module::attachments::AttachmentStore;
+    use crate::modules::kakadu_file_module::{Group, Record};
+
+    /// Отсортированный файл `SHA1:COUNT`: пять настоящих паролей и синтетические хеши
+    /// в первой и последней корзинах (числа появлений условные)
+    const SAMPLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/hibp/pwned-sample.txt"));
+
+    /// Временный каталог, удаляемый вместе с содержимым
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("kakadu-hibp-{}-{}", std::process::id(), name));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn sha1(password: &str) -> [u8; 20] {
+        Sha1::digest(password.as_bytes()).into()
+    }
+
+    fn from_hex(hex: &str) -> [u8; 20] {
+        let mut hash = [0u8; 20];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        hash
+    }
+
+    fn sorted_file(dir: &TempDir) -> PathBuf {
+        let path = dir.0.join("pwned.txt");
+        fs::write(&path, SAMPLE).unwrap();
+        path
+    }
+
+    /// Раскладывает образец по файлам диапазонов, как их сохраняет загрузчик range API
+    fn range_directory(dir: &TempDir) -> PathBuf {
+        let mut ranges: BTreeMap<&str, String> = BTreeMap::new();
+        for line in SAMPLE.lines() {
+            let (prefix, rest) = line.split_at(PREFIX_LEN);
+            ranges.entry(prefix).or_default().push_str(&format!("{}\r\n", rest));
+        }
+        for (i, (prefix, content)) in ranges.iter().enumerate() {
+            // Часть файлов без расширения `.txt`
+            let name = if i % 2 == 0 { format!("{}.txt", prefix) } else { prefix.to_string() };
+            fs::write(dir.0.join(name), content).unwrap();
+        }
+        dir.0.clone()
+    }
+
+    fn assert_lookups(db: &HibpDatabase) {
+        assert_eq!(db.lookup(&sha1("password")).unwrap(), Some(10434004));
+        assert_eq!(db.lookup(&sha1("123456")).unwrap(), Some(37359195));
+        assert_eq!(db.lookup(&sha1("correct horse battery staple")).unwrap(), None);
+
+        // Соседи по корзине не путаются с искомым хешем
+        assert_eq!(db.lookup(&from_hex("5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD9")).unwrap(), Some(5));
+        assert_eq!(db.lookup(&from_hex("5BAA61E4C9B93F3F0682250B6CF8331B7EE68FDA")).unwrap(), None);
+
+        // Первая и последняя корзины
+        assert_eq!(db.lookup(&from_hex("0000000A1B2C3D4E5F60718293A4B5C6D7E8F901")).unwrap(), Some(3));
+        assert_eq!(db.lookup(&from_hex("0000000CAFEBABE0000000000000000000000000")).unwrap(), Some(1));
+        assert_eq!(db.lookup(&[0u8; 20]).unwrap(), None);
+        assert_eq!(db.lookup(&[0xFF; 20]).unwrap(), Some(42));
+        assert_eq!(db.lookup(&from_hex("FFFFF00000000000000000000000000000000000")).unwrap(), Some(9));
+        assert_eq!(db.lookup(&from_hex("FFFFF00000000000000000000000000000000001")).unwrap(), None);
+    }
+
+    #[test]
+    fn index_is_built_next_to_sorted_file() {
+        let dir = TempDir::new("index");
+        let path = sorted_file(&dir);
+
+        let db = HibpDatabase::open(&path).unwrap();
+        let index = dir.0.join("pwned.txt.kkidx");
+        let (size, modified) = file_stamp(&path).unwrap();
+        assert!(index_is_current(&index, size, modified).unwrap());
+        assert_eq!(fs::metadata(&index).unwrap().len(), INDEX_HEADER_LEN + (BUCKETS as u64 + 1) * 8);
+
+        // Корзина 0 - первые две строки файла, корзина 1 - третья, корзина 2 пуста
+        let offset = |lines: usize| SAMPLE.lines().take(lines).map(|l| l.len() as u64 + 1).sum::<u64>();
+        assert_eq!(read_bucket_range(&index, 0).unwrap(), (0, offset(2)));
+        assert_eq!(read_bucket_range(&index, 1).unwrap(), (offset(2), offset(3)));
+        assert_eq!(read_bucket_range(&index, 2).unwrap(), (offset(3), offset(3)));
+        let (_, end) = read_bucket_range(&index, BUCKETS - 1).unwrap();
+        assert_eq!(end, SAMPLE.len() as u64);
+
+        assert_lookups(&db);
+
+        // Индекс другой версии файла считается устаревшим и пересобирается
+        assert!(!index_is_current(&index, size + 1, modified).unwrap());
+        fs::write(&index, b"KKHI").unwrap();
+        assert_lookups(&HibpDatabase::open(&path).unwrap());
+    }
+
+    #[test]
+    fn unsorted_or_malformed_files_are_rejected() {
+        let dir = TempDir::new("invalid");
+        let path = dir.0.join("pwned.txt");
+
+        fs::write(&path, "FFFFF00000000000000000000000000000000000:1\n00000000000000000000000000000000000000AA:1\n").unwrap();
+        assert_eq!(HibpDatabase::open(&path).err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        fs::write(&path, "not a hash\n").unwrap();
+        assert_eq!(HibpDatabase::open(&path).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn range_directory_lookups() {
+        let dir = TempDir::new("ranges");
+        let db = HibpDatabase::open(&range_directory(&dir)).unwrap();
+
+        assert!(matches!(db, HibpDatabase::RangeDirectory { .. }));
+        assert_lookups(&db);
+    }
+
+    #[test]
+    fn breached_records_are_reported_with_counts() {
+        let dir = TempDir::new("breaches");
+        let db = HibpDatabase::open(&sorted_file(&dir)).unwrap();
+
+        let record = |id: u32, password: &str| -> Record {
+            serde_json::from_value(serde_json::json!({
+                "id": id, "pid": 1, "name": format!("Запись {}", id), "login": "", "password": password, "url": "",
+                "loginSymbol": "TAB", "passwordSymbol": "ENTER", "urlSymbol": "NONE"
+            }))
+            .unwrap()
+        };
+        let data = PasswordData {
+            groups: vec![Group::new(1, 0, "Root".to_string())],
+            records: vec![record(2, "qwerty"), record(3, "x7#Lq!v9Zr"), record(4, ""), record(5, "qwerty"), record(6, "dragon")],
+            next_id: 7,
+            attachments: AttachmentStore::default(),
+        };
+
+        let breached = check_breaches(&data, &db).unwrap();
+        let found: Vec<(u32, u64)> = breached.iter().map(|b| (b.record_id, b.count)).collect();
+        assert_eq!(found, [(2, 10556095), (5, 10556095), (6, 1145695)]);
+        assert_eq!(breached[0].path, ["Root"]);
+    }
+}
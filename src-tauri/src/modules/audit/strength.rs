@@ -0,0 +1,311 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Распространенные пароли по убыванию популярности (ранг = номер строки)
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+/// Ряды клавиатуры для поиска «клавиатурных» последовательностей
+const KEYBOARD_ROWS: [&str; 7] = [
+    "1234567890",
+    "qwertyuiop",
+    "asdfghjkl",
+    "zxcvbnm",
+    "йцукенгшщзхъ",
+    "фывапролджэ",
+    "ячсмитьбю",
+];
+
+/// Минимальная длина шаблона (повтора, последовательности, слова из словаря)
+const MIN_PATTERN_LEN: usize = 3;
+
+/// Число вариантов на символ вне шаблонов. Как в zxcvbn, меньше полного алфавита,
+/// поскольку реальные пароли далеки от равномерно случайных
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+
+/// Минимальная длина клавиатурной последовательности: короткие участки рядов слишком часты
+const KEYBOARD_MIN_LEN: usize = 4;
+
+/// Оценка стойкости пароля в духе zxcvbn
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct Strength {
+    pub score: u8,          // Оценка от 0 (очень слабый) до 4 (стойкий)
+    pub guesses_log10: f64, // Десятичный логарифм числа попыток для подбора
+}
+
+/// Найденный в пароле шаблон, покрывающий символы `start..end`
+struct PatternMatch {
+    start: usize,
+    end: usize,
+    guesses_log10: f64,
+}
+
+/// Оценивает стойкость пароля
+///
+/// # Поведение
+/// Пароль разбивается на шаблоны: слова из списка распространенных паролей
+/// (с учетом регистра и замен вида `@ -> a`, `0 -> o`), повторы символов,
+/// алфавитные и клавиатурные последовательности, годы. Для каждого шаблона
+/// оценивается число попыток, остальные символы считаются подбираемыми перебором.
+/// Итог - минимальное число попыток по всем разбиениям, переведенное в шкалу 0..4
+pub fn estimate_strength(password: &str) -> Strength {
+    let chars: Vec<char> = password.chars().collect();
+    if chars.is_empty() {
+        return Strength {
+            score: 0,
+            guesses_log10: 0.0,
+        };
+    }
+
+    let matches = find_matches(&chars);
+    let brute_force = BRUTEFORCE_CARDINALITY.log10();
+
+    // best[i] - минимальный log10 числа попыток для первых i символов
+    let mut best = vec![f64::INFINITY; chars.len() + 1];
+    best[0] = 0.0;
+    for end in 1..=chars.len() {
+        best[end] = best[end - 1] + brute_force;
+        for m in matches.iter().filter(|m| m.end == end) {
+            best[end] = best[end].min(best[m.start] + m.guesses_log10);
+        }
+    }
+
+    let guesses_log10 = best[chars.len()];
+    Strength {
+        score: score_from_guesses(guesses_log10),
+        guesses_log10,
+    }
+}
+
+/// Переводит число попыток в шкалу 0..4 по порогам zxcvbn
+fn score_from_guesses(guesses_log10: f64) -> u8 {
+    match guesses_log10 {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+/// Размер алфавита, из которого состоят символы
+fn cardinality(chars: &[char]) -> usize {
+    let mut size = 0;
+    if chars.iter().any(|c| c.is_ascii_lowercase()) {
+        size += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_uppercase()) {
+        size += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_digit()) {
+        size += 10;
+    }
+    if chars.iter().any(|c| c.is_ascii_punctuation() || *c == ' ') {
+        size += 33;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        size += 100;
+    }
+    size.max(1)
+}
+
+/// Находит все шаблоны в пароле
+fn find_matches(chars: &[char]) -> Vec<PatternMatch> {
+    let mut matches = Vec::new();
+    dictionary_matches(chars, &mut matches);
+    repeat_matches(chars, &mut matches);
+    sequence_matches(chars, &mut matches);
+    keyboard_matches(chars, &mut matches);
+    year_matches(chars, &mut matches);
+    matches
+}
+
+/// Словарь распространенных паролей: слово -> ранг
+fn common_passwords() -> &'static HashMap<String, usize> {
+    static DICTIONARY: OnceLock<HashMap<String, usize>> = OnceLock::new();
+    DICTIONARY.get_or_init(|| {
+        COMMON_PASSWORDS
+            .lines()
+            .filter(|w| !w.is_empty())
+            .enumerate()
+            .map(|(rank, word)| (word.to_string(), rank + 1))
+            .collect()
+    })
+}
+
+/// Приводит символ к букве, которую он обычно заменяет (`@ -> a`, `0 -> o`, ...)
+fn unleet(c: char) -> char {
+    match c {
+        '4' | '@' => 'a',
+        '3' => 'e',
+        '1' | '!' => 'i',
+        '0' => 'o',
+        '$' | '5' => 's',
+        '7' => 't',
+        other => other,
+    }
+}
+
+/// Подстроки, совпадающие со словами словаря (как есть, в нижнем регистре и после замен)
+fn dictionary_matches(chars: &[char], matches: &mut Vec<PatternMatch>) {
+    let dictionary = common_passwords();
+
+    for start in 0..chars.len() {
+        for end in start + MIN_PATTERN_LEN..=chars.len() {
+            let slice = &chars[start..end];
+            let lower: String = slice.iter().flat_map(|c| c.to_lowercase()).collect();
+            let unleeted: String = lower.chars().map(unleet).collect();
+
+            let found = dictionary
+                .get(&lower)
+                .map(|&rank| (rank, false))
+                .or_else(|| dictionary.get(&unleeted).map(|&rank| (rank, true)));
+
+            if let Some((rank, leet)) = found {
+                // Варианты регистра и замены символов удваивают перебор по словарю
+                let uppercase = slice.iter().any(|c| c.is_uppercase());
+                let variations = 1.0 + f64::from(u8::from(uppercase)) + f64::from(u8::from(leet));
+                matches.push(PatternMatch {
+                    start,
+                    end,
+                    guesses_log10: (rank as f64 * variations).log10(),
+                });
+            }
+        }
+    }
+}
+
+/// Повторы одного символа (`aaaa`, `1111`)
+fn repeat_matches(chars: &[char], matches: &mut Vec<PatternMatch>) {
+    let mut start = 0;
+    while start < chars.len() {
+        let end = start + chars[start..].iter().take_while(|&&c| c == chars[start]).count();
+        if end - start >= MIN_PATTERN_LEN {
+            matches.push(PatternMatch {
+                start,
+                end,
+                guesses_log10: (cardinality(&chars[start..start + 1]) as f64 * (end - start) as f64).log10(),
+            });
+        }
+        start = end;
+    }
+}
+
+/// Последовательности с постоянным шагом ±1 (`abcd`, `4321`)
+fn sequence_matches(chars: &[char], matches: &mut Vec<PatternMatch>) {
+    let mut start = 0;
+    while start + 1 < chars.len() {
+        let delta = chars[start + 1] as i64 - chars[start] as i64;
+        let mut end = start + 1;
+        if delta.abs() == 1 {
+            while end < chars.len() && chars[end] as i64 - chars[end - 1] as i64 == delta {
+                end += 1;
+            }
+        }
+
+        if end - start >= MIN_PATTERN_LEN {
+            let base = if chars[start].is_ascii_digit() { 10.0 } else { 26.0 };
+            let direction = if delta < 0 { 2.0 } else { 1.0 };
+            matches.push(PatternMatch {
+                start,
+                end,
+                guesses_log10: (base * direction * (end - start) as f64).log10(),
+            });
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+/// Участки, набранные подряд по ряду клавиатуры (`qwerty`, `asdf`)
+fn keyboard_matches(chars: &[char], matches: &mut Vec<PatternMatch>) {
+    let lower: Vec<char> = chars.iter().flat_map(|c| c.to_lowercase()).collect();
+    if lower.len() != chars.len() {
+        return;
+    }
+
+    for row in KEYBOARD_ROWS {
+        let row: Vec<char> = row.chars().collect();
+        for start in 0..lower.len() {
+            let Some(pos) = row.iter().position(|&c| c == lower[start]) else {
+                continue;
+            };
+            let len = lower[start..]
+                .iter()
+                .zip(&row[pos..])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len >= KEYBOARD_MIN_LEN {
+                matches.push(PatternMatch {
+                    start,
+                    end: start + len,
+                    guesses_log10: (KEYBOARD_ROWS.len() as f64 * row.len() as f64 * len as f64).log10(),
+                });
+            }
+        }
+    }
+}
+
+/// Годы 1900-2049
+fn year_matches(chars: &[char], matches: &mut Vec<PatternMatch>) {
+    for start in 0..chars.len().saturating_sub(3) {
+        let year: String = chars[start..start + 4].iter().collect();
+        if matches!(year.parse::<u32>(), Ok(1900..=2049)) {
+            matches.push(PatternMatch {
+                start,
+                end: start + 4,
+                guesses_log10: 150f64.log10(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(password: &str) -> u8 {
+        estimate_strength(password).score
+    }
+
+    #[test]
+    fn common_passwords_are_weak() {
+        assert_eq!(score(""), 0);
+        assert_eq!(score("password"), 0);
+        assert_eq!(score("Password"), 0);
+        assert_eq!(score("iloveyou"), 0);
+    }
+
+    #[test]
+    fn leet_substitutions_do_not_help() {
+        assert_eq!(score("P@ssw0rd"), 0);
+        assert_eq!(score("l3tm31n"), 0);
+        assert!(score("dr4g0n!") <= 1);
+    }
+
+    #[test]
+    fn keyboard_walks_and_sequences_are_weak() {
+        assert_eq!(score("qwertyuiop"), 0);
+        assert_eq!(score("Asdfghjkl"), 0);
+        assert_eq!(score("йцукенгшщз"), 0);
+        assert_eq!(score("abcdefgh"), 0);
+        assert_eq!(score("98765432"), 0);
+        assert_eq!(score("zzzzzzzzzz"), 0);
+    }
+
+    #[test]
+    fn year_suffix_is_weak() {
+        assert_eq!(score("monkey1990"), 1);
+        assert_eq!(score("Dragon2024!"), 1);
+        assert!(estimate_strength("summer2023").guesses_log10 < estimate_strength("summer").guesses_log10 + 3.0);
+    }
+
+    #[test]
+    fn long_random_passwords_are_strong() {
+        assert_eq!(score("k7#Vp2!xQm9zR"), 4);
+        assert_eq!(score("xK9#mQ2$vL7&nR4!pT8w"), 4);
+        assert_eq!(score("бЖ7щ#Ъп2эЮ!ф"), 4);
+        assert!(score("k7#Vp2!xQm9zR") > score("k7#Vp"));
+    }
+}
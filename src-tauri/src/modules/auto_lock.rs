@@ -0,0 +1,32 @@
+use crate::state::AppState;
+use crate::utils::emit_event;
+use std::{thread, time::Duration};
+use tauri::{AppHandle, Manager};
+
+/// Период проверки таймера бездействия
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Запускает фоновый поток, который блокирует хранилище после истечения
+/// таймаута бездействия (`AppState::lock_timeout`)
+///
+/// Если заблокировать не удалось (хранилище не сохранено в файл, нет ключа сессии,
+/// ошибка записи), отправляет на фронтенд событие `vault_lock_failed` с `CommandError`,
+/// чтобы пользователь успел сохранить изменения. Если за `LOCK_GRACE_PERIOD` активности
+/// не было, данные затираются без сохранения и отправляется `vault_locked`
+pub fn start_auto_lock_monitor(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(CHECK_INTERVAL);
+
+        let state = app.state::<AppState>();
+        if state.is_idle_expired() {
+            if let Err(e) = state.lock_vault(&app) {
+                if state.record_lock_failure() {
+                    let _ = emit_event(&app, "vault_lock_failed", &e, "Ошибка отправки события блокировки");
+                } else if state.lock_grace_expired() {
+                    state.discard_vault();
+                    let _ = emit_event(&app, "vault_locked", &(), "Ошибка отправки события блокировки");
+                }
+            }
+        }
+    });
+}
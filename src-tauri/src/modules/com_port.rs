@@ -7,6 +7,16 @@ use std::{
 };
 use tauri::State;
 
+/// Максимальная длина поля записи (логин, пароль, URL) в байтах UTF-8,
+/// которую принимает устройство Crypto Kakadu
+///
+/// Протокол устройства не позволяет запросить это значение (поддерживается только
+/// проверка связи `cWAY`), а в репозитории нет описания прошивки или протокола,
+/// где оно было бы указано. Значение 64 не подтверждено документацией и должно быть
+/// сверено с прошивкой. Считаются байты, а не символы: кириллическая буква занимает два байта.
+/// Используется генератором паролей. При смене прошивки обновляется только эта константа
+pub const DEVICE_MAX_FIELD_LEN: usize = 64;
+
 #[derive(Default)]
 pub struct ComPortState {
     pub is_connected: Arc<Mutex<bool>>,
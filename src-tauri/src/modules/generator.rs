@@ -0,0 +1,444 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::modules::com_port::DEVICE_MAX_FIELD_LEN;
+use crate::modules::kakadu_file_module::secret::SecretField;
+
+/// Словарь для парольных фраз: английский список BIP-39 (2048 слов, 11 бит на слово)
+const WORDLIST: &str = include_str!("generator/wordlist.txt");
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!#$%&()*+,-./:;<=>?@[]^_{}~";
+
+/// Символы, которые легко перепутать при чтении с экрана
+const AMBIGUOUS: &str = "Il1O0o|";
+
+/// Буквы для произносимых паролей
+const CONSONANTS: &str = "bcdfghjklmnprstvwxz";
+const VOWELS: &str = "aeiuy";
+
+/// Политика генерации пароля
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum PasswordPolicy {
+    /// Случайные символы из выбранных классов
+    Random(RandomPolicy),
+    /// Чередование согласных и гласных, легко произносится и запоминается
+    Pronounceable(PronounceablePolicy),
+    /// Случайные слова из встроенного словаря (diceware)
+    Passphrase(PassphrasePolicy),
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy::Random(RandomPolicy::default())
+    }
+}
+
+/// Политика пароля из случайных символов
+///
+/// Минимумы учитываются только для включенных классов символов
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct RandomPolicy {
+    pub length: usize,           // Длина пароля
+    pub lowercase: bool,         // Строчные латинские буквы
+    pub uppercase: bool,         // Заглавные латинские буквы
+    pub digits: bool,            // Цифры
+    pub symbols: bool,           // Спецсимволы
+    pub exclude_ambiguous: bool, // Исключить похожие символы (`I`, `l`, `1`, `O`, `0`, ...)
+    pub min_lowercase: usize,    // Минимум строчных букв
+    pub min_uppercase: usize,    // Минимум заглавных букв
+    pub min_digits: usize,       // Минимум цифр
+    pub min_symbols: usize,      // Минимум спецсимволов
+}
+
+impl Default for RandomPolicy {
+    fn default() -> Self {
+        Self {
+            length: 20,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: false,
+            min_lowercase: 1,
+            min_uppercase: 1,
+            min_digits: 1,
+            min_symbols: 1,
+        }
+    }
+}
+
+/// Политика произносимого пароля
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PronounceablePolicy {
+    pub length: usize,    // Длина пароля вместе с цифрами
+    pub capitalize: bool, // Начинать с заглавной буквы
+    pub digits: usize,    // Количество цифр в конце пароля
+}
+
+impl Default for PronounceablePolicy {
+    fn default() -> Self {
+        Self {
+            length: 16,
+            capitalize: true,
+            digits: 2,
+        }
+    }
+}
+
+/// Политика парольной фразы
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PassphrasePolicy {
+    pub words: usize,      // Количество слов
+    pub separator: String, // Разделитель между словами
+    pub capitalize: bool,  // Каждое слово с заглавной буквы
+    pub digit: bool,       // Добавить цифру в конец случайного слова
+}
+
+impl Default for PassphrasePolicy {
+    fn default() -> Self {
+        Self {
+            words: 5,
+            separator: "-".to_string(),
+            capitalize: false,
+            digit: false,
+        }
+    }
+}
+
+/// Сгенерированный пароль с оценкой стойкости
+#[derive(Debug, Serialize)]
+pub struct GeneratedPassword {
+    pub password: SecretField, // Пароль
+    pub length: usize,         // Длина в символах
+    pub entropy_bits: f64,     // Оценка энтропии в битах
+}
+
+/// Генерирует пароль по политике с помощью криптографически стойкого ГСЧ
+///
+/// # Ошибки
+/// Возвращает ошибку если политика противоречива: пустой набор символов,
+/// сумма минимумов больше длины или длина в байтах превышает `DEVICE_MAX_FIELD_LEN`
+pub fn generate_password(policy: &PasswordPolicy) -> Result<GeneratedPassword, String> {
+    match policy {
+        PasswordPolicy::Random(policy) => generate_random(policy),
+        PasswordPolicy::Pronounceable(policy) => generate_pronounceable(policy),
+        PasswordPolicy::Passphrase(policy) => generate_passphrase(policy),
+    }
+}
+
+/// Пароль из случайных символов с гарантированными минимумами по классам
+fn generate_random(policy: &RandomPolicy) -> Result<GeneratedPassword, String> {
+    check_length(policy.length)?;
+
+    let classes: Vec<(Vec<char>, usize)> = [
+        (policy.lowercase, LOWERCASE, policy.min_lowercase),
+        (policy.uppercase, UPPERCASE, policy.min_uppercase),
+        (policy.digits, DIGITS, policy.min_digits),
+        (policy.symbols, SYMBOLS, policy.min_symbols),
+    ]
+    .into_iter()
+    .filter(|(enabled, _, _)| *enabled)
+    .map(|(_, chars, min)| {
+        let chars = chars
+            .chars()
+            .filter(|c| !(policy.exclude_ambiguous && AMBIGUOUS.contains(*c)))
+            .collect();
+        (chars, min)
+    })
+    .collect();
+
+    let alphabet: Vec<char> = classes.iter().flat_map(|(chars, _)| chars.iter().copied()).collect();
+    if alphabet.is_empty() {
+        return Err("Не выбран ни один класс символов".to_string());
+    }
+    if classes.iter().map(|(_, min)| min).sum::<usize>() > policy.length {
+        return Err("Сумма минимумов по классам превышает длину пароля".to_string());
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut chars = Zeroizing::new(Vec::with_capacity(policy.length));
+
+    // Сначала обязательные символы каждого класса, затем остальное из общего алфавита
+    for (class, min) in &classes {
+        for _ in 0..*min {
+            chars.push(*class.choose(&mut rng).unwrap());
+        }
+    }
+    while chars.len() < policy.length {
+        chars.push(*alphabet.choose(&mut rng).unwrap());
+    }
+    chars.shuffle(&mut rng);
+
+    Ok(finish(&chars, random_entropy(&classes, policy.length)))
+}
+
+/// Энтропия пароля из случайных символов: log2 числа паролей, удовлетворяющих минимумам
+///
+/// Число паролей длины `L`, в которых из класса `i` (`n_i` символов) взято не меньше
+/// `min_i` символов, равно `L! * [x^L] ∏ Σ_{k ≥ min_i} (n_i x)^k / k!`
+/// (экспоненциальная производящая функция). Без минимумов это `L * log2(Σ n_i)`
+fn random_entropy(classes: &[(Vec<char>, usize)], length: usize) -> f64 {
+    let factorial = |k: usize| (1..=k).map(|i| i as f64).product::<f64>();
+
+    let mut series = vec![0.0; length + 1];
+    series[0] = 1.0;
+    for (chars, min) in classes {
+        let size = chars.len() as f64;
+        let mut next = vec![0.0; length + 1];
+        for (i, coefficient) in series.iter().enumerate().filter(|(_, c)| **c > 0.0) {
+            for k in *min..=length - i {
+                next[i + k] += coefficient * size.powi(k as i32) / factorial(k);
+            }
+        }
+        series = next;
+    }
+
+    (series[length] * factorial(length)).log2()
+}
+
+/// Произносимый пароль: слоги «согласная + гласная» и цифры в конце
+fn generate_pronounceable(policy: &PronounceablePolicy) -> Result<GeneratedPassword, String> {
+    check_length(policy.length)?;
+    if policy.digits >= policy.length {
+        return Err("Количество цифр должно быть меньше длины пароля".to_string());
+    }
+
+    let consonants: Vec<char> = CONSONANTS.chars().collect();
+    let vowels: Vec<char> = VOWELS.chars().collect();
+    let digits: Vec<char> = DIGITS.chars().collect();
+    let letters = policy.length - policy.digits;
+
+    let mut rng = rand::thread_rng();
+    let mut chars = Zeroizing::new(Vec::with_capacity(policy.length));
+    let mut entropy_bits = 0.0;
+
+    for i in 0..letters {
+        let set = if i % 2 == 0 { &consonants } else { &vowels };
+        chars.push(*set.choose(&mut rng).unwrap());
+        entropy_bits += (set.len() as f64).log2();
+    }
+    if policy.capitalize {
+        chars[0] = chars[0].to_ascii_uppercase();
+    }
+    for _ in 0..policy.digits {
+        chars.push(*digits.choose(&mut rng).unwrap());
+        entropy_bits += (digits.len() as f64).log2();
+    }
+
+    Ok(finish(&chars, entropy_bits))
+}
+
+/// Парольная фраза из слов встроенного словаря
+fn generate_passphrase(policy: &PassphrasePolicy) -> Result<GeneratedPassword, String> {
+    if policy.words == 0 {
+        return Err("Количество слов должно быть больше нуля".to_string());
+    }
+
+    let words: Vec<&str> = WORDLIST.lines().filter(|w| !w.is_empty()).collect();
+    let longest = words.iter().map(|w| w.len()).max().unwrap_or(0);
+
+    // Проверяем худший случай заранее, чтобы фраза любого состава поместилась в устройство.
+    // Длины в байтах UTF-8: разделитель может содержать не-ASCII символы
+    let max_length = policy
+        .words
+        .checked_mul(longest)
+        .zip((policy.words - 1).checked_mul(policy.separator.len()))
+        .and_then(|(words, separators)| words.checked_add(separators))
+        .and_then(|length| length.checked_add(usize::from(policy.digit)))
+        .unwrap_or(usize::MAX);
+    check_length(max_length)?;
+
+    let mut rng = rand::thread_rng();
+    let mut chosen: Vec<Zeroizing<String>> = (0..policy.words)
+        .map(|_| {
+            let word = words.choose(&mut rng).unwrap();
+            let mut word = Zeroizing::new(word.to_string());
+            if policy.capitalize {
+                word[..1].make_ascii_uppercase();
+            }
+            word
+        })
+        .collect();
+
+    let mut entropy_bits = policy.words as f64 * (words.len() as f64).log2();
+    if policy.digit {
+        let index = rng.gen_range(0..chosen.len());
+        chosen[index].push(char::from(b'0' + rng.gen_range(0..10)));
+        entropy_bits += 10f64.log2() + (chosen.len() as f64).log2();
+    }
+
+    let mut chars = Zeroizing::new(Vec::with_capacity(max_length));
+    for (i, word) in chosen.iter().enumerate() {
+        if i > 0 {
+            chars.extend(policy.separator.chars());
+        }
+        chars.extend(word.chars());
+    }
+
+    Ok(finish(&chars, entropy_bits))
+}
+
+/// Проверяет, что длина пароля допустима и помещается в поле устройства
+///
+/// `length` - длина в байтах UTF-8. Для паролей из ASCII она совпадает с числом символов
+fn check_length(length: usize) -> Result<(), String> {
+    if length == 0 {
+        return Err("Длина пароля должна быть больше нуля".to_string());
+    }
+    if length > DEVICE_MAX_FIELD_LEN {
+        return Err(format!(
+            "Длина пароля превышает максимальную длину поля устройства ({} байт)",
+            DEVICE_MAX_FIELD_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Собирает результат из символов пароля
+fn finish(chars: &[char], entropy_bits: f64) -> GeneratedPassword {
+    GeneratedPassword {
+        password: SecretField::from(chars.iter().collect::<String>()),
+        length: chars.len(),
+        entropy_bits,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random(policy: RandomPolicy) -> Result<GeneratedPassword, String> {
+        generate_password(&PasswordPolicy::Random(policy))
+    }
+
+    fn classes(sizes: &[(usize, usize)]) -> Vec<(Vec<char>, usize)> {
+        sizes.iter().map(|&(size, min)| (vec!['x'; size], min)).collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn random_respects_length_and_minimums() {
+        let policy = RandomPolicy {
+            length: 12,
+            min_digits: 4,
+            min_symbols: 3,
+            ..RandomPolicy::default()
+        };
+        let generated = random(policy).unwrap();
+        let password = generated.password.expose();
+
+        assert_eq!(generated.length, 12);
+        assert!(password.chars().filter(|c| DIGITS.contains(*c)).count() >= 4);
+        assert!(password.chars().filter(|c| SYMBOLS.contains(*c)).count() >= 3);
+        assert!(password.chars().any(|c| c.is_ascii_lowercase()));
+        assert!(password.chars().any(|c| c.is_ascii_uppercase()));
+    }
+
+    #[test]
+    fn entropy_without_minimums_is_length_times_alphabet_bits() {
+        assert_close(random_entropy(&classes(&[(26, 0), (10, 0)]), 20), 20.0 * 36f64.log2());
+        assert_close(random_entropy(&classes(&[(94, 0)]), DEVICE_MAX_FIELD_LEN), 64.0 * 94f64.log2());
+    }
+
+    #[test]
+    fn entropy_accounts_for_minimums() {
+        // Длина 2, классы по 2 символа и минимум 1 в каждом: 2 * 2 * 2 = 8 паролей
+        assert_close(random_entropy(&classes(&[(2, 1), (2, 1)]), 2), 3.0);
+        // Длина 3, минимум 3 цифры: 10^3 паролей
+        assert_close(random_entropy(&classes(&[(26, 0), (10, 3)]), 3), 1000f64.log2());
+
+        let free = random_entropy(&classes(&[(26, 0), (26, 0), (10, 0), (27, 0)]), 8);
+        let constrained = random_entropy(&classes(&[(26, 2), (26, 2), (10, 2), (27, 2)]), 8);
+        assert!(constrained < free);
+
+        let generated = random(RandomPolicy::default()).unwrap();
+        assert!(generated.entropy_bits < 20.0 * 89f64.log2());
+    }
+
+    #[test]
+    fn contradictory_random_policies_are_rejected() {
+        let no_classes = RandomPolicy {
+            lowercase: false,
+            uppercase: false,
+            digits: false,
+            symbols: false,
+            ..RandomPolicy::default()
+        };
+        assert!(random(no_classes).is_err());
+
+        let minimums = RandomPolicy {
+            length: 4,
+            min_digits: 3,
+            ..RandomPolicy::default()
+        };
+        assert!(random(minimums).is_err());
+
+        let too_long = RandomPolicy {
+            length: DEVICE_MAX_FIELD_LEN + 1,
+            ..RandomPolicy::default()
+        };
+        assert!(random(too_long).is_err());
+    }
+
+    #[test]
+    fn passphrase_length_overflow_is_an_error() {
+        for words in [DEVICE_MAX_FIELD_LEN, usize::MAX / 2, usize::MAX] {
+            let policy = PassphrasePolicy {
+                words,
+                ..PassphrasePolicy::default()
+            };
+            assert!(generate_password(&PasswordPolicy::Passphrase(policy)).is_err());
+        }
+    }
+
+    #[test]
+    fn passphrase_fits_device_field() {
+        let generated = generate_password(&PasswordPolicy::Passphrase(PassphrasePolicy::default())).unwrap();
+
+        assert_eq!(generated.password.expose().split('-').count(), 5);
+        assert!(generated.length <= DEVICE_MAX_FIELD_LEN);
+        assert_close(generated.entropy_bits, 55.0);
+    }
+
+    #[test]
+    fn passphrase_limit_counts_bytes() {
+        // 6 слов по 8 букв и 5 разделителей по 2 символа: 58 символов
+        let ascii = PassphrasePolicy {
+            words: 6,
+            separator: "--".to_string(),
+            ..PassphrasePolicy::default()
+        };
+        let generated = generate_password(&PasswordPolicy::Passphrase(ascii)).unwrap();
+        assert!(generated.password.expose().len() <= DEVICE_MAX_FIELD_LEN);
+
+        // Тот же разделитель кириллицей занимает 4 байта: 68 байт в худшем случае
+        let cyrillic = PassphrasePolicy {
+            words: 6,
+            separator: "жж".to_string(),
+            ..PassphrasePolicy::default()
+        };
+        assert!(generate_password(&PasswordPolicy::Passphrase(cyrillic)).is_err());
+    }
+
+    #[test]
+    fn pronounceable_ends_with_digits() {
+        let generated = generate_password(&PasswordPolicy::Pronounceable(PronounceablePolicy::default())).unwrap();
+        let password = generated.password.expose();
+
+        assert_eq!(generated.length, 16);
+        assert!(password.chars().rev().take(2).all(|c| c.is_ascii_digit()));
+        assert!(password.chars().next().unwrap().is_ascii_uppercase());
+    }
+}
@@ -2,8 +2,64 @@ use aes::cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyIn
 use aes::Aes256;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::error::Error;
+use std::collections::{BTreeSet, HashSet};
 use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+use crate::modules::totp::OtpConfig;
+
+pub mod attachments;
+mod container;
+pub mod error;
+pub mod expiry;
+pub mod integrity;
+pub mod kdf;
+pub mod kinds;
+pub mod search;
+pub mod secret;
+pub mod storage;
+pub mod tree;
+
+pub use container::SessionKey;
+pub use error::{KakaduError, TreeError};
+use attachments::{Attachment, AttachmentStore};
+use kdf::KdfParams;
+use kinds::RecordKind;
+use secret::{ExposeSecret, MasterPassword, SecretField};
+use storage::BackupInfo;
+
+/// Количество резервных копий `.kkd.bak.N` по умолчанию
+pub const DEFAULT_BACKUP_COUNT: usize = 3;
+
+/// Максимальное настраиваемое количество резервных копий
+pub const MAX_BACKUP_COUNT: usize = 20;
+
+/// Способ удаления непустой группы
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteGroupMode {
+    /// Удалить группу вместе со всеми вложенными группами и записями
+    Cascade,
+    /// Перенести дочерние группы и записи в родительскую группу
+    Reparent,
+    /// Отказать в удалении, если группа не пуста
+    #[default]
+    Refuse,
+}
+
+/// Максимальное количество прежних паролей в истории записи
+pub const MAX_PASSWORD_HISTORY: usize = 10;
+
+/// Текущее время в секундах с UNIX-эпохи
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
 
 /// Символы ввода для автозаполнения форм
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -15,23 +71,109 @@ pub enum InputSymbol {
     None,  // Отсутствие специального символа
 }
 
+impl FromStr for InputSymbol {
+    type Err = String;
+
+    /// Разбирает символ ввода без учета регистра (`TAB`, `enter`, ...)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "TAB" => Ok(InputSymbol::Tab),
+            "ENTER" => Ok(InputSymbol::Enter),
+            "SPACE" => Ok(InputSymbol::Space),
+            "NONE" => Ok(InputSymbol::None),
+            _ => Err(format!("Недопустимый символ ввода: {}", s)),
+        }
+    }
+}
+
+/// Метки времени группы или записи (секунды с UNIX-эпохи)
+///
+/// Все поля необязательны: в файлах, созданных до их появления, они отсутствуют
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Timestamps {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>, // Время создания
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<u64>, // Время последнего изменения
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<u64>, // Время последнего использования
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>, // Срок действия (после него требуется смена пароля)
+}
+
+impl Timestamps {
+    /// Метки для только что созданного объекта
+    pub fn created() -> Self {
+        let now = unix_time();
+        Self {
+            created_at: Some(now),
+            modified_at: Some(now),
+            ..Default::default()
+        }
+    }
+
+    /// Отмечает изменение объекта
+    pub fn touch_modified(&mut self) {
+        self.modified_at = Some(unix_time());
+    }
+
+    /// Отмечает использование объекта
+    pub fn touch_used(&mut self) {
+        self.last_used_at = Some(unix_time());
+    }
+
+    /// Истек ли срок действия к моменту `now`
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|t| t <= now)
+    }
+}
+
 /// Группа паролей (категория)
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Group {
     pub id: u32,      // Уникальный идентификатор группы
     pub pid: u32,     // Идентификатор родительской группы
     pub name: String, // Название группы
+
+    #[serde(flatten)]
+    pub times: Timestamps, // Время создания, изменения, использования и срок действия
+}
+
+impl Group {
+    /// Копия группы только с полями, известными устройству Crypto Kakadu
+    pub fn to_legacy(&self) -> Group {
+        Group {
+            id: self.id,
+            pid: self.pid,
+            name: self.name.clone(),
+            times: Timestamps::default(),
+        }
+    }
+
+    /// Создает группу с текущим временем создания
+    pub fn new(id: u32, pid: u32, name: String) -> Self {
+        Self {
+            id,
+            pid,
+            name,
+            times: Timestamps::created(),
+        }
+    }
 }
 
 /// Запись с данными пароля
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Record {
-    pub id: u32,          // Уникальный идентификатор записи
-    pub pid: u32,         // Идентификатор родительской группы
-    pub name: String,     // Название записи
-    pub login: String,    // Логин
-    pub password: String, // Пароль
-    pub url: String,      // URL сайта
+    pub id: u32,               // Уникальный идентификатор записи
+    pub pid: u32,              // Идентификатор родительской группы
+    pub name: String,          // Название записи
+    pub login: String,         // Логин
+    pub password: SecretField, // Пароль (затирается в памяти, скрыт в Debug)
+    pub url: String,           // URL сайта
 
     #[serde(rename = "loginSymbol")]
     pub login_symbol: InputSymbol, // Символ после логина
@@ -41,6 +183,119 @@ pub struct Record {
 
     #[serde(rename = "urlSymbol")]
     pub url_symbol: InputSymbol, // Символ после URL
+
+    #[serde(default, skip_serializing_if = "RecordKind::is_login")]
+    pub kind: RecordKind, // Вид записи (логин, карта, SSH-ключ, ...) и его данные
+
+    #[serde(flatten)]
+    pub times: Timestamps, // Время создания, изменения, использования и срок действия
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otp: Option<OtpConfig>, // Параметры одноразовых паролей (TOTP/HOTP)
+
+    #[serde(rename = "customFields", default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_fields: Vec<CustomField>, // Дополнительные поля в порядке отображения
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String, // Многострочные заметки
+
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>, // Метки записи
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>, // Вложения (содержимое хранится отдельно от JSON)
+
+    /// Предыдущие пароли, от свежих к старым (не более `MAX_PASSWORD_HISTORY`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<PasswordHistoryEntry>,
+}
+
+/// Дополнительное поле записи (секретный вопрос, PIN-код, API-ключ и т.п.)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CustomField {
+    pub name: String,       // Название поля
+    pub value: SecretField, // Значение (затирается в памяти, скрыто в Debug)
+
+    #[serde(default)]
+    pub hidden: bool, // Скрывать значение в интерфейсе, как пароль
+}
+
+/// Прежнее значение пароля записи
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PasswordHistoryEntry {
+    pub password: SecretField, // Прежний пароль
+
+    #[serde(rename = "changedAt")]
+    pub changed_at: u64, // Время замены (секунды с UNIX-эпохи)
+}
+
+impl Record {
+    /// Копия записи только с полями, известными устройству Crypto Kakadu
+    ///
+    /// Каждое поле перечислено явно, чтобы новое поле записи не попало
+    /// в экспорт для устройства незамеченным
+    pub fn to_legacy(&self) -> Record {
+        Record {
+            id: self.id,
+            pid: self.pid,
+            name: self.name.clone(),
+            login: self.login.clone(),
+            password: self.password.clone(),
+            url: self.url.clone(),
+            login_symbol: self.login_symbol.clone(),
+            password_symbol: self.password_symbol.clone(),
+            url_symbol: self.url_symbol.clone(),
+            kind: RecordKind::Login,
+            times: Timestamps::default(),
+            otp: None,
+            custom_fields: Vec::new(),
+            notes: String::new(),
+            tags: BTreeSet::new(),
+            attachments: Vec::new(),
+            history: Vec::new(),
+        }
+    }
+
+    /// Есть ли у записи метка (без учета регистра)
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.trim().to_lowercase();
+        self.tags.iter().any(|t| t.to_lowercase() == tag)
+    }
+
+    /// Сохраняет прежний пароль в истории, если он отличается от текущего
+    ///
+    /// Пустые пароли в историю не попадают; самые старые записи истории
+    /// отбрасываются сверх `MAX_PASSWORD_HISTORY`
+    pub fn remember_password(&mut self, previous: &SecretField) {
+        if previous.is_empty() || *previous == self.password {
+            return;
+        }
+
+        self.history.insert(
+            0,
+            PasswordHistoryEntry {
+                password: previous.clone(),
+                changed_at: unix_time(),
+            },
+        );
+        self.history.truncate(MAX_PASSWORD_HISTORY);
+    }
+
+    /// Восстанавливает пароль из истории; текущий пароль сохраняется в истории
+    ///
+    /// # Возвращает
+    /// `false`, если записи истории с таким индексом нет
+    pub fn restore_password(&mut self, index: usize) -> bool {
+        if index >= self.history.len() {
+            return false;
+        }
+
+        let entry = self.history.remove(index);
+        let previous = std::mem::replace(&mut self.password, entry.password.clone());
+        self.remember_password(&previous);
+        self.times.touch_modified();
+        true
+    }
 }
 
 /// Основная структура данных паролей
@@ -48,44 +303,336 @@ pub struct Record {
 pub struct PasswordData {
     pub groups: Vec<Group>,   // Список групп
     pub records: Vec<Record>, // Список записей
+
+    /// Счетчик ID: следующий выдаваемый ID. Растет монотонно и сохраняется в файле,
+    /// поэтому ID удаленных групп и записей повторно не выдаются
+    #[serde(rename = "nextId", default)]
+    pub next_id: u32,
+
+    /// Содержимое вложений записей. Не входит в JSON: хранится в контейнере
+    /// отдельной двоичной секцией (см. `attachments::encode_payload`)
+    #[serde(skip)]
+    pub attachments: AttachmentStore,
+}
+
+/// Вид объекта, которому переназначен ID
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IdOwner {
+    Group,
+    Record,
+}
+
+/// Сведения о переназначенном при загрузке ID
+#[derive(Debug, Serialize, Clone)]
+pub struct IdRepair {
+    pub owner: IdOwner, // Группа или запись
+    pub old_id: u32,    // Исходный (дублирующийся) ID
+    pub new_id: u32,    // Выданный новый ID
+}
+
+impl PasswordData {
+    /// Максимальный ID среди групп и записей
+    fn max_id(&self) -> u32 {
+        self.groups
+            .iter()
+            .map(|g| g.id)
+            .chain(self.records.iter().map(|r| r.id))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Выделяет новый уникальный ID, общий для групп и записей
+    ///
+    /// Счетчик не опускается ниже максимального существующего ID,
+    /// поэтому файлы без `nextId` и с ID, добавленными извне, обрабатываются корректно
+    ///
+    /// # Ошибки
+    /// `IdsExhausted`, если следующий ID вышел бы за `u32::MAX`
+    pub fn allocate_id(&mut self) -> Result<u32, KakaduError> {
+        let id = self
+            .max_id()
+            .checked_add(1)
+            .map(|id| id.max(self.next_id))
+            .ok_or(KakaduError::IdsExhausted)?;
+        self.next_id = id.checked_add(1).ok_or(KakaduError::IdsExhausted)?;
+        Ok(id)
+    }
+
+    /// Находит повторяющиеся (и нулевые) ID и выдает таким объектам новые
+    ///
+    /// Первый по порядку объект (сначала группы, затем записи) сохраняет свой ID,
+    /// поэтому ссылки `pid` продолжают указывать на него
+    ///
+    /// # Возвращает
+    /// Список переназначений; пустой, если дубликатов нет
+    ///
+    /// # Ошибки
+    /// `IdsExhausted`, если для дубликатов не хватило свободных ID
+    pub fn repair_ids(&mut self) -> Result<Vec<IdRepair>, KakaduError> {
+        let mut seen = HashSet::from([0]);
+        let mut duplicates = Vec::new();
+
+        for (index, group) in self.groups.iter().enumerate() {
+            if !seen.insert(group.id) {
+                duplicates.push((IdOwner::Group, index));
+            }
+        }
+        for (index, record) in self.records.iter().enumerate() {
+            if !seen.insert(record.id) {
+                duplicates.push((IdOwner::Record, index));
+            }
+        }
+
+        duplicates
+            .into_iter()
+            .map(|(owner, index)| {
+                let new_id = self.allocate_id()?;
+                let id = match owner {
+                    IdOwner::Group => &mut self.groups[index].id,
+                    IdOwner::Record => &mut self.records[index].id,
+                };
+                let old_id = std::mem::replace(id, new_id);
+                Ok(IdRepair { owner, old_id, new_id })
+            })
+            .collect()
+    }
+
+    /// Проверяет, существует ли группа с указанным ID
+    pub fn has_group(&self, group_id: u32) -> bool {
+        self.groups.iter().any(|g| g.id == group_id)
+    }
+
+    /// Возвращает ID всех вложенных групп (без самой группы)
+    ///
+    /// Обход устойчив к циклам в `pid`: каждая группа посещается один раз
+    pub fn descendant_group_ids(&self, group_id: u32) -> Vec<u32> {
+        let mut visited = HashSet::from([group_id]);
+        let mut result = Vec::new();
+        let mut queue = vec![group_id];
+
+        while let Some(current) = queue.pop() {
+            for child in self.groups.iter().filter(|g| g.pid == current) {
+                if visited.insert(child.id) {
+                    result.push(child.id);
+                    queue.push(child.id);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Удаляет группу
+    ///
+    /// # Аргументы
+    /// * `group_id` - ID удаляемой группы
+    /// * `mode` - что делать с содержимым группы (см. `DeleteGroupMode`)
+    ///
+    /// # Ошибки
+    /// `GroupNotFound`, `RootGroup` для корневой группы (`pid == 0`) и `GroupNotEmpty`
+    /// для непустой группы в режиме `Refuse`. При ошибке данные не изменяются
+    pub fn delete_group(&mut self, group_id: u32, mode: DeleteGroupMode) -> Result<(), TreeError> {
+        let group = self.groups.iter().find(|g| g.id == group_id).ok_or(TreeError::GroupNotFound)?;
+        if group.pid == 0 {
+            return Err(TreeError::RootGroup);
+        }
+        let parent_id = group.pid;
+
+        let is_empty = !self.groups.iter().any(|g| g.pid == group_id) && !self.records.iter().any(|r| r.pid == group_id);
+
+        match mode {
+            DeleteGroupMode::Refuse if !is_empty => return Err(TreeError::GroupNotEmpty),
+            DeleteGroupMode::Refuse => {}
+            DeleteGroupMode::Cascade => {
+                let mut removed: HashSet<u32> = self.descendant_group_ids(group_id).into_iter().collect();
+                removed.insert(group_id);
+
+                self.groups.retain(|g| !removed.contains(&g.id));
+                self.records.retain(|r| !removed.contains(&r.pid));
+            }
+            DeleteGroupMode::Reparent => {
+                self.groups.iter_mut().filter(|g| g.pid == group_id).for_each(|g| g.pid = parent_id);
+                self.records.iter_mut().filter(|r| r.pid == group_id).for_each(|r| r.pid = parent_id);
+            }
+        }
+
+        self.groups.retain(|g| g.id != group_id);
+        Ok(())
+    }
+
+    /// Переносит группу в другую родительскую группу
+    ///
+    /// # Возвращает
+    /// Перенесенную группу
+    ///
+    /// # Ошибки
+    /// `GroupNotFound`, если нет группы или нового родителя; `GroupCycle`, если новый
+    /// родитель - сама группа или ее потомок; `RootGroup` для корневой группы
+    pub fn move_group(&mut self, group_id: u32, new_parent_id: u32) -> Result<&Group, TreeError> {
+        if !self.has_group(new_parent_id) {
+            return Err(TreeError::GroupNotFound);
+        }
+        if new_parent_id == group_id || self.descendant_group_ids(group_id).contains(&new_parent_id) {
+            return Err(TreeError::GroupCycle);
+        }
+
+        let group = self.groups.iter_mut().find(|g| g.id == group_id).ok_or(TreeError::GroupNotFound)?;
+        if group.pid == 0 {
+            return Err(TreeError::RootGroup);
+        }
+        group.pid = new_parent_id;
+        group.times.touch_modified();
+
+        Ok(group)
+    }
+
+    /// Переносит записи в группу `new_group_id` целиком или не переносит вовсе
+    ///
+    /// # Возвращает
+    /// ID исходных групп в порядке появления переносимых записей
+    ///
+    /// # Ошибки
+    /// `GroupNotFound` для несуществующей целевой группы, `RecordNotFound` для первой
+    /// ненайденной записи. При ошибке данные не изменяются
+    pub fn move_records(&mut self, ids: &[u32], new_group_id: u32) -> Result<Vec<u32>, TreeError> {
+        if !self.has_group(new_group_id) {
+            return Err(TreeError::GroupNotFound);
+        }
+
+        let mut source_groups = Vec::new();
+        for id in ids {
+            let record = self.records.iter().find(|r| r.id == *id).ok_or(TreeError::RecordNotFound(*id))?;
+            if !source_groups.contains(&record.pid) {
+                source_groups.push(record.pid);
+            }
+        }
+
+        self.records.iter_mut().filter(|r| ids.contains(&r.id)).for_each(|r| {
+            r.pid = new_group_id;
+            r.times.touch_modified();
+        });
+
+        Ok(source_groups)
+    }
+
+    /// Копия данных для устройства Crypto Kakadu
+    ///
+    /// Устройство не знает о полях, добавленных в формат v2, поэтому они удаляются:
+    /// вид записи, история паролей, метки времени, параметры OTP, дополнительные поля,
+    /// заметки, метки и вложения не экспортируются (см. `Record::to_legacy`)
+    pub fn to_legacy(&self) -> PasswordData {
+        PasswordData {
+            groups: self.groups.iter().map(Group::to_legacy).collect(),
+            records: self.records.iter().map(Record::to_legacy).collect(),
+            next_id: self.next_id,
+            attachments: AttachmentStore::default(),
+        }
+    }
+
+    /// Отпечаток содержимого (SHA-256 от JSON) для обнаружения несохраненных изменений
+    pub fn digest(&self) -> Result<[u8; 32], KakaduError> {
+        let json_data = Zeroizing::new(serde_json::to_vec(self)?);
+        Ok(Sha256::digest(&*json_data).into())
+    }
 }
 
 /// Провайдер для работы с зашифрованными файлами паролей
-pub struct KakaduProvider;
+pub struct KakaduProvider {
+    pub backup_count: usize, // Сколько резервных копий хранить при сохранении
+}
+
+impl Default for KakaduProvider {
+    fn default() -> Self {
+        Self {
+            backup_count: DEFAULT_BACKUP_COUNT,
+        }
+    }
+}
 
 impl KakaduProvider {
     /// Открывает и расшифровывает файл с паролями
     ///
+    /// Поддерживает контейнер v2 и устаревший формат без заголовка
+    ///
     /// # Аргументы
     /// * `path` - путь к файлу
     ///
     /// # Возвращает
-    /// Result с PasswordData или ошибкой
-    pub fn open_file(&self, path: &str, password: &str) -> Result<PasswordData, Box<dyn Error>> {
+    /// Result с PasswordData или типизированной ошибкой `KakaduError`
+    pub fn open_file(&self, path: &str, password: &MasterPassword) -> Result<PasswordData, KakaduError> {
+        self.open_session(path, password).map(|(data, _)| data)
+    }
+
+    /// Открывает файл и возвращает ключ сессии для сохранения без мастер-пароля
+    ///
+    /// Для устаревших файлов и файлов с KDF только для чтения ключ сессии не возвращается:
+    /// ключ с параметрами по умолчанию получается только при сохранении в новом формате
+    /// (`save_file` с паролем), чтобы открытие не тратило время и память на лишний Argon2id
+    pub fn open_session(
+        &self,
+        path: &str,
+        password: &MasterPassword,
+    ) -> Result<(PasswordData, Option<SessionKey>), KakaduError> {
+        let password = password.expose_secret();
+
         // Чтение зашифрованных данных из файла
-        let encrypted_data = fs::read(&path)?;
+        let encrypted_data = fs::read(path)?;
 
-        // Расшифровка данных
-        let decrypted_data = Self::decrypt_data(&encrypted_data, password)?;
+        // Файл v2 аутентифицирован, поэтому ошибка JSON означает именно ошибку формата данных
+        if container::is_container(&encrypted_data) {
+            let (decrypted_data, session) = container::open(&encrypted_data, password)?;
+            let (json_data, attachments) = attachments::decode_payload(&decrypted_data)?;
+            let mut password_data: PasswordData = serde_json::from_slice(json_data)?;
+            password_data.attachments = attachments;
 
-        // Десериализация JSON
-        let password_data: PasswordData = serde_json::from_slice(&decrypted_data)?;
+            let session = Some(session).filter(|s| s.kdf().is_writable());
+            return Ok((password_data, session));
+        }
+
+        // Устаревший формат не содержит проверки пароля: некорректное выравнивание
+        // или мусор вместо JSON после расшифровки почти наверняка означают неверный пароль
+        let decrypted_data = match Self::decrypt_data(&encrypted_data, password) {
+            Err(KakaduError::BadPadding) => return Err(KakaduError::WrongPassword),
+            result => result?,
+        };
+        if decrypted_data.first() != Some(&b'{') {
+            return Err(KakaduError::WrongPassword);
+        }
+
+        // Синтаксически верный JSON неподходящей структуры - это ошибка формата, а не пароля
+        let password_data: PasswordData = serde_json::from_slice(&decrypted_data).map_err(|e| {
+            if e.is_data() {
+                KakaduError::Json(e)
+            } else {
+                KakaduError::WrongPassword
+            }
+        })?;
 
-        Ok(password_data)
+        Ok((password_data, None))
     }
 
-    /// Расшифровывает данные с помощью AES-256
-    fn decrypt_data(source_array: &[u8], password: &str) -> Result<Vec<u8>, Box<dyn Error>> {
-        // Генерация 256-битного ключа из пароля с помощью SHA-256
-        let mut hasher = Sha256::new();
-        hasher.update(password.as_bytes());
-        let key = hasher.finalize();
+    /// Получает новый ключ сессии из пароля с параметрами KDF по умолчанию
+    pub fn derive_session_key(&self, password: &MasterPassword) -> Result<SessionKey, KakaduError> {
+        SessionKey::derive(password.expose_secret(), KdfParams::default())
+    }
+
+    /// Расшифровывает данные устаревшего формата (AES-256 ECB без заголовка)
+    fn decrypt_data(source_array: &[u8], password: &str) -> Result<Zeroizing<Vec<u8>>, KakaduError> {
+        let block_size = 16; // AES block size (128 бит)
+
+        // Данные ECB всегда кратны размеру блока
+        if source_array.is_empty() || !source_array.len().is_multiple_of(block_size) {
+            return Err(KakaduError::Truncated);
+        }
+
+        // Генерация 256-битного ключа устаревшим способом (SHA-256 без соли)
+        let key = kdf::legacy_key(password);
 
         // Инициализация AES-256 дешифратора
-        let cipher = Aes256::new_from_slice(&key)?;
+        let cipher = Aes256::new_from_slice(key.expose_secret()).map_err(|e| KakaduError::Crypto(e.to_string()))?;
 
-        let block_size = 16; // AES block size (128 бит)
-        let mut decrypted = Vec::with_capacity(source_array.len());
+        let mut decrypted = Zeroizing::new(Vec::with_capacity(source_array.len()));
 
         // Обработка данных блоками
         for chunk in source_array.chunks(block_size) {
@@ -95,37 +642,101 @@ impl KakaduProvider {
         }
 
         // Удаление PKCS7 padding
-        Self::remove_padding(&mut decrypted, block_size);
+        Self::remove_padding(&mut decrypted, block_size)?;
 
         Ok(decrypted)
     }
 
     /// Сохраняет данные паролей в зашифрованный файл
-    pub fn save_file(&self, path: String, password: &str, data: &PasswordData) -> Result<(), Box<dyn Error>> {
-        // Сериализация в JSON
-        let json_data = serde_json::to_vec(data)?;
+    ///
+    /// Всегда записывает контейнер актуальной версии формата.
+    /// Запись атомарная: предыдущая версия файла уходит в резервную копию `.bak.1`
+    pub fn save_file(&self, path: String, password: &MasterPassword, data: &PasswordData) -> Result<(), KakaduError> {
+        self.save_with_key(path, &self.derive_session_key(password)?, data)
+    }
 
-        // Шифрование данных
-        let encrypted_data = Self::encrypt_data(&json_data, password)?;
+    /// Сохраняет данные паролей, используя ключ сессии вместо мастер-пароля
+    pub fn save_with_key(&self, path: String, session: &SessionKey, data: &PasswordData) -> Result<(), KakaduError> {
+        let encrypted_data = Self::seal(session, data)?;
 
-        // Запись в файл
-        fs::write(path, encrypted_data)?;
+        // Атомарная запись с ротацией резервных копий
+        storage::write_atomic(Path::new(&path), &encrypted_data, self.backup_count)?;
 
         Ok(())
     }
 
-    /// Шифрует данные с помощью AES-256
-    fn encrypt_data(source_data: &[u8], password: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    /// Перешифровывает резервные копии файла ключом сессии после смены мастер-пароля
+    ///
+    /// # Поведение
+    /// Копии, которые открываются старым паролем, перезаписываются на месте новым ключом,
+    /// чтобы на диске не оставалось данных под старым паролем. Просматриваются номера
+    /// до `MAX_BACKUP_COUNT`, включая копии сверх текущего `backup_count`
+    ///
+    /// # Возвращает
+    /// Копии, которые не удалось открыть старым паролем (записаны с еще более ранним
+    /// паролем или повреждены): они остались на диске без изменений
+    pub fn reencrypt_backups(
+        &self,
+        path: &str,
+        old_password: &MasterPassword,
+        session: &SessionKey,
+    ) -> Result<Vec<BackupInfo>, KakaduError> {
+        let mut skipped = Vec::new();
+
+        for backup in storage::list_backups(Path::new(path), self.backup_count.max(MAX_BACKUP_COUNT))? {
+            match self.open_file(&backup.path, old_password) {
+                Ok(data) => storage::write_atomic(Path::new(&backup.path), &Self::seal(session, &data)?, 0)?,
+                Err(_) => skipped.push(backup),
+            }
+        }
+
+        Ok(skipped)
+    }
+
+    /// Шифрует данные и вложения в контейнер актуальной версии
+    fn seal(session: &SessionKey, data: &PasswordData) -> Result<Vec<u8>, KakaduError> {
+        // Сериализация в JSON (буфер с открытыми данными затирается после шифрования)
+        let json_data = Zeroizing::new(serde_json::to_vec(data)?);
+
+        // Вложения добавляются двоичной секцией после JSON
+        let payload = attachments::encode_payload(&json_data, &data.records, &data.attachments);
+
+        // Шифрование данных в контейнер v2 (новый nonce при каждом сохранении)
+        container::seal_with_key(&payload, session)
+    }
+
+    /// Сохраняет данные в устаревшем формате без заголовка для устройства Crypto Kakadu
+    ///
+    /// Записываются только поля, известные устройству (см. `PasswordData::to_legacy`)
+    pub fn save_legacy_file(&self, path: String, password: &MasterPassword, data: &PasswordData) -> Result<(), KakaduError> {
+        let json_data = Zeroizing::new(serde_json::to_vec(&data.to_legacy())?);
+        let encrypted_data = Self::encrypt_data(&json_data, password.expose_secret())?;
+        storage::write_atomic(Path::new(&path), &encrypted_data, 0)?;
+
+        Ok(())
+    }
+
+    /// Возвращает резервные копии файла с номерами до `backup_count`, от свежих к старым
+    pub fn list_backups(&self, path: &str) -> Result<Vec<BackupInfo>, KakaduError> {
+        Ok(storage::list_backups(Path::new(path), self.backup_count)?)
+    }
+
+    /// Восстанавливает файл из резервной копии с номером `index`.
+    /// Текущая версия файла сохраняется как `.bak.1`
+    pub fn restore_backup(&self, path: &str, index: usize) -> Result<(), KakaduError> {
+        Ok(storage::restore_backup(Path::new(path), index, self.backup_count)?)
+    }
+
+    /// Шифрует данные в устаревшем формате (AES-256 ECB без заголовка)
+    fn encrypt_data(source_data: &[u8], password: &str) -> Result<Vec<u8>, KakaduError> {
         // Генерация ключа (аналогично decrypt_data)
-        let mut hasher = Sha256::new();
-        hasher.update(password.as_bytes());
-        let key = hasher.finalize();
+        let key = kdf::legacy_key(password);
 
-        let cipher = Aes256::new_from_slice(&key)?;
+        let cipher = Aes256::new_from_slice(key.expose_secret()).map_err(|e| KakaduError::Crypto(e.to_string()))?;
         let block_size = 16;
 
         // Добавление PKCS7 padding
-        let mut padded_data = source_data.to_vec();
+        let mut padded_data = Zeroizing::new(source_data.to_vec());
         Self::add_padding(&mut padded_data, block_size);
 
         let mut encrypted = Vec::with_capacity(padded_data.len());
@@ -147,18 +758,430 @@ impl KakaduProvider {
     }
 
     /// Удаляет PKCS7 padding из данных
-    fn remove_padding(data: &mut Vec<u8>, block_size: usize) {
-        if let Some(&last_byte) = data.last() {
-            if last_byte <= block_size as u8 {
-                let pad_len = last_byte as usize;
-                if data.len() >= pad_len {
-                    let padding_ok = data[data.len() - pad_len..].iter().all(|&b| b == last_byte);
-
-                    if padding_ok {
-                        data.truncate(data.len() - pad_len);
-                    }
-                }
-            }
+    ///
+    /// # Ошибки
+    /// `KakaduError::BadPadding`, если выравнивание некорректно
+    fn remove_padding(data: &mut Vec<u8>, block_size: usize) -> Result<(), KakaduError> {
+        let last_byte = *data.last().ok_or(KakaduError::Truncated)?;
+        let pad_len = last_byte as usize;
+
+        if pad_len == 0 || pad_len > block_size || data.len() < pad_len {
+            return Err(KakaduError::BadPadding);
+        }
+
+        let padding_ok = data[data.len() - pad_len..].iter().all(|&b| b == last_byte);
+        if !padding_ok {
+            return Err(KakaduError::BadPadding);
         }
+
+        data.truncate(data.len() - pad_len);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Временный файл теста, удаляемый при освобождении
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("kakadu-{}-{}.kkd", std::process::id(), name)))
+        }
+
+        fn path(&self) -> String {
+            self.0.to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn password(value: &str) -> MasterPassword {
+        MasterPassword::from(value.to_string())
+    }
+
+    fn sample_data() -> PasswordData {
+        let record: Record = serde_json::from_value(serde_json::json!({
+            "id": 2, "pid": 1, "name": "Почта", "login": "bob", "password": "hunter2", "url": "https://mail.example",
+            "loginSymbol": "TAB", "passwordSymbol": "ENTER", "urlSymbol": "NONE"
+        }))
+        .unwrap();
+
+        PasswordData {
+            groups: vec![Group::new(1, 0, "Root".to_string())],
+            records: vec![record],
+            next_id: 3,
+            attachments: AttachmentStore::default(),
+        }
+    }
+
+    /// Ключ сессии с дешевыми параметрами Argon2id
+    fn test_session(value: &str) -> SessionKey {
+        let kdf = KdfParams::Argon2id {
+            m_cost_kib: 8,
+            t_cost: 1,
+            p_cost: 1,
+        };
+        SessionKey::derive(value, kdf).unwrap()
+    }
+
+    fn open_error(file: &TempFile, value: &str) -> KakaduError {
+        KakaduProvider::default().open_file(&file.path(), &password(value)).unwrap_err()
+    }
+
+    fn write_v2(file: &TempFile, value: &str, data: &PasswordData) {
+        let provider = KakaduProvider { backup_count: 0 };
+        provider.save_with_key(file.path(), &test_session(value), data).unwrap();
+    }
+
+    /// Дерево: 1 (корень) → 2 → 3, 1 → 4; записи 10 в группе 2 и 11 в группе 3
+    fn tree_data() -> PasswordData {
+        let record = |id: u32, pid: u32| -> Record {
+            serde_json::from_value(serde_json::json!({
+                "id": id, "pid": pid, "name": "Запись", "login": "", "password": "", "url": "",
+                "loginSymbol": "TAB", "passwordSymbol": "ENTER", "urlSymbol": "NONE"
+            }))
+            .unwrap()
+        };
+
+        PasswordData {
+            groups: [(1, 0), (2, 1), (3, 2), (4, 1)]
+                .map(|(id, pid)| Group::new(id, pid, format!("Группа {}", id)))
+                .into(),
+            records: vec![record(10, 2), record(11, 3)],
+            next_id: 12,
+            attachments: AttachmentStore::default(),
+        }
+    }
+
+    fn group_parents(data: &PasswordData) -> Vec<(u32, u32)> {
+        data.groups.iter().map(|g| (g.id, g.pid)).collect()
+    }
+
+    fn record_parents(data: &PasswordData) -> Vec<(u32, u32)> {
+        data.records.iter().map(|r| (r.id, r.pid)).collect()
+    }
+
+    #[test]
+    fn delete_group_cascade_removes_subtree() {
+        let mut data = tree_data();
+        data.delete_group(2, DeleteGroupMode::Cascade).unwrap();
+
+        assert_eq!(group_parents(&data), [(1, 0), (4, 1)]);
+        assert!(data.records.is_empty());
+    }
+
+    #[test]
+    fn delete_group_reparent_moves_children_up() {
+        let mut data = tree_data();
+        data.delete_group(2, DeleteGroupMode::Reparent).unwrap();
+
+        assert_eq!(group_parents(&data), [(1, 0), (3, 1), (4, 1)]);
+        assert_eq!(record_parents(&data), [(10, 1), (11, 3)]);
+    }
+
+    #[test]
+    fn delete_group_refuse_only_removes_empty_groups() {
+        let mut data = tree_data();
+        assert_eq!(data.delete_group(2, DeleteGroupMode::Refuse), Err(TreeError::GroupNotEmpty));
+        assert_eq!(data.delete_group(3, DeleteGroupMode::default()), Err(TreeError::GroupNotEmpty));
+        assert_eq!(group_parents(&data), group_parents(&tree_data()));
+
+        data.delete_group(4, DeleteGroupMode::Refuse).unwrap();
+        assert_eq!(group_parents(&data), [(1, 0), (2, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn delete_group_protects_root_and_reports_missing_group() {
+        let mut data = tree_data();
+        for mode in [DeleteGroupMode::Cascade, DeleteGroupMode::Reparent, DeleteGroupMode::Refuse] {
+            assert_eq!(data.delete_group(1, mode), Err(TreeError::RootGroup));
+            assert_eq!(data.delete_group(99, mode), Err(TreeError::GroupNotFound));
+        }
+        assert_eq!(group_parents(&data), group_parents(&tree_data()));
+        assert_eq!(record_parents(&data), record_parents(&tree_data()));
+    }
+
+    #[test]
+    fn move_group_rejects_cycles() {
+        let mut data = tree_data();
+
+        assert_eq!(data.move_group(2, 2).unwrap_err(), TreeError::GroupCycle);
+        assert_eq!(data.move_group(2, 3).unwrap_err(), TreeError::GroupCycle);
+        assert_eq!(data.move_group(1, 4).unwrap_err(), TreeError::GroupCycle);
+        assert_eq!(group_parents(&data), group_parents(&tree_data()));
+
+        assert_eq!(data.move_group(3, 4).unwrap().pid, 4);
+        assert_eq!(group_parents(&data), [(1, 0), (2, 1), (3, 4), (4, 1)]);
+    }
+
+    #[test]
+    fn move_group_reports_missing_groups_and_root() {
+        let mut data = tree_data();
+
+        assert_eq!(data.move_group(2, 99).unwrap_err(), TreeError::GroupNotFound);
+        assert_eq!(data.move_group(99, 2).unwrap_err(), TreeError::GroupNotFound);
+
+        // Второй корень, не связанный с первым: цикла нет, но корень не переносится
+        data.groups.push(Group::new(5, 0, "Корень 2".to_string()));
+        assert_eq!(data.move_group(5, 2).unwrap_err(), TreeError::RootGroup);
+    }
+
+    #[test]
+    fn move_records_is_all_or_nothing() {
+        let mut data = tree_data();
+
+        assert_eq!(data.move_records(&[10], 99), Err(TreeError::GroupNotFound));
+        assert_eq!(data.move_records(&[10, 42], 4), Err(TreeError::RecordNotFound(42)));
+        assert_eq!(record_parents(&data), record_parents(&tree_data()));
+
+        assert_eq!(data.move_records(&[11, 10], 4), Ok(vec![3, 2]));
+        assert_eq!(record_parents(&data), [(10, 4), (11, 4)]);
+    }
+
+    #[test]
+    fn reencrypt_backups_moves_old_password_copies_to_new_key() {
+        let file = TempFile::new("reencrypt");
+        let provider = KakaduProvider { backup_count: 3 };
+        let (old, new) = (test_session("old"), test_session("new"));
+
+        // .bak.3 - с еще более ранним паролем, .bak.2 и .bak.1 - со старым
+        for session in [&test_session("older"), &old, &old, &new] {
+            provider.save_with_key(file.path(), session, &sample_data()).unwrap();
+        }
+        let backups: Vec<_> = (1..=3).map(|i| storage::backup_path(&file.0, i)).collect();
+        let _cleanup: Vec<_> = backups.iter().map(|b| TempFile(b.clone())).collect();
+
+        let skipped = provider.reencrypt_backups(&file.path(), &password("old"), &new).unwrap();
+
+        assert_eq!(skipped.iter().map(|b| b.index).collect::<Vec<_>>(), [3]);
+        for backup in &backups[..2] {
+            let backup = backup.to_string_lossy();
+            assert_eq!(provider.open_file(&backup, &password("old")).unwrap_err().code(), "wrong_password");
+            let data = provider.open_file(&backup, &password("new")).unwrap();
+            assert_eq!(data.digest().unwrap(), sample_data().digest().unwrap());
+        }
+        assert!(provider.open_file(&backups[2].to_string_lossy(), &password("older")).is_ok());
+    }
+
+    #[test]
+    fn v2_round_trip_keeps_writable_session() {
+        let file = TempFile::new("v2-round-trip");
+        write_v2(&file, "pw", &sample_data());
+
+        let (data, session) = KakaduProvider::default().open_session(&file.path(), &password("pw")).unwrap();
+        assert_eq!(data.digest().unwrap(), sample_data().digest().unwrap());
+        assert!(session.is_some());
+    }
+
+    #[test]
+    fn legacy_round_trip_has_no_session() {
+        let file = TempFile::new("legacy-round-trip");
+        let provider = KakaduProvider::default();
+        provider.save_legacy_file(file.path(), &password("pw"), &sample_data()).unwrap();
+
+        let (data, session) = provider.open_session(&file.path(), &password("pw")).unwrap();
+        assert_eq!(data.records[0].password.expose(), "hunter2");
+        assert!(session.is_none());
+    }
+
+    #[test]
+    fn wrong_password_on_v2_file() {
+        let file = TempFile::new("v2-wrong");
+        write_v2(&file, "pw", &sample_data());
+        assert_eq!(open_error(&file, "other").code(), "wrong_password");
+    }
+
+    #[test]
+    fn wrong_password_on_legacy_file() {
+        let file = TempFile::new("legacy-wrong");
+        KakaduProvider::default()
+            .save_legacy_file(file.path(), &password("pw"), &sample_data())
+            .unwrap();
+
+        // Неверный пароль почти всегда дает некорректное выравнивание - оно не должно
+        // выдаваться за повреждение файла
+        for guess in ["other", "pw ", "PW", "1234", "hunter2", ""] {
+            assert_eq!(open_error(&file, guess).code(), "wrong_password", "пароль {:?}", guess);
+        }
+    }
+
+    #[test]
+    fn truncated_files() {
+        let file = TempFile::new("truncated");
+        write_v2(&file, "pw", &sample_data());
+        let content = fs::read(&file.0).unwrap();
+
+        fs::write(&file.0, &content[..10]).unwrap();
+        assert_eq!(open_error(&file, "pw").code(), "truncated");
+
+        // Устаревший формат: длина не кратна блоку AES
+        fs::write(&file.0, [0u8; 17]).unwrap();
+        assert_eq!(open_error(&file, "pw").code(), "truncated");
+
+        fs::write(&file.0, []).unwrap();
+        assert_eq!(open_error(&file, "pw").code(), "truncated");
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let file = TempFile::new("version");
+        write_v2(&file, "pw", &sample_data());
+        let mut content = fs::read(&file.0).unwrap();
+        content[container::MAGIC.len()] = 9;
+        fs::write(&file.0, &content).unwrap();
+
+        let error = open_error(&file, "pw");
+        assert_eq!(error.code(), "unsupported_version");
+        assert!(matches!(error, KakaduError::UnsupportedVersion(9)));
+    }
+
+    #[test]
+    fn corrupted_ciphertext() {
+        let file = TempFile::new("corrupted");
+        write_v2(&file, "pw", &sample_data());
+        let mut content = fs::read(&file.0).unwrap();
+        let last = content.len() - 1;
+        content[last] ^= 1;
+        fs::write(&file.0, &content).unwrap();
+
+        assert_eq!(open_error(&file, "pw").code(), "corrupted");
+    }
+
+    #[test]
+    fn json_errors_with_correct_password() {
+        let file = TempFile::new("json");
+        let encrypted = container::seal_with_key(b"{\"groups\": 5}", &test_session("pw")).unwrap();
+        fs::write(&file.0, encrypted).unwrap();
+        assert_eq!(open_error(&file, "pw").code(), "json");
+
+        // В устаревшем формате JSON неподходящей структуры тоже не считается неверным паролем
+        let encrypted = KakaduProvider::encrypt_data(b"{\"groups\": 5}", "pw").unwrap();
+        fs::write(&file.0, encrypted).unwrap();
+        assert_eq!(open_error(&file, "pw").code(), "json");
+        assert_eq!(open_error(&file, "other").code(), "wrong_password");
+    }
+
+    #[test]
+    fn io_error_for_missing_file() {
+        let file = TempFile::new("missing");
+        assert_eq!(open_error(&file, "pw").code(), "io");
+    }
+
+    #[test]
+    fn bad_padding_and_crypto_codes() {
+        let mut data = vec![1u8, 2, 3, 0];
+        let error = KakaduProvider::remove_padding(&mut data, 16).unwrap_err();
+        assert_eq!(error.code(), "bad_padding");
+
+        let mut data = vec![5u8, 4, 4, 4];
+        assert_eq!(KakaduProvider::remove_padding(&mut data, 16).unwrap_err().code(), "bad_padding");
+
+        let error = SessionKey::derive("pw", KdfParams::Sha256).unwrap_err();
+        assert_eq!(error.code(), "crypto");
+    }
+
+    #[test]
+    fn allocate_id_skips_existing_ids() {
+        let mut data = sample_data();
+        assert_eq!(data.allocate_id().unwrap(), 3);
+        assert_eq!(data.allocate_id().unwrap(), 4);
+
+        data.records[0].id = 10;
+        assert_eq!(data.allocate_id().unwrap(), 11);
+        assert_eq!(data.next_id, 12);
+    }
+
+    #[test]
+    fn allocate_id_stops_at_u32_max() {
+        let mut data = sample_data();
+        data.next_id = u32::MAX - 1;
+        assert_eq!(data.allocate_id().unwrap(), u32::MAX - 1);
+        assert_eq!(data.allocate_id().unwrap_err().code(), "ids_exhausted");
+
+        let mut data = sample_data();
+        data.records[0].id = u32::MAX;
+        assert_eq!(data.allocate_id().unwrap_err().code(), "ids_exhausted");
+        assert_eq!(data.repair_ids().unwrap().len(), 0);
+
+        data.groups.push(Group::new(u32::MAX, 1, "Копия".to_string()));
+        assert_eq!(data.repair_ids().unwrap_err().code(), "ids_exhausted");
+    }
+
+    fn secret(value: &str) -> SecretField {
+        SecretField::from(value.to_string())
+    }
+
+    fn history(record: &Record) -> Vec<&str> {
+        record.history.iter().map(|e| e.password.expose()).collect()
+    }
+
+    /// Меняет пароль записи так же, как команда обновления записи
+    fn change_password(record: &mut Record, value: &str) {
+        let previous = std::mem::replace(&mut record.password, secret(value));
+        record.remember_password(&previous);
+    }
+
+    #[test]
+    fn remember_password_records_changes_only() {
+        let mut record = sample_data().records.remove(0);
+
+        change_password(&mut record, "second");
+        assert_eq!(history(&record), ["hunter2"]);
+        assert!(record.history[0].changed_at > 0);
+
+        // Тот же пароль и пустой прежний пароль не записываются
+        change_password(&mut record, "second");
+        record.password = secret("");
+        change_password(&mut record, "third");
+        assert_eq!(history(&record), ["hunter2"]);
+    }
+
+    #[test]
+    fn remember_password_keeps_newest_entries() {
+        let mut record = sample_data().records.remove(0);
+        for i in 0..MAX_PASSWORD_HISTORY + 3 {
+            change_password(&mut record, &format!("password-{}", i));
+        }
+
+        assert_eq!(record.history.len(), MAX_PASSWORD_HISTORY);
+        assert_eq!(record.history[0].password.expose(), format!("password-{}", MAX_PASSWORD_HISTORY + 1));
+        assert_eq!(record.history[MAX_PASSWORD_HISTORY - 1].password.expose(), "password-2");
+    }
+
+    #[test]
+    fn restore_password_swaps_with_current() {
+        let mut record = sample_data().records.remove(0);
+        change_password(&mut record, "second");
+        change_password(&mut record, "third");
+        assert_eq!(history(&record), ["second", "hunter2"]);
+
+        assert!(!record.restore_password(2));
+        assert_eq!(record.password.expose(), "third");
+        assert_eq!(history(&record), ["second", "hunter2"]);
+
+        assert!(record.restore_password(1));
+        assert_eq!(record.password.expose(), "hunter2");
+        assert_eq!(history(&record), ["third", "second"]);
+        assert!(record.times.modified_at.is_some());
+    }
+
+    #[test]
+    fn legacy_copy_has_no_history() {
+        let mut data = sample_data();
+        change_password(&mut data.records[0], "second");
+
+        let legacy = data.to_legacy();
+        assert!(legacy.records[0].history.is_empty());
+        assert_eq!(legacy.records[0].password.expose(), "second");
+        assert!(!serde_json::to_string(&legacy.records[0]).unwrap().contains("history"));
+        assert_eq!(data.records[0].history.len(), 1);
     }
 }
@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use zeroize::Zeroizing;
+
+use super::container::ByteReader;
+use super::error::KakaduError;
+use super::Record;
+
+/// Максимальный суммарный размер вложений в одном хранилище (уникальное содержимое)
+pub const MAX_VAULT_ATTACHMENTS_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Сигнатура полезной нагрузки с вложениями. JSON всегда начинается с `{`,
+/// поэтому хранилища без вложений по-прежнему содержат только JSON
+const BUNDLE_MAGIC: &[u8; 4] = b"KKDA";
+
+/// Длина SHA-256 в байтах
+const HASH_LEN: usize = 32;
+
+/// Ссылка на вложение в записи
+///
+/// Само содержимое хранится в `AttachmentStore` хранилища, в JSON попадают только метаданные
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub id: String,    // ID содержимого: SHA-256 в hex
+    pub name: String,  // Имя файла
+    pub size: u64,     // Размер в байтах
+    pub added_at: u64, // Время добавления (секунды с UNIX-эпохи)
+}
+
+/// Содержимое вложений хранилища с дедупликацией по хешу
+///
+/// Одинаковые файлы, прикрепленные к разным записям, хранятся один раз.
+/// Буферы затираются в памяти при удалении
+#[derive(Clone, Default)]
+pub struct AttachmentStore {
+    blobs: BTreeMap<String, Zeroizing<Vec<u8>>>,
+}
+
+impl fmt::Debug for AttachmentStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AttachmentStore({} blobs, {} bytes)", self.blobs.len(), self.total_size())
+    }
+}
+
+impl AttachmentStore {
+    /// Добавляет содержимое, если такого еще нет
+    ///
+    /// # Возвращает
+    /// ID содержимого (SHA-256 в hex)
+    pub fn insert(&mut self, content: Zeroizing<Vec<u8>>) -> String {
+        let id = content_id(&content);
+        self.blobs.entry(id.clone()).or_insert(content);
+        id
+    }
+
+    /// Содержимое по ID
+    pub fn get(&self, id: &str) -> Option<&[u8]> {
+        self.blobs.get(id).map(|b| b.as_slice())
+    }
+
+    /// Есть ли содержимое с указанным ID
+    pub fn contains(&self, id: &str) -> bool {
+        self.blobs.contains_key(id)
+    }
+
+    /// Суммарный размер хранимого содержимого
+    pub fn total_size(&self) -> u64 {
+        self.blobs.values().map(|b| b.len() as u64).sum()
+    }
+
+    /// Поместится ли содержимое с указанными ID и размером в лимит `MAX_VAULT_ATTACHMENTS_SIZE`
+    ///
+    /// Уже хранимое содержимое повторно место не занимает
+    pub fn fits(&self, id: &str, size: u64) -> bool {
+        self.contains(id) || self.total_size().saturating_add(size) <= MAX_VAULT_ATTACHMENTS_SIZE
+    }
+
+    /// Удаляет содержимое, на которое не ссылается ни одна запись
+    pub fn retain_referenced(&mut self, records: &[Record]) {
+        let referenced = referenced_ids(records);
+        self.blobs.retain(|id, _| referenced.contains(id.as_str()));
+    }
+}
+
+/// ID содержимого: SHA-256 в hex
+pub fn content_id(content: &[u8]) -> String {
+    Sha256::digest(content).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// ID вложений, на которые ссылаются записи
+fn referenced_ids(records: &[Record]) -> BTreeSet<&str> {
+    records
+        .iter()
+        .flat_map(|r| r.attachments.iter().map(|a| a.id.as_str()))
+        .collect()
+}
+
+/// Формирует открытую полезную нагрузку контейнера
+///
+/// # Формат
+/// Без вложений - только JSON. С вложениями:
+/// `KKDA (4) | длина JSON (8, LE) | JSON | количество (4, LE) |
+/// { SHA-256 (32) | длина (8, LE) | содержимое }*`
+///
+/// Записывается только содержимое, на которое ссылаются записи
+pub fn encode_payload(json: &[u8], records: &[Record], store: &AttachmentStore) -> Zeroizing<Vec<u8>> {
+    let blobs: Vec<(&String, &Zeroizing<Vec<u8>>)> = {
+        let referenced = referenced_ids(records);
+        store.blobs.iter().filter(|(id, _)| referenced.contains(id.as_str())).collect()
+    };
+    if blobs.is_empty() {
+        return Zeroizing::new(json.to_vec());
+    }
+
+    let blobs_len: usize = blobs.iter().map(|(_, b)| HASH_LEN + 8 + b.len()).sum();
+    let mut out = Zeroizing::new(Vec::with_capacity(BUNDLE_MAGIC.len() + 8 + json.len() + 4 + blobs_len));
+    out.extend_from_slice(BUNDLE_MAGIC);
+    out.extend_from_slice(&(json.len() as u64).to_le_bytes());
+    out.extend_from_slice(json);
+    out.extend_from_slice(&(blobs.len() as u32).to_le_bytes());
+    for (_, blob) in blobs {
+        out.extend_from_slice(&Sha256::digest(blob.as_slice()));
+        out.extend_from_slice(&(blob.len() as u64).to_le_bytes());
+        out.extend_from_slice(blob);
+    }
+    out
+}
+
+/// Разбирает открытую полезную нагрузку контейнера (см. `encode_payload`)
+///
+/// # Возвращает
+/// JSON и содержимое вложений
+///
+/// # Ошибки
+/// `Truncated` при нехватке данных, `Corrupted` если хеш содержимого не совпадает
+pub fn decode_payload(payload: &[u8]) -> Result<(&[u8], AttachmentStore), KakaduError> {
+    if !payload.starts_with(BUNDLE_MAGIC) {
+        return Ok((payload, AttachmentStore::default()));
+    }
+
+    let mut reader = ByteReader::new(&payload[BUNDLE_MAGIC.len()..]);
+    let json_len = usize::try_from(u64::from_le_bytes(reader.array()?)).map_err(|_| KakaduError::Truncated)?;
+    let json = reader.take(json_len)?;
+
+    let count = u32::from_le_bytes(reader.array()?);
+    let mut store = AttachmentStore::default();
+    for _ in 0..count {
+        let hash: [u8; HASH_LEN] = reader.array()?;
+        let len = usize::try_from(u64::from_le_bytes(reader.array()?)).map_err(|_| KakaduError::Truncated)?;
+        let content = reader.take(len)?;
+
+        if Sha256::digest(content).as_slice() != hash {
+            return Err(KakaduError::Corrupted("не совпадает хеш вложения".to_string()));
+        }
+        store.insert(Zeroizing::new(content.to_vec()));
+    }
+
+    Ok((json, store))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn content(bytes: &[u8]) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(bytes.to_vec())
+    }
+
+    fn record(id: u32, attachments: &[&str]) -> Record {
+        let attachments: Vec<_> = attachments
+            .iter()
+            .map(|a| json!({ "id": a, "name": "file.txt", "size": 1, "addedAt": 0 }))
+            .collect();
+        serde_json::from_value(json!({
+            "id": id, "pid": 1, "name": "Запись", "login": "", "password": "", "url": "",
+            "loginSymbol": "TAB", "passwordSymbol": "ENTER", "urlSymbol": "NONE",
+            "attachments": attachments
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn payload_round_trip() {
+        let mut store = AttachmentStore::default();
+        let first = store.insert(content(b"first"));
+        let second = store.insert(content(&[0u8, 255, 7]));
+        let unused = store.insert(content(b"unused"));
+        let records = [record(1, &[&first]), record(2, &[&second])];
+
+        let payload = encode_payload(br#"{"records":[]}"#, &records, &store);
+        assert!(payload.starts_with(BUNDLE_MAGIC));
+
+        let (json, decoded) = decode_payload(&payload).unwrap();
+        assert_eq!(json, br#"{"records":[]}"#);
+        assert_eq!(decoded.get(&first), Some(&b"first"[..]));
+        assert_eq!(decoded.get(&second), Some(&[0u8, 255, 7][..]));
+        // Содержимое без ссылок из записей не сохраняется
+        assert!(!decoded.contains(&unused));
+    }
+
+    #[test]
+    fn plain_json_payload_is_readable() {
+        let json = br#"{"groups":[],"records":[]}"#;
+
+        let payload = encode_payload(json, &[record(1, &[])], &AttachmentStore::default());
+        assert_eq!(payload.as_slice(), json);
+
+        let (decoded, store) = decode_payload(json).unwrap();
+        assert_eq!(decoded, json);
+        assert_eq!(store.total_size(), 0);
+    }
+
+    #[test]
+    fn damaged_payload_is_rejected() {
+        let mut store = AttachmentStore::default();
+        let id = store.insert(content(b"attachment body"));
+        let payload = encode_payload(b"{}", &[record(1, &[&id])], &store);
+
+        for len in [payload.len() - 1, BUNDLE_MAGIC.len() + 4, BUNDLE_MAGIC.len() + 8 + 2 + 3] {
+            assert!(matches!(decode_payload(&payload[..len]), Err(KakaduError::Truncated)), "длина {}", len);
+        }
+
+        let mut tampered = payload.to_vec();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(decode_payload(&tampered), Err(KakaduError::Corrupted(_))));
+    }
+
+    #[test]
+    fn identical_content_is_stored_once() {
+        let mut store = AttachmentStore::default();
+        let first = store.insert(content(b"shared"));
+        let second = store.insert(content(b"shared"));
+        assert_eq!(first, second);
+        assert_eq!(first, content_id(b"shared"));
+        assert_eq!(store.total_size(), 6);
+
+        let records = [record(1, &[&first]), record(2, &[&second])];
+        let payload = encode_payload(b"{}", &records, &store);
+        let count_at = BUNDLE_MAGIC.len() + 8 + 2;
+        assert_eq!(payload[count_at..count_at + 4], 1u32.to_le_bytes());
+
+        // Содержимое остается, пока на него ссылается хотя бы одна запись
+        store.retain_referenced(&records[1..]);
+        assert!(store.contains(&first));
+        store.retain_referenced(&[]);
+        assert!(!store.contains(&first));
+    }
+
+    #[test]
+    fn size_limit_counts_unique_content() {
+        let mut store = AttachmentStore::default();
+        let id = store.insert(content(&[1u8; 1024]));
+        let free = MAX_VAULT_ATTACHMENTS_SIZE - 1024;
+
+        assert!(store.fits("new", free));
+        assert!(!store.fits("new", free + 1));
+        assert!(!store.fits("new", u64::MAX));
+        // Повторное прикрепление того же содержимого лимит не расходует
+        assert!(store.fits(&id, 1024));
+    }
+}
@@ -0,0 +1,357 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use super::error::KakaduError;
+use super::kdf::{KdfParams, KEY_LEN};
+use super::secret::{DerivedKey, ExposeSecret};
+
+/// Сигнатура контейнера .kkd версии 2 и выше
+pub const MAGIC: &[u8; 4] = b"KKDF";
+
+/// Текущая версия формата, которая всегда используется при записи
+pub const FORMAT_VERSION: u8 = 2;
+
+/// Длина соли KDF в байтах
+pub const SALT_LEN: usize = 16;
+
+/// Длина nonce для AES-256-GCM в байтах
+pub const NONCE_LEN: usize = 12;
+
+/// Длина контрольного значения ключа в байтах
+pub const KEY_CHECK_LEN: usize = 16;
+
+/// Длина тега аутентификации AES-256-GCM в байтах
+const TAG_LEN: usize = 16;
+
+/// Заголовок контейнера .kkd v2
+///
+/// # Формат
+/// `MAGIC (4) | версия (1) | id KDF (1) | длина параметров KDF (2, LE) | параметры KDF |
+/// длина соли (1) | соль | nonce (12) | контрольное значение ключа (16) |
+/// шифротекст AES-256-GCM с тегом (16)`
+///
+/// Все байты заголовка передаются в AEAD как associated data, поэтому
+/// любое изменение заголовка приводит к ошибке проверки тега.
+/// Контрольное значение ключа позволяет отличить неверный пароль от поврежденного файла.
+#[derive(Debug, Clone)]
+pub struct ContainerHeader {
+    pub version: u8,
+    pub kdf: KdfParams,
+    pub salt: Vec<u8>,
+    pub nonce: [u8; NONCE_LEN],
+    pub key_check: [u8; KEY_CHECK_LEN],
+}
+
+/// Ключ сессии: полученный из пароля ключ вместе с солью и параметрами KDF
+///
+/// Позволяет повторно шифровать хранилище без мастер-пароля:
+/// при каждом сохранении меняется только nonce
+#[derive(Debug)]
+pub struct SessionKey {
+    kdf: KdfParams,
+    salt: Vec<u8>,
+    key: DerivedKey,
+}
+
+impl SessionKey {
+    /// Получает новый ключ из пароля со свежей солью
+    pub fn derive(password: &str, kdf: KdfParams) -> Result<Self, KakaduError> {
+        if !kdf.is_writable() {
+            return Err(KakaduError::Crypto("выбранная KDF поддерживается только для чтения".to_string()));
+        }
+
+        let mut salt = vec![0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let key = kdf.derive_key(password, &salt)?;
+
+        Ok(Self { kdf, salt, key })
+    }
+
+    /// Параметры KDF, с которыми получен ключ
+    pub fn kdf(&self) -> &KdfParams {
+        &self.kdf
+    }
+}
+
+impl ContainerHeader {
+    /// Создает заголовок текущей версии для ключа сессии со свежим nonce
+    pub fn for_key(session: &SessionKey) -> Self {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        Self {
+            version: FORMAT_VERSION,
+            kdf: session.kdf.clone(),
+            salt: session.salt.clone(),
+            nonce,
+            key_check: key_check(session.key.expose_secret()),
+        }
+    }
+
+    /// Сериализует заголовок в байты
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let kdf_id = self.kdf.id();
+        let kdf_params = self.kdf.encode();
+
+        let mut out = Vec::with_capacity(
+            MAGIC.len() + 4 + kdf_params.len() + 1 + self.salt.len() + NONCE_LEN + KEY_CHECK_LEN,
+        );
+        out.extend_from_slice(MAGIC);
+        out.push(self.version);
+        out.push(kdf_id);
+        out.extend_from_slice(&(kdf_params.len() as u16).to_le_bytes());
+        out.extend_from_slice(&kdf_params);
+        out.push(self.salt.len() as u8);
+        out.extend_from_slice(&self.salt);
+        out.extend_from_slice(&self.nonce);
+        out.extend_from_slice(&self.key_check);
+        out
+    }
+
+    /// Разбирает заголовок в начале данных
+    ///
+    /// # Возвращает
+    /// Заголовок и длину заголовка в байтах (смещение начала шифротекста)
+    pub fn parse(data: &[u8]) -> Result<(Self, usize), KakaduError> {
+        let mut reader = ByteReader::new(data);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(KakaduError::Corrupted("отсутствует сигнатура контейнера".to_string()));
+        }
+
+        let version = reader.u8()?;
+        if version != FORMAT_VERSION {
+            return Err(KakaduError::UnsupportedVersion(version));
+        }
+
+        let kdf_id = reader.u8()?;
+        let params_len = u16::from_le_bytes([reader.u8()?, reader.u8()?]) as usize;
+        let kdf = KdfParams::decode(kdf_id, reader.take(params_len)?)?;
+
+        let salt_len = reader.u8()? as usize;
+        let salt = reader.take(salt_len)?.to_vec();
+        let nonce: [u8; NONCE_LEN] = reader.array()?;
+        let key_check: [u8; KEY_CHECK_LEN] = reader.array()?;
+
+        Ok((
+            Self {
+                version,
+                kdf,
+                salt,
+                nonce,
+                key_check,
+            },
+            reader.position(),
+        ))
+    }
+
+    /// Получает 256-битный ключ из пароля и сверяет его с контрольным значением
+    pub fn derive_key(&self, password: &str) -> Result<DerivedKey, KakaduError> {
+        let key = self.kdf.derive_key(password, &self.salt)?;
+        if key_check(key.expose_secret()) != self.key_check {
+            return Err(KakaduError::WrongPassword);
+        }
+        Ok(key)
+    }
+}
+
+/// Контрольное значение ключа: усеченный SHA-256 от ключа с доменным префиксом
+fn key_check(key: &[u8; KEY_LEN]) -> [u8; KEY_CHECK_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"KKDF key check");
+    hasher.update(key);
+    let digest = hasher.finalize();
+
+    let mut check = [0u8; KEY_CHECK_LEN];
+    check.copy_from_slice(&digest[..KEY_CHECK_LEN]);
+    check
+}
+
+/// Проверяет, начинаются ли данные с сигнатуры контейнера v2
+pub fn is_container(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Шифрует данные в контейнер v2 ключом сессии с аутентификацией заголовка
+pub fn seal_with_key(plaintext: &[u8], session: &SessionKey) -> Result<Vec<u8>, KakaduError> {
+    let header = ContainerHeader::for_key(session);
+    let header_bytes = header.to_bytes();
+
+    let cipher = Aes256Gcm::new_from_slice(session.key.expose_secret()).map_err(|e| KakaduError::Crypto(e.to_string()))?;
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&header.nonce),
+            Payload {
+                msg: plaintext,
+                aad: &header_bytes,
+            },
+        )
+        .map_err(|_| KakaduError::Crypto("ошибка шифрования данных".to_string()))?;
+
+    let mut out = header_bytes;
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Расшифровывает контейнер v2 и проверяет тег аутентификации
+///
+/// # Возвращает
+/// Открытые данные и ключ сессии для последующих сохранений
+pub fn open(data: &[u8], password: &str) -> Result<(Zeroizing<Vec<u8>>, SessionKey), KakaduError> {
+    let (header, offset) = ContainerHeader::parse(data)?;
+    if data.len() - offset < TAG_LEN {
+        return Err(KakaduError::Truncated);
+    }
+
+    let key = header.derive_key(password)?;
+
+    let cipher = Aes256Gcm::new_from_slice(key.expose_secret()).map_err(|e| KakaduError::Crypto(e.to_string()))?;
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(&header.nonce),
+            Payload {
+                msg: &data[offset..],
+                aad: &data[..offset],
+            },
+        )
+        .map_err(|_| KakaduError::Corrupted("не совпадает тег аутентификации".to_string()))?;
+
+    let session = SessionKey {
+        kdf: header.kdf,
+        salt: header.salt,
+        key,
+    };
+
+    Ok((Zeroizing::new(plaintext), session))
+}
+
+/// Последовательное чтение байтов заголовка и полезной нагрузки с проверкой границ
+pub(super) struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(super) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(super) fn take(&mut self, len: usize) -> Result<&'a [u8], KakaduError> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len())
+            .ok_or(KakaduError::Truncated)?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub(super) fn u8(&mut self) -> Result<u8, KakaduError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(super) fn array<const N: usize>(&mut self) -> Result<[u8; N], KakaduError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    pub(super) fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Дешевые параметры Argon2id, чтобы тесты не тратили сотни миллисекунд на KDF
+    fn test_kdf() -> KdfParams {
+        KdfParams::Argon2id {
+            m_cost_kib: 8,
+            t_cost: 1,
+            p_cost: 1,
+        }
+    }
+
+    fn sealed(plaintext: &[u8]) -> Vec<u8> {
+        let session = SessionKey::derive("correct horse", test_kdf()).unwrap();
+        seal_with_key(plaintext, &session).unwrap()
+    }
+
+    /// Смещение nonce в заголовке: MAGIC, версия, id KDF, длина и параметры KDF, длина и соль
+    fn nonce_offset() -> usize {
+        MAGIC.len() + 1 + 1 + 2 + test_kdf().encode().len() + 1 + SALT_LEN
+    }
+
+    #[test]
+    fn seal_open_round_trip() {
+        let data = sealed(b"{\"groups\":[]}");
+        assert!(is_container(&data));
+
+        let (plaintext, session) = open(&data, "correct horse").unwrap();
+        assert_eq!(plaintext.as_slice(), b"{\"groups\":[]}");
+        assert_eq!(session.kdf(), &test_kdf());
+
+        // Повторное сохранение ключом сессии открывается тем же паролем
+        let resealed = seal_with_key(b"second", &session).unwrap();
+        assert_eq!(open(&resealed, "correct horse").unwrap().0.as_slice(), b"second");
+    }
+
+    #[test]
+    fn wrong_password_is_reported() {
+        let data = sealed(b"secret");
+        assert!(matches!(open(&data, "wrong horse"), Err(KakaduError::WrongPassword)));
+    }
+
+    #[test]
+    fn flipped_header_byte_is_corrupted() {
+        let mut data = sealed(b"secret");
+        data[nonce_offset()] ^= 0x01;
+        assert!(matches!(open(&data, "correct horse"), Err(KakaduError::Corrupted(_))));
+
+        let mut data = sealed(b"secret");
+        data[0] ^= 0x01;
+        assert!(matches!(open(&data, "correct horse"), Err(KakaduError::Corrupted(_))));
+    }
+
+    #[test]
+    fn flipped_ciphertext_byte_is_corrupted() {
+        let mut data = sealed(b"secret");
+        let last = data.len() - 1;
+        data[last] ^= 0x80;
+        assert!(matches!(open(&data, "correct horse"), Err(KakaduError::Corrupted(_))));
+
+        let mut data = sealed(b"secret");
+        let first = nonce_offset() + NONCE_LEN + KEY_CHECK_LEN;
+        data[first] ^= 0x80;
+        assert!(matches!(open(&data, "correct horse"), Err(KakaduError::Corrupted(_))));
+    }
+
+    #[test]
+    fn truncated_input_is_reported() {
+        let data = sealed(b"secret");
+        let header_len = nonce_offset() + NONCE_LEN + KEY_CHECK_LEN;
+
+        for len in [2, MAGIC.len() + 1, nonce_offset(), header_len - 1, header_len + TAG_LEN - 1] {
+            assert!(
+                matches!(open(&data[..len], "correct horse"), Err(KakaduError::Truncated)),
+                "длина {}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn other_versions_are_unsupported() {
+        for version in [0, 1, 3, 255] {
+            let mut data = sealed(b"secret");
+            data[MAGIC.len()] = version;
+            assert!(matches!(
+                open(&data, "correct horse"),
+                Err(KakaduError::UnsupportedVersion(v)) if v == version
+            ));
+        }
+    }
+}
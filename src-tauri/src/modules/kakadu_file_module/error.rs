@@ -0,0 +1,127 @@
+use std::fmt;
+use std::io;
+
+/// Ошибки чтения и записи файлов .kkd
+///
+/// Каждому варианту соответствует стабильный код (`code`),
+/// по которому фронтенд выбирает локализованное сообщение
+#[derive(Debug)]
+pub enum KakaduError {
+    /// Пароль не подходит к файлу
+    WrongPassword,
+    /// Файл обрезан: не хватает заголовка или данных
+    Truncated,
+    /// Некорректное PKCS7-выравнивание в файле устаревшего формата
+    BadPadding,
+    /// Версия формата не поддерживается этой версией приложения
+    UnsupportedVersion(u8),
+    /// Пароль верный, но содержимое файла повреждено
+    Corrupted(String),
+    /// Внутренняя ошибка криптографических примитивов
+    Crypto(String),
+    /// Ошибка ввода-вывода
+    Io(io::Error),
+    /// Ошибка (де)сериализации JSON
+    Json(serde_json::Error),
+    /// Исчерпан диапазон ID групп и записей
+    IdsExhausted,
+}
+
+impl KakaduError {
+    /// Стабильный код ошибки для фронтенда
+    pub fn code(&self) -> &'static str {
+        match self {
+            KakaduError::WrongPassword => "wrong_password",
+            KakaduError::Truncated => "truncated",
+            KakaduError::BadPadding => "bad_padding",
+            KakaduError::UnsupportedVersion(_) => "unsupported_version",
+            KakaduError::Corrupted(_) => "corrupted",
+            KakaduError::Crypto(_) => "crypto",
+            KakaduError::Io(_) => "io",
+            KakaduError::Json(_) => "json",
+            KakaduError::IdsExhausted => "ids_exhausted",
+        }
+    }
+}
+
+impl fmt::Display for KakaduError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KakaduError::WrongPassword => write!(f, "Неверный пароль"),
+            KakaduError::Truncated => write!(f, "Файл обрезан"),
+            KakaduError::BadPadding => write!(f, "Неверный пароль или файл поврежден (некорректное выравнивание)"),
+            KakaduError::UnsupportedVersion(v) => write!(f, "Неподдерживаемая версия формата: {}", v),
+            KakaduError::Corrupted(e) => write!(f, "Файл поврежден: {}", e),
+            KakaduError::Crypto(e) => write!(f, "Ошибка шифрования: {}", e),
+            KakaduError::Io(e) => write!(f, "Ошибка ввода-вывода: {}", e),
+            KakaduError::Json(e) => write!(f, "Ошибка формата данных: {}", e),
+            KakaduError::IdsExhausted => write!(f, "Исчерпан диапазон идентификаторов групп и записей"),
+        }
+    }
+}
+
+impl std::error::Error for KakaduError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KakaduError::Io(e) => Some(e),
+            KakaduError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Ошибки изменения дерева групп и записей
+///
+/// Как и у `KakaduError`, каждому варианту соответствует стабильный код (`code`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeError {
+    /// Группа с указанным ID не найдена
+    GroupNotFound,
+    /// Операция недопустима для корневой группы (`pid == 0`)
+    RootGroup,
+    /// Группа содержит вложенные группы или записи
+    GroupNotEmpty,
+    /// Перенос сделал бы группу потомком самой себя
+    GroupCycle,
+    /// Запись с указанным ID не найдена
+    RecordNotFound(u32),
+}
+
+impl TreeError {
+    /// Стабильный код ошибки для фронтенда
+    pub fn code(&self) -> &'static str {
+        match self {
+            TreeError::GroupNotFound => "no_group",
+            TreeError::RootGroup => "root_group",
+            TreeError::GroupNotEmpty => "group_not_empty",
+            TreeError::GroupCycle => "group_cycle",
+            TreeError::RecordNotFound(_) => "no_record",
+        }
+    }
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeError::GroupNotFound => write!(f, "Группа с указанным ID не найдена"),
+            TreeError::RootGroup => write!(f, "Операция недопустима для корневой группы"),
+            TreeError::GroupNotEmpty => write!(f, "Группа не пуста"),
+            TreeError::GroupCycle => write!(f, "Нельзя перенести группу внутрь самой себя"),
+            TreeError::RecordNotFound(id) => write!(f, "Запись с ID {} не найдена", id),
+        }
+    }
+}
+
+impl std::error::Error for TreeError {}
+
+impl From<io::Error> for KakaduError {
+    fn from(e: io::Error) -> Self {
+        KakaduError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for KakaduError {
+    fn from(e: serde_json::Error) -> Self {
+        KakaduError::Json(e)
+    }
+}
@@ -0,0 +1,146 @@
+use serde::Serialize;
+
+use super::tree::GroupTree;
+use super::PasswordData;
+
+/// Секунд в сутках
+pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Запись с истекающим или истекшим сроком действия
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiringRecord {
+    pub record_id: u32,    // ID записи
+    pub name: String,      // Название записи
+    pub path: Vec<String>, // Путь (breadcrumb) группы записи
+    pub expires_at: u64,   // Срок действия (секунды с UNIX-эпохи)
+    pub expired: bool,     // Срок уже истек
+}
+
+/// Находит записи, срок действия которых истекает до `now + within_secs`
+///
+/// # Поведение
+/// Срок записи - ближайший из ее собственного `expires_at` и сроков всех групп
+/// на пути от корня, поэтому срок группы распространяется на все вложенные записи.
+/// Уже истекшие записи тоже входят в результат
+///
+/// # Возвращает
+/// Записи по возрастанию срока действия
+pub fn expiring_records(data: &PasswordData, now: u64, within_secs: u64) -> Vec<ExpiringRecord> {
+    let tree = GroupTree::new(data);
+    let deadline = now.saturating_add(within_secs);
+
+    let mut result: Vec<ExpiringRecord> = data
+        .records
+        .iter()
+        .filter_map(|record| {
+            let group_expiry = tree
+                .ancestors(record.pid)
+                .into_iter()
+                .chain(tree.group(record.pid))
+                .filter_map(|g| g.times.expires_at)
+                .min();
+            let expires_at = record.times.expires_at.into_iter().chain(group_expiry).min()?;
+
+            (expires_at <= deadline).then(|| ExpiringRecord {
+                record_id: record.id,
+                name: record.name.clone(),
+                path: tree.path(record.pid),
+                expires_at,
+                expired: expires_at <= now,
+            })
+        })
+        .collect();
+
+    result.sort_by_key(|r| (r.expires_at, r.record_id));
+    result
+}
+
+/// Записи с уже истекшим сроком действия
+pub fn expired_records(data: &PasswordData, now: u64) -> Vec<ExpiringRecord> {
+    expiring_records(data, now, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::kakadu_file_module::attachments::AttachmentStore;
+    use crate::modules::kakadu_file_module::{Group, Record};
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn record(id: u32, pid: u32, expires_at: Option<u64>) -> Record {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "pid": pid, "name": format!("Запись {}", id), "login": "", "password": "", "url": "",
+            "loginSymbol": "TAB", "passwordSymbol": "ENTER", "urlSymbol": "NONE",
+            "expiresAt": expires_at
+        }))
+        .unwrap()
+    }
+
+    /// Корень 1 и группа 2 со сроком через 10 дней
+    fn vault(records: Vec<Record>) -> PasswordData {
+        let mut work = Group::new(2, 1, "Работа".to_string());
+        work.times.expires_at = Some(NOW + 10 * SECONDS_PER_DAY);
+        PasswordData {
+            groups: vec![Group::new(1, 0, "Root".to_string()), work],
+            records,
+            next_id: 100,
+            attachments: AttachmentStore::default(),
+        }
+    }
+
+    fn ids(records: &[ExpiringRecord]) -> Vec<(u32, bool)> {
+        records.iter().map(|r| (r.record_id, r.expired)).collect()
+    }
+
+    #[test]
+    fn expired_and_expiring_soon_records() {
+        let data = vault(vec![
+            record(10, 1, Some(NOW + 3 * SECONDS_PER_DAY)),
+            record(11, 1, Some(NOW - SECONDS_PER_DAY)),
+            record(12, 1, Some(NOW)),
+            record(13, 1, Some(NOW + 30 * SECONDS_PER_DAY)),
+        ]);
+
+        let soon = expiring_records(&data, NOW, 7 * SECONDS_PER_DAY);
+        assert_eq!(ids(&soon), [(11, true), (12, true), (10, false)]);
+        assert_eq!(soon[2].expires_at, NOW + 3 * SECONDS_PER_DAY);
+        assert_eq!(soon[2].path, ["Root"]);
+
+        assert_eq!(ids(&expired_records(&data, NOW)), [(11, true), (12, true)]);
+    }
+
+    #[test]
+    fn records_without_expiry_are_skipped() {
+        let data = vault(vec![record(10, 1, None)]);
+
+        assert!(expiring_records(&data, NOW, u64::MAX).is_empty());
+        assert!(expired_records(&data, u64::MAX).is_empty());
+    }
+
+    #[test]
+    fn group_expiry_applies_to_nested_records() {
+        let data = vault(vec![
+            record(10, 2, None),
+            record(11, 2, Some(NOW + SECONDS_PER_DAY)),
+            record(12, 2, Some(NOW + 20 * SECONDS_PER_DAY)),
+        ]);
+
+        let soon = expiring_records(&data, NOW, 10 * SECONDS_PER_DAY);
+        assert_eq!(ids(&soon), [(11, false), (10, false), (12, false)]);
+        // Срок записи 12 позже срока группы, поэтому действует срок группы
+        assert_eq!(soon[2].expires_at, NOW + 10 * SECONDS_PER_DAY);
+        assert_eq!(soon[0].path, ["Root", "Работа"]);
+    }
+
+    #[test]
+    fn serialized_in_camel_case() {
+        let data = vault(vec![record(10, 1, Some(NOW))]);
+        let value = serde_json::to_value(&expired_records(&data, NOW)[0]).unwrap();
+
+        assert_eq!(value["recordId"], 10);
+        assert_eq!(value["expiresAt"], NOW);
+        assert!(value.get("record_id").is_none());
+    }
+}
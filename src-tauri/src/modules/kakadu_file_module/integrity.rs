@@ -0,0 +1,395 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use super::{Group, KakaduError, PasswordData};
+
+/// Название группы, в которую переносятся потерянные группы и записи
+pub const RECOVERED_GROUP_NAME: &str = "Recovered";
+
+/// Название корневой группы, создаваемой при ее отсутствии
+const ROOT_GROUP_NAME: &str = "NewDatabase";
+
+/// Проблема целостности хранилища
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VaultProblem {
+    /// Несколько групп или записей используют один ID
+    DuplicateId { id: u32 },
+    /// Группа или запись использует зарезервированный ID 0 (`pid` корня)
+    ReservedId,
+    /// Нет ни одной корневой группы (`pid == 0`)
+    MissingRoot,
+    /// Корневых групп больше одной
+    MultipleRoots { group_ids: Vec<u32> },
+    /// Группа ссылается на несуществующую родительскую группу
+    OrphanGroup { group_id: u32, pid: u32 },
+    /// Группы ссылаются друг на друга по кругу
+    GroupCycle { group_ids: Vec<u32> },
+    /// Запись ссылается на несуществующую группу
+    OrphanRecord { record_id: u32, pid: u32 },
+}
+
+/// Отчет о проверке (или исправлении) хранилища
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct VaultReport {
+    pub problems: Vec<VaultProblem>, // Найденные (или исправленные) проблемы
+}
+
+impl VaultReport {
+    /// Проблем не найдено
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Проверяет структуру хранилища, не изменяя данные
+pub fn validate_vault(data: &PasswordData) -> VaultReport {
+    let mut problems = Vec::new();
+
+    // Повторяющиеся и нулевые ID среди групп и записей (то же правило, что в `repair_ids`)
+    let mut seen = HashSet::new();
+    let mut duplicates = Vec::new();
+    let mut reserved = false;
+    for id in data.groups.iter().map(|g| g.id).chain(data.records.iter().map(|r| r.id)) {
+        if id == 0 {
+            reserved = true;
+        } else if !seen.insert(id) && !duplicates.contains(&id) {
+            duplicates.push(id);
+        }
+    }
+    if reserved {
+        problems.push(VaultProblem::ReservedId);
+    }
+    problems.extend(duplicates.into_iter().map(|id| VaultProblem::DuplicateId { id }));
+
+    // Корневые группы
+    let roots = root_ids(data);
+    match roots.len() {
+        0 => problems.push(VaultProblem::MissingRoot),
+        1 => {}
+        _ => problems.push(VaultProblem::MultipleRoots { group_ids: roots }),
+    }
+
+    // Группы с несуществующим родителем
+    let group_ids: HashSet<u32> = data.groups.iter().map(|g| g.id).collect();
+    problems.extend(
+        data.groups
+            .iter()
+            .filter(|g| g.pid != 0 && !group_ids.contains(&g.pid))
+            .map(|g| VaultProblem::OrphanGroup { group_id: g.id, pid: g.pid }),
+    );
+
+    // Циклы в иерархии групп
+    problems.extend(
+        find_cycles(&data.groups)
+            .into_iter()
+            .map(|group_ids| VaultProblem::GroupCycle { group_ids }),
+    );
+
+    // Записи с несуществующей группой
+    problems.extend(
+        data.records
+            .iter()
+            .filter(|r| !group_ids.contains(&r.pid))
+            .map(|r| VaultProblem::OrphanRecord { record_id: r.id, pid: r.pid }),
+    );
+
+    VaultReport { problems }
+}
+
+/// Исправляет структуру хранилища детерминированно
+///
+/// # Порядок исправлений
+/// 1. Повторяющимся и нулевым ID выдаются новые (`PasswordData::repair_ids`)
+/// 2. При отсутствии корня создается корневая группа; из нескольких корней
+///    корнем остается группа с наименьшим ID, остальные становятся ее детьми
+/// 3. Группы без родителя и по одной группе (с наименьшим ID) из каждого цикла
+///    переносятся в группу `Recovered` внутри корня
+/// 4. Записи без группы переносятся в `Recovered`
+///
+/// # Возвращает
+/// Отчет с проблемами, найденными до исправления
+///
+/// # Ошибки
+/// `IdsExhausted`, если для исправления не хватило свободных ID
+pub fn repair_vault(data: &mut PasswordData) -> Result<VaultReport, KakaduError> {
+    let report = validate_vault(data);
+    if report.is_ok() {
+        return Ok(report);
+    }
+
+    data.repair_ids()?;
+
+    // Единственный корень
+    let mut roots = root_ids(data);
+    roots.sort_unstable();
+    let root_id = match roots.first() {
+        Some(&root_id) => root_id,
+        None => {
+            let id = data.allocate_id()?;
+            data.groups.push(Group::new(id, 0, ROOT_GROUP_NAME.to_string()));
+            id
+        }
+    };
+    for group in data.groups.iter_mut().filter(|g| g.pid == 0 && g.id != root_id) {
+        group.pid = root_id;
+    }
+
+    // Группы без родителя и разрыв циклов
+    let group_ids: HashSet<u32> = data.groups.iter().map(|g| g.id).collect();
+    let mut detached: Vec<u32> = data
+        .groups
+        .iter()
+        .filter(|g| g.pid != 0 && !group_ids.contains(&g.pid))
+        .map(|g| g.id)
+        .collect();
+    detached.extend(
+        find_cycles(&data.groups)
+            .into_iter()
+            .filter_map(|cycle| cycle.into_iter().min()),
+    );
+
+    let has_orphan_records = data.records.iter().any(|r| !group_ids.contains(&r.pid));
+    if detached.is_empty() && !has_orphan_records {
+        return Ok(report);
+    }
+
+    let recovered_id = recovered_group(data, root_id)?;
+    for group in data.groups.iter_mut().filter(|g| detached.contains(&g.id)) {
+        group.pid = recovered_id;
+    }
+
+    // Записи без группы
+    for record in data.records.iter_mut().filter(|r| !group_ids.contains(&r.pid)) {
+        record.pid = recovered_id;
+    }
+
+    Ok(report)
+}
+
+/// ID корневых групп в порядке следования
+fn root_ids(data: &PasswordData) -> Vec<u32> {
+    data.groups.iter().filter(|g| g.pid == 0).map(|g| g.id).collect()
+}
+
+/// Возвращает ID группы `Recovered` внутри корня, создавая ее при необходимости
+fn recovered_group(data: &mut PasswordData, root_id: u32) -> Result<u32, KakaduError> {
+    if let Some(group) = data
+        .groups
+        .iter()
+        .find(|g| g.pid == root_id && g.name == RECOVERED_GROUP_NAME)
+    {
+        return Ok(group.id);
+    }
+
+    let id = data.allocate_id()?;
+    data.groups.push(Group::new(id, root_id, RECOVERED_GROUP_NAME.to_string()));
+    Ok(id)
+}
+
+/// Находит циклы в иерархии групп
+///
+/// # Возвращает
+/// Для каждого цикла отсортированный список ID входящих в него групп
+fn find_cycles(groups: &[Group]) -> Vec<Vec<u32>> {
+    let parents: HashMap<u32, u32> = groups.iter().map(|g| (g.id, g.pid)).collect();
+    let mut done: HashSet<u32> = HashSet::new();
+    let mut cycles = Vec::new();
+
+    for group in groups {
+        let mut path: Vec<u32> = Vec::new();
+        let mut current = group.id;
+
+        // Идем вверх по родителям, пока не дойдем до корня, обработанной группы или цикла
+        loop {
+            if done.contains(&current) {
+                break;
+            }
+            if let Some(pos) = path.iter().position(|&id| id == current) {
+                let mut cycle = path[pos..].to_vec();
+                cycle.sort_unstable();
+                cycles.push(cycle);
+                break;
+            }
+
+            path.push(current);
+            match parents.get(&current) {
+                Some(&pid) if pid != 0 => current = pid,
+                _ => break,
+            }
+        }
+
+        done.extend(path);
+    }
+
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::kakadu_file_module::attachments::AttachmentStore;
+    use crate::modules::kakadu_file_module::Record;
+
+    fn record(id: u32, pid: u32) -> Record {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "pid": pid, "name": "Запись", "login": "", "password": "", "url": "",
+            "loginSymbol": "TAB", "passwordSymbol": "ENTER", "urlSymbol": "NONE"
+        }))
+        .unwrap()
+    }
+
+    fn vault(groups: Vec<Group>, records: Vec<Record>) -> PasswordData {
+        PasswordData {
+            groups,
+            records,
+            next_id: 2,
+            attachments: AttachmentStore::default(),
+        }
+    }
+
+    #[test]
+    fn zero_id_is_reported_and_repaired() {
+        let mut data = vault(
+            vec![Group::new(1, 0, "Root".to_string())],
+            vec![record(0, 1), record(0, 1), record(2, 1)],
+        );
+
+        assert_eq!(validate_vault(&data).problems, [VaultProblem::ReservedId]);
+
+        let report = repair_vault(&mut data).unwrap();
+        assert_eq!(report.problems, [VaultProblem::ReservedId]);
+        assert!(validate_vault(&data).is_ok());
+        assert!(data.records.iter().all(|r| r.id != 0));
+    }
+
+    #[test]
+    fn zero_id_group_is_not_a_duplicate_of_root_pid() {
+        let data = vault(
+            vec![Group::new(1, 0, "Root".to_string()), Group::new(0, 1, "Дочерняя".to_string())],
+            vec![],
+        );
+
+        assert_eq!(validate_vault(&data).problems, [VaultProblem::ReservedId]);
+    }
+
+    #[test]
+    fn duplicates_are_reported_once_per_id() {
+        let data = vault(
+            vec![Group::new(1, 0, "Root".to_string()), Group::new(2, 1, "Группа".to_string())],
+            vec![record(2, 1), record(2, 1), record(3, 1)],
+        );
+
+        assert_eq!(validate_vault(&data).problems, [VaultProblem::DuplicateId { id: 2 }]);
+    }
+
+    fn group(id: u32, pid: u32) -> Group {
+        Group::new(id, pid, format!("Группа {}", id))
+    }
+
+    fn parent(data: &PasswordData, group_id: u32) -> u32 {
+        data.groups.iter().find(|g| g.id == group_id).unwrap().pid
+    }
+
+    fn recovered_id(data: &PasswordData) -> u32 {
+        data.groups.iter().find(|g| g.name == RECOVERED_GROUP_NAME).unwrap().id
+    }
+
+    #[test]
+    fn orphans_are_moved_into_recovered_group() {
+        let mut data = vault(vec![group(1, 0), group(2, 1), group(3, 50)], vec![record(10, 2), record(11, 60)]);
+
+        let report = repair_vault(&mut data).unwrap();
+        assert_eq!(
+            report.problems,
+            [
+                VaultProblem::OrphanGroup { group_id: 3, pid: 50 },
+                VaultProblem::OrphanRecord { record_id: 11, pid: 60 }
+            ]
+        );
+
+        let recovered = recovered_id(&data);
+        assert_eq!(parent(&data, recovered), 1);
+        assert_eq!(parent(&data, 3), recovered);
+        assert_eq!(data.records.iter().map(|r| (r.id, r.pid)).collect::<Vec<_>>(), [(10, 2), (11, recovered)]);
+        assert!(validate_vault(&data).is_ok());
+    }
+
+    #[test]
+    fn existing_recovered_group_is_reused() {
+        let mut data = vault(
+            vec![group(1, 0), Group::new(2, 1, RECOVERED_GROUP_NAME.to_string())],
+            vec![record(10, 70)],
+        );
+
+        repair_vault(&mut data).unwrap();
+        assert_eq!(data.groups.len(), 2);
+        assert_eq!(data.records[0].pid, 2);
+    }
+
+    #[test]
+    fn cycles_are_broken_at_smallest_id() {
+        // 3 → 4 → 5 → 3 не связаны с корнем; 6 - потомок цикла
+        let mut data = vault(vec![group(1, 0), group(3, 5), group(4, 3), group(5, 4), group(6, 4)], vec![]);
+
+        let report = repair_vault(&mut data).unwrap();
+        assert_eq!(report.problems, [VaultProblem::GroupCycle { group_ids: vec![3, 4, 5] }]);
+
+        let recovered = recovered_id(&data);
+        assert_eq!(parent(&data, 3), recovered);
+        assert_eq!((parent(&data, 4), parent(&data, 5), parent(&data, 6)), (3, 4, 4));
+        assert!(validate_vault(&data).is_ok());
+    }
+
+    #[test]
+    fn self_parent_is_a_cycle() {
+        let mut data = vault(vec![group(1, 0), group(2, 2)], vec![]);
+
+        assert_eq!(validate_vault(&data).problems, [VaultProblem::GroupCycle { group_ids: vec![2] }]);
+        repair_vault(&mut data).unwrap();
+        assert_eq!(parent(&data, 2), recovered_id(&data));
+        assert!(validate_vault(&data).is_ok());
+    }
+
+    #[test]
+    fn extra_roots_become_children_of_smallest_root() {
+        let mut data = vault(vec![group(5, 0), group(2, 0), group(7, 0), group(8, 7)], vec![record(10, 7)]);
+
+        let report = repair_vault(&mut data).unwrap();
+        assert_eq!(report.problems, [VaultProblem::MultipleRoots { group_ids: vec![5, 2, 7] }]);
+
+        assert_eq!((parent(&data, 2), parent(&data, 5), parent(&data, 7), parent(&data, 8)), (0, 2, 2, 7));
+        assert_eq!(data.records[0].pid, 7);
+        // Потерянных объектов нет - группа Recovered не создается
+        assert!(data.groups.iter().all(|g| g.name != RECOVERED_GROUP_NAME));
+        assert!(validate_vault(&data).is_ok());
+    }
+
+    #[test]
+    fn missing_root_is_created() {
+        // Единственные группы образуют цикл, корня нет
+        let mut data = vault(vec![group(1, 2), group(2, 1)], vec![record(10, 1)]);
+
+        let report = repair_vault(&mut data).unwrap();
+        assert_eq!(report.problems, [VaultProblem::MissingRoot, VaultProblem::GroupCycle { group_ids: vec![1, 2] }]);
+
+        let root = data.groups.iter().find(|g| g.pid == 0).unwrap();
+        assert_eq!(root.name, ROOT_GROUP_NAME);
+        let recovered = recovered_id(&data);
+        assert_eq!(parent(&data, recovered), root.id);
+        assert_eq!((parent(&data, 1), parent(&data, 2)), (recovered, 1));
+        assert_eq!(data.records[0].pid, 1);
+        assert!(validate_vault(&data).is_ok());
+    }
+
+    #[test]
+    fn empty_vault_gets_a_root() {
+        let mut data = vault(vec![], vec![record(10, 1)]);
+
+        repair_vault(&mut data).unwrap();
+        let root = data.groups.iter().find(|g| g.pid == 0).unwrap().id;
+        assert_eq!(data.records[0].pid, recovered_id(&data));
+        assert_eq!(parent(&data, recovered_id(&data)), root);
+        assert!(validate_vault(&data).is_ok());
+    }
+}
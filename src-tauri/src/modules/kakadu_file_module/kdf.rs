@@ -0,0 +1,202 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use sha2::{Digest, Sha256};
+
+use super::error::KakaduError;
+use super::secret::{empty_key, DerivedKey, ExposeSecretMut};
+
+/// Длина получаемого ключа в байтах (AES-256)
+pub const KEY_LEN: usize = 32;
+
+/// Идентификаторы KDF, записываемые в заголовок файла
+const KDF_SHA256: u8 = 0;
+const KDF_ARGON2ID: u8 = 1;
+
+/// Верхние границы параметров Argon2id при чтении файла,
+/// чтобы поврежденный заголовок не мог исчерпать память или время
+const ARGON2_MAX_M_COST_KIB: u32 = 1024 * 1024;
+const ARGON2_MAX_T_COST: u32 = 64;
+const ARGON2_MAX_P_COST: u32 = 64;
+
+/// Параметры функции получения ключа, хранящиеся в заголовке
+#[derive(Debug, Clone, PartialEq)]
+pub enum KdfParams {
+    /// SHA-256 от соли и пароля. Только для чтения ранних файлов v2
+    Sha256,
+    /// Argon2id с настраиваемыми затратами памяти, итераций и потоков
+    Argon2id {
+        m_cost_kib: u32, // Объем памяти в КиБ
+        t_cost: u32,     // Количество итераций
+        p_cost: u32,     // Степень параллелизма
+    },
+}
+
+impl Default for KdfParams {
+    /// Параметры, используемые при записи новых файлов
+    fn default() -> Self {
+        KdfParams::Argon2id {
+            m_cost_kib: 64 * 1024,
+            t_cost: 3,
+            p_cost: 4,
+        }
+    }
+}
+
+impl KdfParams {
+    /// Идентификатор KDF в заголовке
+    pub fn id(&self) -> u8 {
+        match self {
+            KdfParams::Sha256 => KDF_SHA256,
+            KdfParams::Argon2id { .. } => KDF_ARGON2ID,
+        }
+    }
+
+    /// Допускается ли запись новых файлов с этими параметрами
+    pub fn is_writable(&self) -> bool {
+        !matches!(self, KdfParams::Sha256)
+    }
+
+    /// Кодирует параметры KDF для заголовка
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            KdfParams::Sha256 => Vec::new(),
+            KdfParams::Argon2id {
+                m_cost_kib,
+                t_cost,
+                p_cost,
+            } => [m_cost_kib, t_cost, p_cost]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+        }
+    }
+
+    /// Восстанавливает параметры KDF из идентификатора и байтов заголовка
+    pub fn decode(id: u8, params: &[u8]) -> Result<Self, KakaduError> {
+        match id {
+            KDF_SHA256 => Ok(KdfParams::Sha256),
+            KDF_ARGON2ID => {
+                if params.len() != 12 {
+                    return Err(KakaduError::Corrupted("некорректная длина параметров Argon2id".to_string()));
+                }
+                let read = |i: usize| u32::from_le_bytes(params[i * 4..i * 4 + 4].try_into().unwrap());
+                let (m_cost_kib, t_cost, p_cost) = (read(0), read(1), read(2));
+
+                if m_cost_kib > ARGON2_MAX_M_COST_KIB || t_cost > ARGON2_MAX_T_COST || p_cost > ARGON2_MAX_P_COST {
+                    return Err(KakaduError::Corrupted("параметры Argon2id превышают допустимые значения".to_string()));
+                }
+
+                Ok(KdfParams::Argon2id {
+                    m_cost_kib,
+                    t_cost,
+                    p_cost,
+                })
+            }
+            other => Err(KakaduError::Corrupted(format!("неизвестный идентификатор KDF: {}", other))),
+        }
+    }
+
+    /// Получает 256-битный ключ из пароля и соли
+    pub fn derive_key(&self, password: &str, salt: &[u8]) -> Result<DerivedKey, KakaduError> {
+        let mut key = empty_key();
+
+        match self {
+            KdfParams::Sha256 => {
+                let mut hasher = Sha256::new();
+                hasher.update(salt);
+                hasher.update(password.as_bytes());
+                hasher.finalize_into(key.expose_secret_mut().into());
+                Ok(key)
+            }
+            KdfParams::Argon2id {
+                m_cost_kib,
+                t_cost,
+                p_cost,
+            } => {
+                let params = Params::new(*m_cost_kib, *t_cost, *p_cost, Some(KEY_LEN))
+                    .map_err(|e| KakaduError::Corrupted(format!("некорректные параметры Argon2id: {}", e)))?;
+                let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+                argon2
+                    .hash_password_into(password.as_bytes(), salt, key.expose_secret_mut())
+                    .map_err(|e| KakaduError::Crypto(format!("ошибка получения ключа Argon2id: {}", e)))?;
+                Ok(key)
+            }
+        }
+    }
+}
+
+/// Получает ключ устаревшим способом: один проход SHA-256 без соли
+///
+/// Используется только для файлов без заголовка и формата устройства Crypto Kakadu
+pub fn legacy_key(password: &str) -> DerivedKey {
+    let mut key = empty_key();
+    let mut hasher = Sha256::new();
+    hasher.update(password.as_bytes());
+    hasher.finalize_into(key.expose_secret_mut().into());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argon2_params(m_cost_kib: u32, t_cost: u32, p_cost: u32) -> Vec<u8> {
+        KdfParams::Argon2id {
+            m_cost_kib,
+            t_cost,
+            p_cost,
+        }
+        .encode()
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        for params in [
+            KdfParams::default(),
+            KdfParams::Sha256,
+            KdfParams::Argon2id {
+                m_cost_kib: ARGON2_MAX_M_COST_KIB,
+                t_cost: ARGON2_MAX_T_COST,
+                p_cost: ARGON2_MAX_P_COST,
+            },
+        ] {
+            assert_eq!(KdfParams::decode(params.id(), &params.encode()).unwrap(), params);
+        }
+    }
+
+    #[test]
+    fn decode_rejects_params_above_limits() {
+        let too_much = [
+            argon2_params(ARGON2_MAX_M_COST_KIB + 1, 3, 4),
+            argon2_params(64 * 1024, ARGON2_MAX_T_COST + 1, 4),
+            argon2_params(64 * 1024, 3, ARGON2_MAX_P_COST + 1),
+            argon2_params(u32::MAX, u32::MAX, u32::MAX),
+        ];
+        for params in too_much {
+            assert!(matches!(KdfParams::decode(KDF_ARGON2ID, &params), Err(KakaduError::Corrupted(_))));
+        }
+    }
+
+    #[test]
+    fn decode_rejects_wrong_length() {
+        let params = argon2_params(64 * 1024, 3, 4);
+        for len in [0, 4, 11] {
+            assert!(matches!(KdfParams::decode(KDF_ARGON2ID, &params[..len]), Err(KakaduError::Corrupted(_))));
+        }
+
+        let mut longer = params.clone();
+        longer.push(0);
+        assert!(matches!(KdfParams::decode(KDF_ARGON2ID, &longer), Err(KakaduError::Corrupted(_))));
+    }
+
+    #[test]
+    fn decode_rejects_unknown_id() {
+        assert!(matches!(KdfParams::decode(2, &[]), Err(KakaduError::Corrupted(_))));
+    }
+
+    #[test]
+    fn sha256_is_read_only() {
+        assert!(!KdfParams::Sha256.is_writable());
+        assert!(KdfParams::default().is_writable());
+    }
+}
@@ -0,0 +1,352 @@
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use super::secret::SecretField;
+
+/// Допустимая длина номера платежной карты (ISO/IEC 7812)
+const CARD_NUMBER_MIN_LEN: usize = 12;
+const CARD_NUMBER_MAX_LEN: usize = 19;
+
+/// Допустимый год окончания действия карты (четыре цифры)
+const CARD_EXPIRY_YEARS: std::ops::RangeInclusive<u16> = 1000..=9999;
+
+/// Вид записи с данными, специфичными для вида
+///
+/// Общие поля (`name`, `login`, `password`, `url`, `notes`, ...) остаются в `Record`:
+/// для Wi-Fi сети в `password` хранится ключ сети, для SSH-ключа - парольная фраза
+/// закрытого ключа, для защищенной заметки текст хранится в `notes`.
+/// Записи без поля `kind` (созданные до появления видов) считаются логинами
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RecordKind {
+    /// Логин для сайта или приложения
+    #[default]
+    Login,
+    /// Платежная карта
+    Card(CardDetails),
+    /// Личные данные
+    Identity(IdentityDetails),
+    /// Защищенная заметка
+    SecureNote,
+    /// Пара SSH-ключей
+    SshKey(SshKeyDetails),
+    /// Wi-Fi сеть
+    Wifi(WifiDetails),
+    /// Лицензия на программное обеспечение
+    License(LicenseDetails),
+}
+
+/// Данные платежной карты
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CardDetails {
+    pub holder: String,      // Владелец, как напечатано на карте
+    pub number: SecretField, // Номер карты (только цифры)
+    pub expiry_month: u8,    // Месяц окончания действия (1-12)
+    pub expiry_year: u16,    // Год окончания действия (четыре цифры)
+    pub cvv: SecretField,    // CVV/CVC
+    pub pin: SecretField,    // PIN-код
+}
+
+/// Личные данные
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct IdentityDetails {
+    pub first_name: String,           // Имя
+    pub middle_name: String,          // Отчество
+    pub last_name: String,            // Фамилия
+    pub birth_date: String,           // Дата рождения (ГГГГ-ММ-ДД)
+    pub email: String,                // Электронная почта
+    pub phone: String,                // Телефон
+    pub address: String,              // Почтовый адрес
+    pub document_number: SecretField, // Номер паспорта или другого документа
+}
+
+/// Пара SSH-ключей
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SshKeyDetails {
+    pub public_key: String,       // Открытый ключ в формате OpenSSH (`тип base64 комментарий`)
+    pub private_key: SecretField, // Закрытый ключ в формате PEM/OpenSSH
+    pub key_type: String,         // Тип ключа (`ssh-ed25519`, `ssh-rsa`, ...), вычисляется
+    pub fingerprint: String,      // Отпечаток `SHA256:...` как у `ssh-keygen -l`, вычисляется
+}
+
+/// Способ защиты Wi-Fi сети
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum WifiSecurity {
+    Open,
+    Wep,
+    WpaPersonal,
+    #[default]
+    Wpa2Personal,
+    Wpa3Personal,
+    Enterprise,
+}
+
+/// Wi-Fi сеть
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WifiDetails {
+    pub ssid: String,           // Имя сети
+    pub security: WifiSecurity, // Способ защиты
+    pub hidden: bool,           // Скрытая сеть (SSID не транслируется)
+}
+
+/// Лицензия на программное обеспечение
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LicenseDetails {
+    pub product: String,          // Название продукта
+    pub version: String,          // Версия
+    pub licensee: String,         // На кого оформлена лицензия
+    pub email: String,            // Электронная почта регистрации
+    pub license_key: SecretField, // Лицензионный ключ
+    pub order_number: String,     // Номер заказа
+    pub expires_at: Option<u64>,  // Окончание действия (секунды с UNIX-эпохи)
+}
+
+impl RecordKind {
+    /// Запись - обычный логин (вид по умолчанию)
+    pub fn is_login(&self) -> bool {
+        matches!(self, RecordKind::Login)
+    }
+
+    /// Проверяет и нормализует данные перед сохранением в записи
+    ///
+    /// # Поведение
+    /// * номер карты очищается от пробелов и дефисов и проверяется алгоритмом Луна
+    /// * для SSH-ключа тип и отпечаток вычисляются из открытого ключа
+    ///
+    /// # Ошибки
+    /// Возвращает текст ошибки для фронтенда, если данные некорректны
+    pub fn validate(&mut self) -> Result<(), String> {
+        match self {
+            RecordKind::Card(card) => card.validate(),
+            RecordKind::SshKey(key) => key.validate(),
+            RecordKind::Wifi(wifi) => {
+                if wifi.ssid.is_empty() {
+                    return Err("Имя Wi-Fi сети не может быть пустым".to_string());
+                }
+                Ok(())
+            }
+            RecordKind::Login | RecordKind::Identity(_) | RecordKind::SecureNote | RecordKind::License(_) => Ok(()),
+        }
+    }
+}
+
+impl CardDetails {
+    fn validate(&mut self) -> Result<(), String> {
+        let number: Zeroizing<String> = Zeroizing::new(
+            self.number
+                .expose()
+                .chars()
+                .filter(|c| !c.is_whitespace() && *c != '-')
+                .collect(),
+        );
+
+        if !number.is_empty() {
+            if !number.chars().all(|c| c.is_ascii_digit())
+                || !(CARD_NUMBER_MIN_LEN..=CARD_NUMBER_MAX_LEN).contains(&number.len())
+            {
+                return Err(format!(
+                    "Номер карты должен содержать от {} до {} цифр",
+                    CARD_NUMBER_MIN_LEN, CARD_NUMBER_MAX_LEN
+                ));
+            }
+            if !luhn_valid(&number) {
+                return Err("Номер карты не проходит проверку контрольной цифры".to_string());
+            }
+        }
+        self.number = SecretField::from(number.to_string());
+
+        // Срок не задан (месяц и год равны нулю) или задан полностью: месяц 1-12, год из четырех цифр
+        let no_expiry = self.expiry_month == 0 && self.expiry_year == 0;
+        if !no_expiry && (!(1..=12).contains(&self.expiry_month) || !CARD_EXPIRY_YEARS.contains(&self.expiry_year)) {
+            return Err("Некорректный срок действия карты: месяц 1-12, год из четырех цифр".to_string());
+        }
+
+        let cvv = self.cvv.expose();
+        if !cvv.is_empty() && (!cvv.chars().all(|c| c.is_ascii_digit()) || !(3..=4).contains(&cvv.len())) {
+            return Err("CVV должен содержать 3 или 4 цифры".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl SshKeyDetails {
+    fn validate(&mut self) -> Result<(), String> {
+        self.public_key = self.public_key.trim().to_string();
+        if self.public_key.is_empty() {
+            self.key_type.clear();
+            self.fingerprint.clear();
+        } else {
+            let (key_type, fingerprint) = ssh_fingerprint(&self.public_key)?;
+            self.key_type = key_type;
+            self.fingerprint = fingerprint;
+        }
+
+        let private_key = self.private_key.expose().trim_start();
+        if !private_key.is_empty() && (!private_key.starts_with("-----BEGIN") || !private_key.contains("PRIVATE KEY-----")) {
+            return Err("Закрытый ключ должен быть в формате PEM или OpenSSH".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Проверка номера алгоритмом Луна: контрольная сумма с удвоением каждой второй цифры справа
+///
+/// Ожидает строку из цифр
+pub fn luhn_valid(number: &str) -> bool {
+    let sum: u32 = number
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| {
+            let digit = u32::from(b - b'0');
+            match (i % 2 == 1, digit * 2) {
+                (true, doubled) if doubled > 9 => doubled - 9,
+                (true, doubled) => doubled,
+                (false, _) => digit,
+            }
+        })
+        .sum();
+
+    !number.is_empty() && sum.is_multiple_of(10)
+}
+
+/// Вычисляет отпечаток открытого SSH-ключа в формате OpenSSH
+///
+/// # Аргументы
+/// * `public_key` - строка `тип base64 [комментарий]` из `.pub` файла или `authorized_keys`
+///
+/// # Возвращает
+/// Тип ключа и отпечаток `SHA256:<base64 без выравнивания>`
+///
+/// # Ошибки
+/// Возвращает ошибку если строка не в формате OpenSSH или тип внутри ключа
+/// не совпадает с указанным
+pub fn ssh_fingerprint(public_key: &str) -> Result<(String, String), String> {
+    let invalid = || "Открытый ключ должен быть в формате OpenSSH: `тип base64 [комментарий]`".to_string();
+
+    let mut parts = public_key.split_whitespace();
+    let key_type = parts.next().ok_or_else(invalid)?;
+    let blob = parts
+        .next()
+        .and_then(|b| STANDARD.decode(b).ok())
+        .ok_or_else(invalid)?;
+
+    // Ключ начинается со строки SSH (u32 BE длина + байты) с типом ключа
+    let embedded_type = blob
+        .get(..4)
+        .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+        .and_then(|len| blob.get(4..len.checked_add(4)?))
+        .ok_or_else(invalid)?;
+    if embedded_type != key_type.as_bytes() {
+        return Err("Тип ключа не совпадает с содержимым открытого ключа".to_string());
+    }
+
+    Ok((
+        key_type.to_string(),
+        format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(&blob))),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Открытые ключи, сгенерированные `ssh-keygen`, и их отпечатки из `ssh-keygen -lf`
+    const ED25519_KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOtS2F4YwP6TuiAE+k/M+52Fs2QbAmeY3+H2++rfTFvr test@example";
+    const ED25519_FINGERPRINT: &str = "SHA256:ex90+/UANWcYWSSnZZMuEPnhk4Mfjux9VncpEARJ2qM";
+    const ECDSA_KEY: &str = "ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBF7L2SqL/4KWUK8nulIQg7AEdcrd5U4Da99G7tpNeESkNiEsGGopN8cmZAi2w4zrSp6ToGo+1HiuQiqetut+TYg=";
+    const ECDSA_FINGERPRINT: &str = "SHA256:KuOVuDCf9QsB2k9dt1xWFnl9pzPRXopFimI6VIY+AJY";
+
+    fn card(number: &str, month: u8, year: u16) -> RecordKind {
+        RecordKind::Card(CardDetails {
+            number: SecretField::from(number.to_string()),
+            expiry_month: month,
+            expiry_year: year,
+            ..CardDetails::default()
+        })
+    }
+
+    #[test]
+    fn luhn_accepts_valid_numbers() {
+        for number in ["4111111111111111", "5555555555554444", "378282246310005", "6011111111111117", "0", "18"] {
+            assert!(luhn_valid(number), "{}", number);
+        }
+    }
+
+    #[test]
+    fn luhn_rejects_invalid_numbers() {
+        for number in ["4111111111111112", "5555555555554445", "378282246310006", "1", "19", ""] {
+            assert!(!luhn_valid(number), "{}", number);
+        }
+    }
+
+    #[test]
+    fn card_number_is_normalized_and_checked() {
+        let mut kind = card("4111 1111-1111 1111", 12, 2030);
+        kind.validate().unwrap();
+        let RecordKind::Card(details) = &kind else { unreachable!() };
+        assert_eq!(details.number.expose(), "4111111111111111");
+
+        assert!(card("4111 1111 1111 1112", 12, 2030).validate().is_err());
+        assert!(card("41111111111", 12, 2030).validate().is_err());
+        assert!(card("4111a11111111111", 12, 2030).validate().is_err());
+        assert!(card("", 0, 0).validate().is_ok());
+    }
+
+    #[test]
+    fn card_expiry_requires_month_and_four_digit_year() {
+        for (month, year) in [(1, 2030), (12, 1000), (6, 9999), (0, 0)] {
+            assert!(card("", month, year).validate().is_ok(), "{}/{}", month, year);
+        }
+        for (month, year) in [(13, 2030), (0, 2030), (12, 0), (12, 30), (12, 999), (12, 10000)] {
+            assert!(card("", month, year).validate().is_err(), "{}/{}", month, year);
+        }
+    }
+
+    #[test]
+    fn ssh_fingerprint_matches_ssh_keygen() {
+        assert_eq!(
+            ssh_fingerprint(ED25519_KEY).unwrap(),
+            ("ssh-ed25519".to_string(), ED25519_FINGERPRINT.to_string())
+        );
+        assert_eq!(ssh_fingerprint(ECDSA_KEY).unwrap().1, ECDSA_FINGERPRINT);
+
+        let mut kind = RecordKind::SshKey(SshKeyDetails {
+            public_key: format!("  {}\n", ED25519_KEY),
+            ..SshKeyDetails::default()
+        });
+        kind.validate().unwrap();
+        let RecordKind::SshKey(details) = &kind else { unreachable!() };
+        assert_eq!(details.key_type, "ssh-ed25519");
+        assert_eq!(details.fingerprint, ED25519_FINGERPRINT);
+    }
+
+    #[test]
+    fn malformed_ssh_keys_are_rejected() {
+        let mismatched = ED25519_KEY.replacen("ssh-ed25519", "ssh-rsa", 1);
+        for key in [
+            "",
+            "ssh-ed25519",
+            "ssh-ed25519 not-base64!",
+            mismatched.as_str(),
+            // Длина типа 0xFFFFFFFF: граница блока не должна переполняться
+            "ssh-ed25519 /////3NzaC1lZDI1NTE5",
+            // Длина типа больше самого блока
+            "ssh-ed25519 AAAAFHNzaC1lZDI1NTE5",
+        ] {
+            assert!(ssh_fingerprint(key).is_err(), "{}", key);
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroizing;
+
+use super::tree::GroupTree;
+use super::{PasswordData, Record};
+
+/// Минимальная длина слова запроса, для которого допускаются опечатки
+const TYPO_MIN_LEN: usize = 4;
+
+/// Параметры поиска записей
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub group_id: Option<u32>,   // Искать только в группе и ее потомках
+    pub include_passwords: bool, // Искать также по паролям (по умолчанию нет)
+    pub limit: Option<usize>,    // Максимальное количество результатов
+}
+
+/// Поле записи, по которому найдено совпадение
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchField {
+    Name,
+    Login,
+    Url,
+    Password,
+}
+
+impl SearchField {
+    /// Вес поля при ранжировании: совпадение в названии важнее, чем в URL
+    fn weight(self) -> f64 {
+        match self {
+            SearchField::Name => 1.0,
+            SearchField::Login => 0.8,
+            SearchField::Url => 0.7,
+            SearchField::Password => 0.5,
+        }
+    }
+}
+
+/// Найденная запись. Пароль в результат не входит
+#[derive(Debug, Serialize, Clone)]
+pub struct SearchResult {
+    pub id: u32,                  // ID записи
+    pub pid: u32,                 // ID группы записи
+    pub name: String,             // Название записи
+    pub login: String,            // Логин
+    pub url: String,              // URL сайта
+    pub path: Vec<String>,        // Путь (breadcrumb) группы записи от корня
+    pub score: f64,               // Релевантность от 0 до 1
+    pub fields: Vec<SearchField>, // Поля, в которых найдено совпадение
+}
+
+/// Ищет записи по названию, логину и URL (и паролю, если это явно разрешено)
+///
+/// # Поведение
+/// Запрос и поля приводятся к нижнему регистру, диакритические знаки отбрасываются
+/// (`é` -> `e`, `ё` -> `е`, `й` -> `и`), поэтому кириллица и латиница ищутся одинаково.
+/// Запрос делится на слова; запись подходит, если каждое слово нашлось хотя бы в одном поле.
+/// Слово совпадает точно, по началу, как подстрока, как подпоследовательность символов
+/// или с одной-двумя опечатками - в порядке убывания релевантности
+///
+/// # Возвращает
+/// Результаты по убыванию релевантности, затем по названию. Пустой запрос ничего не находит
+pub fn search_records(data: &PasswordData, query: &str, options: &SearchOptions) -> Vec<SearchResult> {
+    let tokens: Vec<Vec<char>> = normalize(query)
+        .split_whitespace()
+        .map(|t| t.chars().collect())
+        .collect();
+    if tokens.is_empty() {
+        return Vec::new();
+    }
+
+    let tree = GroupTree::new(data);
+    let scope: Option<HashSet<u32>> = options.group_id.map(|id| tree.subtree(id).into_iter().collect());
+
+    let mut results: Vec<SearchResult> = data
+        .records
+        .iter()
+        .filter(|r| scope.as_ref().is_none_or(|s| s.contains(&r.pid)))
+        .filter_map(|r| match_record(r, &tokens, options.include_passwords))
+        .map(|(record, score, fields)| SearchResult {
+            id: record.id,
+            pid: record.pid,
+            name: record.name.clone(),
+            login: record.login.clone(),
+            url: record.url.clone(),
+            path: tree.path(record.pid),
+            score,
+            fields,
+        })
+        .collect();
+
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    if let Some(limit) = options.limit {
+        results.truncate(limit);
+    }
+
+    results
+}
+
+/// Сопоставляет запись со словами запроса
+///
+/// # Возвращает
+/// Запись, среднюю релевантность по словам и список совпавших полей,
+/// либо `None`, если хотя бы одно слово не найдено
+fn match_record<'a>(
+    record: &'a Record,
+    tokens: &[Vec<char>],
+    include_passwords: bool,
+) -> Option<(&'a Record, f64, Vec<SearchField>)> {
+    let mut fields: Vec<(SearchField, Zeroizing<Vec<char>>)> = vec![
+        (SearchField::Name, Zeroizing::new(normalize(&record.name).chars().collect())),
+        (SearchField::Login, Zeroizing::new(normalize(&record.login).chars().collect())),
+        (SearchField::Url, Zeroizing::new(normalize(&record.url).chars().collect())),
+    ];
+    if include_passwords {
+        // Нормализованная копия пароля затирается вместе с остальными полями
+        let password = Zeroizing::new(normalize(record.password.expose()));
+        fields.push((SearchField::Password, Zeroizing::new(password.chars().collect())));
+    }
+
+    let mut total = 0.0;
+    let mut matched = Vec::new();
+
+    for token in tokens {
+        let best = fields
+            .iter()
+            .filter_map(|(field, text)| match_token(token, text).map(|score| (*field, score * field.weight())))
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))?;
+
+        total += best.1;
+        if !matched.contains(&best.0) {
+            matched.push(best.0);
+        }
+    }
+
+    Some((record, total / tokens.len() as f64, matched))
+}
+
+/// Оценивает совпадение слова запроса с текстом поля
+///
+/// # Возвращает
+/// Релевантность от 0 до 1 или `None`, если совпадения нет
+fn match_token(token: &[char], text: &[char]) -> Option<f64> {
+    if text.is_empty() || token.len() > text.len() + 2 {
+        return None;
+    }
+    if token == text {
+        return Some(1.0);
+    }
+    if text.starts_with(token) {
+        return Some(0.9);
+    }
+
+    // Подстрока: совпадение с начала слова ценнее, чем в его середине
+    let positions: Vec<usize> = (0..text.len())
+        .filter(|&i| text[i..].starts_with(token))
+        .collect();
+    if !positions.is_empty() {
+        let at_word_start = positions.iter().any(|&i| i == 0 || !text[i - 1].is_alphanumeric());
+        return Some(if at_word_start { 0.8 } else { 0.7 });
+    }
+
+    if let Some(span) = subsequence_span(token, text) {
+        return Some(0.3 + 0.3 * token.len() as f64 / span as f64);
+    }
+
+    if token.len() >= TYPO_MIN_LEN {
+        let max_distance = if token.len() >= 8 { 2 } else { 1 };
+        let has_close_word = text
+            .split(|c| !c.is_alphanumeric())
+            .any(|word| edit_distance(token, word) <= max_distance);
+        if has_close_word {
+            return Some(0.4);
+        }
+    }
+
+    None
+}
+
+/// Длина самого короткого фрагмента текста, начинающегося с первого символа слова
+/// и содержащего все символы слова по порядку
+fn subsequence_span(token: &[char], text: &[char]) -> Option<usize> {
+    if token.len() < 2 {
+        return None;
+    }
+
+    let mut best: Option<usize> = None;
+    for start in (0..text.len()).filter(|&i| text[i] == token[0]) {
+        let mut next = 1;
+        for (offset, c) in text[start + 1..].iter().enumerate() {
+            if *c == token[next] {
+                next += 1;
+                if next == token.len() {
+                    let span = offset + 2;
+                    best = Some(best.map_or(span, |b| b.min(span)));
+                    break;
+                }
+            }
+        }
+    }
+
+    best
+}
+
+/// Расстояние Дамерау-Левенштейна (с перестановкой соседних символов)
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let width = b.len() + 1;
+    let mut d = vec![0usize; (a.len() + 1) * width];
+
+    for i in 0..=a.len() {
+        d[i * width] = i;
+    }
+    for (j, cell) in d[..width].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut value = (d[(i - 1) * width + j] + 1)
+                .min(d[i * width + j - 1] + 1)
+                .min(d[(i - 1) * width + j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                value = value.min(d[(i - 2) * width + j - 2] + 1);
+            }
+            d[i * width + j] = value;
+        }
+    }
+
+    d[a.len() * width + b.len()]
+}
+
+/// Приводит строку к виду для сравнения: нижний регистр без диакритических знаков
+fn normalize(s: &str) -> String {
+    s.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::kakadu_file_module::attachments::AttachmentStore;
+    use crate::modules::kakadu_file_module::Group;
+    use serde_json::json;
+
+    fn record(id: u32, pid: u32, name: &str, login: &str, password: &str) -> Record {
+        serde_json::from_value(json!({
+            "id": id, "pid": pid, "name": name, "login": login, "password": password, "url": "",
+            "loginSymbol": "TAB", "passwordSymbol": "ENTER", "urlSymbol": "NONE"
+        }))
+        .unwrap()
+    }
+
+    fn vault(records: Vec<Record>) -> PasswordData {
+        let groups = [(1, 0, "Root"), (2, 1, "Работа"), (3, 2, "Почта"), (4, 1, "Дом")];
+        PasswordData {
+            groups: groups.iter().map(|&(id, pid, name)| Group::new(id, pid, name.to_string())).collect(),
+            records,
+            next_id: 100,
+            attachments: AttachmentStore::default(),
+        }
+    }
+
+    fn ids(results: &[SearchResult]) -> Vec<u32> {
+        results.iter().map(|r| r.id).collect()
+    }
+
+    #[test]
+    fn case_and_diacritics_are_ignored() {
+        let data = vault(vec![
+            record(10, 1, "Café", "", ""),
+            record(11, 1, "Ёлка", "", ""),
+            record(12, 1, "Елена", "", ""),
+        ]);
+        let options = SearchOptions::default();
+
+        assert_eq!(ids(&search_records(&data, "cafe", &options)), [10]);
+        assert_eq!(ids(&search_records(&data, "CAFÉ", &options)), [10]);
+        assert_eq!(ids(&search_records(&data, "елка", &options)), [11]);
+        assert_eq!(ids(&search_records(&data, "ЁЛКА", &options)), [11]);
+        assert_eq!(ids(&search_records(&data, "Ёлена", &options)), [12]);
+        assert!(search_records(&data, "  ", &options).is_empty());
+    }
+
+    #[test]
+    fn results_are_ranked_by_match_quality() {
+        let data = vault(vec![
+            record(10, 1, "Digit", "", ""),   // подстрока в середине слова
+            record(11, 1, "Gait", "", ""),    // подпоследовательность
+            record(12, 1, "My git", "", ""),  // подстрока с начала слова
+            record(13, 1, "GitHub", "", ""),  // начало
+            record(14, 1, "Git", "", ""),     // точное совпадение
+            record(15, 1, "Gitt", "", ""),    // начало (название идет позже GitHub)
+            record(16, 1, "Mail", "", ""),    // нет совпадения
+        ]);
+
+        let results = search_records(&data, "git", &SearchOptions::default());
+        assert_eq!(ids(&results), [14, 13, 15, 12, 10, 11]);
+        assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_eq!(results[0].fields, [SearchField::Name]);
+    }
+
+    #[test]
+    fn typos_rank_below_exact_matches() {
+        let data = vault(vec![record(10, 1, "Amazn", "", ""), record(11, 1, "Amazon", "", "")]);
+
+        assert_eq!(ids(&search_records(&data, "amazon", &SearchOptions::default())), [11, 10]);
+        // Короткие слова опечаток не допускают
+        assert!(search_records(&data, "amx", &SearchOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn every_word_must_match_some_field() {
+        let data = vault(vec![record(10, 1, "Почта", "alice", ""), record(11, 1, "Почта", "bob", "")]);
+
+        let results = search_records(&data, "почта alice", &SearchOptions::default());
+        assert_eq!(ids(&results), [10]);
+        assert_eq!(results[0].fields, [SearchField::Name, SearchField::Login]);
+    }
+
+    #[test]
+    fn group_scope_includes_descendant_groups() {
+        let data = vault(vec![
+            record(10, 1, "Mail", "", ""),
+            record(11, 2, "Mail", "", ""),
+            record(12, 3, "Mail", "", ""),
+            record(13, 4, "Mail", "", ""),
+        ]);
+        let options = SearchOptions { group_id: Some(2), ..Default::default() };
+
+        let results = search_records(&data, "mail", &options);
+        assert_eq!(ids(&results), [11, 12]);
+        assert_eq!(results[1].path, ["Root", "Работа", "Почта"]);
+        assert_eq!(search_records(&data, "mail", &SearchOptions::default()).len(), 4);
+        assert_eq!(search_records(&data, "mail", &SearchOptions { limit: Some(1), ..Default::default() }).len(), 1);
+    }
+
+    #[test]
+    fn secrets_are_searched_only_on_request() {
+        let mut bank = record(10, 1, "Bank", "", "hunter2");
+        bank.custom_fields = serde_json::from_value(json!([{ "name": "PIN", "value": "swordfish", "hidden": true }])).unwrap();
+        let data = vault(vec![bank]);
+        let with_passwords = SearchOptions { include_passwords: true, ..Default::default() };
+
+        assert!(search_records(&data, "hunter2", &SearchOptions::default()).is_empty());
+        let results = search_records(&data, "hunter2", &with_passwords);
+        assert_eq!(ids(&results), [10]);
+        assert_eq!(results[0].fields, [SearchField::Password]);
+
+        // Значения дополнительных полей (в том числе скрытых) в поиске не участвуют
+        assert!(search_records(&data, "swordfish", &SearchOptions::default()).is_empty());
+        assert!(search_records(&data, "swordfish", &with_passwords).is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+use secrecy::SecretBox;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::kdf::KEY_LEN;
+
+pub use secrecy::{ExposeSecret, ExposeSecretMut, SecretString};
+
+/// Мастер-пароль: затирается при освобождении и не выводится в `Debug`
+pub type MasterPassword = SecretString;
+
+/// Ключ шифрования, полученный из мастер-пароля
+pub type DerivedKey = SecretBox<[u8; KEY_LEN]>;
+
+/// Создает обнуленный ключ, который затем заполняет KDF
+pub fn empty_key() -> DerivedKey {
+    SecretBox::new(Box::new([0u8; KEY_LEN]))
+}
+
+/// Секретное строковое поле записи (например, пароль)
+///
+/// Сериализуется как обычная строка, чтобы не менять формат файла и API фронтенда,
+/// но затирает память при освобождении и скрывает значение в `Debug`
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(transparent)]
+pub struct SecretField(String);
+
+impl SecretField {
+    /// Возвращает значение секрета. Не сохраняйте результат дольше необходимого
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Проверяет, пусто ли значение
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretField {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Debug for SecretField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretField(***)")
+    }
+}
@@ -0,0 +1,263 @@
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Сведения о резервной копии файла для отображения в UI
+#[derive(Debug, Serialize, Clone)]
+pub struct BackupInfo {
+    pub index: usize,  // Номер копии (1 - самая свежая)
+    pub path: String,  // Полный путь к файлу копии
+    pub size: u64,     // Размер в байтах
+    pub modified: u64, // Время изменения (секунды с UNIX-эпохи)
+}
+
+/// Путь к резервной копии с номером `index`: `<файл>.bak.<index>`
+pub fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".bak.{}", index));
+    PathBuf::from(name)
+}
+
+/// Атомарно записывает данные в файл, сохраняя предыдущие версии
+///
+/// # Порядок действий
+/// 1. Запись во временный файл в том же каталоге и fsync. Права доступа временного файла
+///    копируются с исходного, новый файл доступен только владельцу
+/// 2. Сдвиг резервных копий `.bak.1..N` и копирование текущего файла в `.bak.1`
+/// 3. Атомарное переименование временного файла поверх исходного
+///
+/// При сбое на любом шаге исходный файл остается нетронутым
+pub fn write_atomic(path: &Path, data: &[u8], backup_count: usize) -> io::Result<()> {
+    let tmp_path = temp_path(path);
+
+    let result = (|| {
+        let mut tmp = create_temp(&tmp_path, path)?;
+        tmp.write_all(data)?;
+        tmp.sync_all()?;
+        drop(tmp);
+
+        if path.exists() {
+            rotate_backups(path, backup_count)?;
+        }
+
+        fs::rename(&tmp_path, path)?;
+        sync_parent_dir(path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
+}
+
+/// Возвращает существующие резервные копии `.bak.1..backup_count`, от свежих к старым
+///
+/// Пропуски в нумерации (например, удаленная вручную копия) не прерывают поиск
+pub fn list_backups(path: &Path, backup_count: usize) -> io::Result<Vec<BackupInfo>> {
+    let mut backups = Vec::new();
+
+    for index in 1..=backup_count {
+        let backup = backup_path(path, index);
+        let meta = match fs::metadata(&backup) {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        backups.push(BackupInfo {
+            index,
+            path: backup.to_string_lossy().into_owned(),
+            size: meta.len(),
+            modified,
+        });
+    }
+
+    Ok(backups)
+}
+
+/// Восстанавливает файл из резервной копии с номером `index`
+///
+/// Текущая версия файла не теряется: она сохраняется как `.bak.1`,
+/// а остальные копии сдвигаются
+pub fn restore_backup(path: &Path, index: usize, backup_count: usize) -> io::Result<()> {
+    let data = fs::read(backup_path(path, index))?;
+    write_atomic(path, &data, backup_count.max(1))
+}
+
+/// Сдвигает копии `.bak.i` в `.bak.i+1`, удаляя лишние, и копирует текущий файл в `.bak.1`
+fn rotate_backups(path: &Path, backup_count: usize) -> io::Result<()> {
+    if backup_count == 0 {
+        return Ok(());
+    }
+
+    let oldest = backup_path(path, backup_count);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+
+    for index in (1..backup_count).rev() {
+        let from = backup_path(path, index);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, index + 1))?;
+        }
+    }
+
+    let first = backup_path(path, 1);
+    fs::copy(path, &first)?;
+    File::open(&first)?.sync_all()
+}
+
+/// Путь к временному файлу рядом с целевым, чтобы rename оставался атомарным
+fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp", file_name))
+}
+
+/// Создает временный файл с правами исходного файла (0600, если его еще нет)
+///
+/// Права задаются и после открытия: оставшийся от сбоя временный файл сохраняет прежние
+#[cfg(unix)]
+fn create_temp(tmp_path: &Path, path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mode = match fs::metadata(path) {
+        Ok(meta) => meta.permissions().mode() & 0o7777,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => 0o600,
+        Err(e) => return Err(e),
+    };
+    let tmp = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(tmp_path)?;
+    tmp.set_permissions(fs::Permissions::from_mode(mode))?;
+    Ok(tmp)
+}
+
+#[cfg(not(unix))]
+fn create_temp(tmp_path: &Path, _path: &Path) -> io::Result<File> {
+    OpenOptions::new().write(true).create(true).truncate(true).open(tmp_path)
+}
+
+/// Синхронизирует каталог, чтобы переименование пережило сбой питания
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Временный каталог, удаляемый вместе с содержимым
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("kakadu-storage-{}-{}", std::process::id(), name));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn indices(backups: &[BackupInfo]) -> Vec<usize> {
+        backups.iter().map(|b| b.index).collect()
+    }
+
+    #[cfg(unix)]
+    fn mode(path: &Path) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).unwrap().permissions().mode() & 0o7777
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_keeps_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("permissions");
+        let path = dir.0.join("vault.kkd");
+
+        write_atomic(&path, b"new", 2).unwrap();
+        assert_eq!(mode(&path), 0o600);
+
+        for expected in [0o600, 0o640] {
+            fs::set_permissions(&path, fs::Permissions::from_mode(expected)).unwrap();
+            write_atomic(&path, b"next", 2).unwrap();
+            assert_eq!(mode(&path), expected);
+            assert_eq!(mode(&backup_path(&path, 1)), expected);
+        }
+    }
+
+    #[test]
+    fn rotation_keeps_backup_count_copies() {
+        let dir = TempDir::new("rotation");
+        let path = dir.0.join("vault.kkd");
+
+        for version in 0..5u8 {
+            write_atomic(&path, &[version], 2).unwrap();
+        }
+
+        assert_eq!(fs::read(&path).unwrap(), [4]);
+        assert_eq!(fs::read(backup_path(&path, 1)).unwrap(), [3]);
+        assert_eq!(fs::read(backup_path(&path, 2)).unwrap(), [2]);
+        assert!(!backup_path(&path, 3).exists());
+        assert_eq!(indices(&list_backups(&path, 2).unwrap()), [1, 2]);
+    }
+
+    #[test]
+    fn list_backups_skips_gaps() {
+        let dir = TempDir::new("gaps");
+        let path = dir.0.join("vault.kkd");
+        fs::write(&path, b"current").unwrap();
+        for index in [1, 3, 5] {
+            fs::write(backup_path(&path, index), [index as u8]).unwrap();
+        }
+
+        assert_eq!(indices(&list_backups(&path, 5).unwrap()), [1, 3, 5]);
+        assert_eq!(indices(&list_backups(&path, 4).unwrap()), [1, 3]);
+        assert!(list_backups(&path, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn restore_keeps_current_version() {
+        let dir = TempDir::new("restore");
+        let path = dir.0.join("vault.kkd");
+        write_atomic(&path, b"old", 3).unwrap();
+        write_atomic(&path, b"new", 3).unwrap();
+
+        restore_backup(&path, 1, 3).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        assert_eq!(fs::read(backup_path(&path, 1)).unwrap(), b"new");
+        assert_eq!(fs::read(backup_path(&path, 2)).unwrap(), b"old");
+    }
+}
@@ -0,0 +1,279 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use super::{Group, PasswordData};
+
+/// Название синтетического узла для групп, недостижимых из корня
+pub const UNREACHABLE_GROUP_NAME: &str = "Unreachable";
+
+/// Узел дерева групп для фронтенда
+#[derive(Debug, Serialize, Clone)]
+pub struct GroupNode {
+    pub id: u32,                   // ID группы
+    pub pid: u32,                  // ID родительской группы
+    pub name: String,              // Название группы
+    pub path: Vec<String>,         // Полный путь (breadcrumb) от корня до группы включительно
+    pub record_count: usize,       // Записей непосредственно в группе
+    pub total_record_count: usize, // Записей в группе и всех вложенных группах
+    pub children: Vec<GroupNode>,  // Дочерние группы
+}
+
+/// Иерархическое представление групп поверх плоского списка `PasswordData::groups`
+///
+/// Строится один раз и позволяет быстро получать детей, путь к корню,
+/// поддерево и количество записей. Все обходы устойчивы к циклам в `pid`
+pub struct GroupTree<'a> {
+    order: Vec<u32>,
+    groups: HashMap<u32, &'a Group>,
+    children: HashMap<u32, Vec<u32>>,
+    record_counts: HashMap<u32, usize>,
+    roots: Vec<u32>,
+}
+
+impl<'a> GroupTree<'a> {
+    /// Строит дерево по данным хранилища
+    pub fn new(data: &'a PasswordData) -> Self {
+        let mut groups = HashMap::new();
+        let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut roots = Vec::new();
+
+        for group in &data.groups {
+            groups.insert(group.id, group);
+        }
+        let order = data.groups.iter().map(|g| g.id).collect();
+        for group in &data.groups {
+            if group.pid == 0 || !groups.contains_key(&group.pid) {
+                roots.push(group.id);
+            } else {
+                children.entry(group.pid).or_default().push(group.id);
+            }
+        }
+
+        let mut record_counts = HashMap::new();
+        for record in &data.records {
+            *record_counts.entry(record.pid).or_insert(0) += 1;
+        }
+
+        Self {
+            order,
+            groups,
+            children,
+            record_counts,
+            roots,
+        }
+    }
+
+    /// Группа по ID
+    pub fn group(&self, id: u32) -> Option<&'a Group> {
+        self.groups.get(&id).copied()
+    }
+
+    /// Верхние группы дерева: корни и группы с несуществующим родителем
+    pub fn roots(&self) -> &[u32] {
+        &self.roots
+    }
+
+    /// ID дочерних групп в порядке следования в файле
+    pub fn children(&self, id: u32) -> &[u32] {
+        self.children.get(&id).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Предки группы от корня до непосредственного родителя
+    pub fn ancestors(&self, id: u32) -> Vec<&'a Group> {
+        let mut visited = HashSet::from([id]);
+        let mut ancestors = Vec::new();
+        let mut current = self.group(id);
+
+        while let Some(group) = current.and_then(|g| self.group(g.pid)) {
+            if !visited.insert(group.id) {
+                break;
+            }
+            ancestors.push(group);
+            current = Some(group);
+        }
+
+        ancestors.reverse();
+        ancestors
+    }
+
+    /// Путь (breadcrumb) из названий групп от корня до группы включительно
+    pub fn path(&self, id: u32) -> Vec<String> {
+        self.ancestors(id)
+            .into_iter()
+            .chain(self.group(id))
+            .map(|g| g.name.clone())
+            .collect()
+    }
+
+    /// ID группы и всех ее потомков в порядке обхода в глубину
+    pub fn subtree(&self, id: u32) -> Vec<u32> {
+        let mut visited = HashSet::new();
+        let mut result = Vec::new();
+        let mut stack = vec![id];
+
+        while let Some(current) = stack.pop() {
+            if !visited.insert(current) {
+                continue;
+            }
+            result.push(current);
+            stack.extend(self.children(current).iter().rev());
+        }
+
+        result
+    }
+
+    /// Количество записей непосредственно в группе
+    pub fn record_count(&self, id: u32) -> usize {
+        self.record_counts.get(&id).copied().unwrap_or(0)
+    }
+
+    /// Количество записей в группе и всех вложенных группах
+    pub fn total_record_count(&self, id: u32) -> usize {
+        self.subtree(id).into_iter().map(|g| self.record_count(g)).sum()
+    }
+
+    /// Строит вложенные узлы для всего дерева
+    ///
+    /// Группы, недостижимые из верхних групп (замкнутые в цикл по `pid`), не теряются:
+    /// они собираются под синтетическим узлом `Unreachable` с `id == 0` и `pid == 0`.
+    /// Исправить такую структуру можно командой `repair_vault`
+    pub fn to_nodes(&self) -> Vec<GroupNode> {
+        let mut visited = HashSet::new();
+        let mut nodes: Vec<GroupNode> = self
+            .roots
+            .iter()
+            .filter_map(|&id| self.build_node(id, Vec::new(), &mut visited))
+            .collect();
+
+        let unreachable_path = vec![UNREACHABLE_GROUP_NAME.to_string()];
+        let unreachable: Vec<GroupNode> = self
+            .order
+            .iter()
+            .filter_map(|&id| self.build_node(id, unreachable_path.clone(), &mut visited))
+            .collect();
+        if !unreachable.is_empty() {
+            nodes.push(GroupNode {
+                id: 0,
+                pid: 0,
+                name: UNREACHABLE_GROUP_NAME.to_string(),
+                path: unreachable_path,
+                record_count: 0,
+                total_record_count: unreachable.iter().map(|n| n.total_record_count).sum(),
+                children: unreachable,
+            });
+        }
+
+        nodes
+    }
+
+    /// Рекурсивно строит узел; `parent_path` - путь родителя
+    fn build_node(&self, id: u32, parent_path: Vec<String>, visited: &mut HashSet<u32>) -> Option<GroupNode> {
+        if !visited.insert(id) {
+            return None;
+        }
+        let group = self.group(id)?;
+
+        let mut path = parent_path;
+        path.push(group.name.clone());
+
+        let children: Vec<GroupNode> = self
+            .children(id)
+            .iter()
+            .filter_map(|&child| self.build_node(child, path.clone(), visited))
+            .collect();
+
+        let record_count = self.record_count(id);
+        let total_record_count = record_count + children.iter().map(|c| c.total_record_count).sum::<usize>();
+
+        Some(GroupNode {
+            id,
+            pid: group.pid,
+            name: group.name.clone(),
+            path,
+            record_count,
+            total_record_count,
+            children,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::kakadu_file_module::attachments::AttachmentStore;
+    use crate::modules::kakadu_file_module::Record;
+
+    fn record(id: u32, pid: u32) -> Record {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "pid": pid, "name": "Запись", "login": "", "password": "", "url": "",
+            "loginSymbol": "TAB", "passwordSymbol": "ENTER", "urlSymbol": "NONE"
+        }))
+        .unwrap()
+    }
+
+    fn vault(groups: &[(u32, u32, &str)], records: Vec<Record>) -> PasswordData {
+        PasswordData {
+            groups: groups.iter().map(|&(id, pid, name)| Group::new(id, pid, name.to_string())).collect(),
+            records,
+            next_id: 100,
+            attachments: AttachmentStore::default(),
+        }
+    }
+
+    fn ids(nodes: &[GroupNode]) -> Vec<u32> {
+        nodes.iter().map(|n| n.id).collect()
+    }
+
+    #[test]
+    fn nested_groups_have_paths_and_counts() {
+        let data = vault(
+            &[(1, 0, "Root"), (2, 1, "Работа"), (3, 2, "Почта")],
+            vec![record(10, 1), record(11, 3), record(12, 3)],
+        );
+        let nodes = GroupTree::new(&data).to_nodes();
+
+        assert_eq!(ids(&nodes), [1]);
+        assert_eq!(nodes[0].total_record_count, 3);
+        let mail = &nodes[0].children[0].children[0];
+        assert_eq!(mail.path, ["Root", "Работа", "Почта"]);
+        assert_eq!(mail.record_count, 2);
+    }
+
+    #[test]
+    fn cyclic_groups_go_under_synthetic_node() {
+        let data = vault(
+            &[(1, 0, "Root"), (2, 3, "A"), (3, 2, "B"), (4, 3, "C")],
+            vec![record(10, 2), record(11, 4)],
+        );
+        let nodes = GroupTree::new(&data).to_nodes();
+
+        assert_eq!(ids(&nodes), [1, 0]);
+        let unreachable = &nodes[1];
+        assert_eq!(unreachable.name, UNREACHABLE_GROUP_NAME);
+        assert_eq!(unreachable.total_record_count, 2);
+        assert_eq!(ids(&unreachable.children), [2]);
+
+        let a = &unreachable.children[0];
+        assert_eq!(ids(&a.children), [3]);
+        assert_eq!(ids(&a.children[0].children), [4]);
+        assert_eq!(a.children[0].children[0].path, [UNREACHABLE_GROUP_NAME, "A", "B", "C"]);
+    }
+
+    #[test]
+    fn orphan_groups_stay_top_level() {
+        let data = vault(&[(1, 0, "Root"), (2, 42, "Потерянная")], vec![]);
+        let tree = GroupTree::new(&data);
+
+        assert_eq!(tree.roots(), [1, 2]);
+        assert_eq!(ids(&tree.to_nodes()), [1, 2]);
+    }
+
+    #[test]
+    fn traversals_survive_cycles() {
+        let data = vault(&[(1, 0, "Root"), (2, 3, "A"), (3, 2, "B")], vec![]);
+        let tree = GroupTree::new(&data);
+
+        assert_eq!(tree.subtree(2), [2, 3]);
+        assert_eq!(tree.path(2), ["B", "A"]);
+    }
+}
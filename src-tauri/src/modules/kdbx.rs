@@ -0,0 +1,581 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::modules::kakadu_file_module::{KakaduError, PasswordData};
+
+mod crypto;
+mod format;
+mod mapping;
+mod xml;
+
+pub use mapping::KdbxImportSummary;
+
+use format::KdfSettings;
+
+/// Количество раундов AES-KDF при экспорте
+const AES_KDF_ROUNDS: u64 = 2_000_000;
+
+/// Параметры Argon2 при экспорте: те же затраты, что у KDF по умолчанию для файлов .kkd
+const ARGON2_MEMORY: u64 = 64 * 1024 * 1024; // Объем памяти в байтах
+const ARGON2_ITERATIONS: u64 = 3;             // Количество итераций
+const ARGON2_PARALLELISM: u32 = 4;            // Степень параллелизма
+
+/// Шифр данных файла KDBX
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum KdbxCipher {
+    Aes256,
+    #[default]
+    ChaCha20,
+}
+
+/// Функция получения ключа KDBX
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum KdbxKdf {
+    Argon2d,
+    #[default]
+    Argon2id,
+    AesKdf,
+}
+
+/// Параметры экспорта в KDBX
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct KdbxOptions {
+    pub cipher: KdbxCipher, // Шифр данных
+    pub kdf: KdbxKdf,       // Функция получения ключа
+}
+
+/// Составной ключ KeePass: SHA-256 от SHA-256 пароля и ключа из файла-ключа
+pub struct KdbxKey(Zeroizing<[u8; 32]>);
+
+impl KdbxKey {
+    /// Собирает составной ключ из пароля и/или содержимого файла-ключа
+    ///
+    /// # Ошибки
+    /// `Crypto`, если не задан ни пароль, ни файл-ключ; `Corrupted` для XML файла-ключа
+    /// с несовпадающим хешем
+    pub fn new(password: Option<&str>, key_file: Option<&[u8]>) -> Result<Self, KakaduError> {
+        if password.is_none() && key_file.is_none() {
+            return Err(KakaduError::Crypto("не задан ни пароль, ни файл-ключ".to_string()));
+        }
+
+        let mut hasher = Sha256::new();
+        if let Some(password) = password {
+            hasher.update(Sha256::digest(password.as_bytes()));
+        }
+        if let Some(content) = key_file {
+            hasher.update(key_file_key(content)?.as_slice());
+        }
+
+        Ok(Self(Zeroizing::new(hasher.finalize().into())))
+    }
+}
+
+/// Ключ из файла-ключа KeePass
+///
+/// # Поддерживаемые форматы
+/// * XML версии 1.0 (ключ в Base64) и 2.0 (ключ в hex с контрольным хешем)
+/// * 32 байта - ключ как есть
+/// * 64 hex-символа - ключ в hex
+/// * любой другой файл - SHA-256 от содержимого
+fn key_file_key(content: &[u8]) -> Result<Zeroizing<[u8; 32]>, KakaduError> {
+    if let Some(key) = xml_key_file(content)? {
+        return Ok(key);
+    }
+    if let Ok(key) = <[u8; 32]>::try_from(content) {
+        return Ok(Zeroizing::new(key));
+    }
+    if content.len() == 64 {
+        if let Some(key) = decode_hex(content) {
+            return Ok(key);
+        }
+    }
+    Ok(Zeroizing::new(Sha256::digest(content).into()))
+}
+
+/// Разбирает XML файл-ключ; `None`, если файл не в этом формате
+fn xml_key_file(content: &[u8]) -> Result<Option<Zeroizing<[u8; 32]>>, KakaduError> {
+    let Ok(root) = xml::parse(content, None) else {
+        return Ok(None);
+    };
+    let Some(data) = root.child("Key").and_then(|k| k.child("Data")).filter(|_| root.name == "KeyFile") else {
+        return Ok(None);
+    };
+    let version = root.child("Meta").map(|m| m.child_text("Version")).unwrap_or_default();
+
+    if version.starts_with("2.") {
+        let hex: Zeroizing<Vec<u8>> = Zeroizing::new(data.text.bytes().filter(|b| !b.is_ascii_whitespace()).collect());
+        let key = decode_hex(&hex).ok_or_else(|| KakaduError::Corrupted("некорректный ключ в файле-ключе".to_string()))?;
+
+        // Контрольный хеш - первые 4 байта SHA-256 ключа в hex
+        if let Some(hash) = data.attribute("Hash") {
+            let expected: String = Sha256::digest(key.as_slice())[..4].iter().map(|b| format!("{:02X}", b)).collect();
+            if !hash.eq_ignore_ascii_case(&expected) {
+                return Err(KakaduError::Corrupted("не совпадает хеш файла-ключа".to_string()));
+            }
+        }
+        return Ok(Some(key));
+    }
+
+    let key = Zeroizing::new(
+        STANDARD
+            .decode(data.text.trim())
+            .map_err(|_| KakaduError::Corrupted("некорректный ключ в файле-ключе".to_string()))?,
+    );
+    Ok(Some(Zeroizing::new(
+        key.as_slice().try_into().unwrap_or_else(|_| Sha256::digest(key.as_slice()).into()),
+    )))
+}
+
+/// Декодирует 32-байтный ключ из 64 hex-символов
+fn decode_hex(hex: &[u8]) -> Option<Zeroizing<[u8; 32]>> {
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut key = Zeroizing::new([0u8; 32]);
+    for (byte, pair) in key.iter_mut().zip(hex.chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(key)
+}
+
+/// Импортирует базу KDBX 4 в хранилище
+///
+/// # Аргументы
+/// * `data` - хранилище, в которое добавляются группы и записи
+/// * `file` - содержимое файла .kdbx
+/// * `key` - составной ключ
+/// * `group_id` - группа, в которую попадает содержимое корневой группы KeePass;
+///   `None` - новая корневая группа с именем корня KeePass
+///
+/// # Поведение
+/// Группам и записям выдаются новые ID. Корзина KeePass не импортируется.
+/// При любой ошибке хранилище не изменяется
+///
+/// # Ошибки
+/// Ошибки чтения файла (`WrongPassword`, `UnsupportedVersion`, `Corrupted`, ...),
+/// а также превышение лимита размера вложений
+pub fn import_kdbx(data: &mut PasswordData, file: &[u8], key: &KdbxKey, group_id: Option<u32>) -> Result<KdbxImportSummary, KakaduError> {
+    let mut payload = format::read(file, &key.0)?;
+    let document = xml::parse(&payload.xml, Some(&mut payload.stream))?;
+
+    let mut imported = data.clone();
+    let summary = mapping::import(&mut imported, &document, &payload.binaries, group_id)?;
+    *data = imported;
+
+    Ok(summary)
+}
+
+/// Экспортирует хранилище в файл KDBX 4.0
+///
+/// # Возвращает
+/// Содержимое файла .kdbx
+pub fn export_kdbx(data: &PasswordData, key: &KdbxKey, options: KdbxOptions) -> Result<Vec<u8>, KakaduError> {
+    let mut salt = vec![0u8; 32];
+    rand::thread_rng().fill_bytes(&mut salt);
+
+    let argon2 = |algorithm| KdfSettings::Argon2 {
+        algorithm,
+        version: argon2::Version::V0x13,
+        salt: salt.clone(),
+        memory: ARGON2_MEMORY,
+        iterations: ARGON2_ITERATIONS,
+        parallelism: ARGON2_PARALLELISM,
+    };
+    let kdf = match options.kdf {
+        KdbxKdf::Argon2d => argon2(argon2::Algorithm::Argon2d),
+        KdbxKdf::Argon2id => argon2(argon2::Algorithm::Argon2id),
+        KdbxKdf::AesKdf => KdfSettings::Aes {
+            seed: salt.as_slice().try_into().expect("соль длиной 32 байта"),
+            rounds: AES_KDF_ROUNDS,
+        },
+    };
+
+    write_kdbx(data, key, options.cipher, &kdf)
+}
+
+/// Записывает хранилище в файл KDBX 4.0 с заданными параметрами KDF
+fn write_kdbx(data: &PasswordData, key: &KdbxKey, cipher: KdbxCipher, kdf: &KdfSettings) -> Result<Vec<u8>, KakaduError> {
+    let mut stream_key = Zeroizing::new([0u8; 64]);
+    rand::thread_rng().fill_bytes(stream_key.as_mut_slice());
+
+    let (xml, binaries) = mapping::export(data, format::inner_stream(stream_key.as_slice()));
+    format::write(&xml, &binaries, &stream_key, &key.0, cipher, kdf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::kakadu_file_module::attachments::{content_id, Attachment, AttachmentStore};
+    use crate::modules::kakadu_file_module::{Group, Record};
+    use crate::modules::totp::{OtpConfig, OtpKind};
+    use argon2::{Algorithm, Version};
+
+    const SAMPLE_PASSWORD: &str = "kakadu-sample";
+
+    /// Дешевые параметры KDF: тестам важен формат, а не затраты
+    fn cheap_argon2(algorithm: Algorithm) -> KdfSettings {
+        KdfSettings::Argon2 {
+            algorithm,
+            version: Version::V0x13,
+            salt: vec![7u8; 32],
+            memory: 64 * 1024,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    fn key() -> KdbxKey {
+        KdbxKey::new(Some("пароль"), None).unwrap()
+    }
+
+    /// Хранилище со всеми переносимыми в KDBX данными:
+    /// группа с подгруппой, OTP, дополнительные поля, метки, история и вложение
+    fn sample_vault() -> PasswordData {
+        let content = b"attachment content".to_vec();
+        let mut attachments = AttachmentStore::default();
+        attachments.insert(Zeroizing::new(content.clone()));
+
+        let mail: Record = serde_json::from_value(serde_json::json!({
+            "id": 3, "pid": 2, "name": "Почта", "login": "alice", "password": "текущий", "url": "https://mail.example.com",
+            "loginSymbol": "ENTER", "passwordSymbol": "TAB", "urlSymbol": "NONE",
+            "createdAt": 1_600_000_000u64, "modifiedAt": 1_700_000_000u64,
+            "otp": {
+                "type": "hotp", "secret": "JBSWY3DPEHPK3PXP", "algorithm": "SHA256", "digits": 8, "counter": 7,
+                "issuer": "Example", "label": "Example:alice"
+            },
+            "customFields": [
+                { "name": "PIN", "value": "1234", "hidden": true },
+                { "name": "Вопрос", "value": "Ответ", "hidden": false },
+                { "name": "Password", "value": "конфликт имени", "hidden": false }
+            ],
+            "notes": "строка 1\nстрока 2",
+            "tags": ["личное", "почта"],
+            "attachments": [{ "id": content_id(&content), "name": "note.txt", "size": content.len(), "addedAt": 1 }],
+            "history": [
+                { "password": "старый-2", "changedAt": 1_650_000_000u64 },
+                { "password": "старый-1", "changedAt": 1_620_000_000u64 }
+            ]
+        }))
+        .unwrap();
+        let bank: Record = serde_json::from_value(serde_json::json!({
+            "id": 4, "pid": 1, "name": "Банк", "login": "", "password": "s3cr3t", "url": "",
+            "loginSymbol": "TAB", "passwordSymbol": "ENTER", "urlSymbol": "NONE"
+        }))
+        .unwrap();
+
+        PasswordData {
+            groups: vec![Group::new(1, 0, "Личное".to_string()), Group::new(2, 1, "Работа".to_string())],
+            records: vec![mail, bank],
+            next_id: 5,
+            attachments,
+        }
+    }
+
+    fn empty_vault() -> PasswordData {
+        PasswordData {
+            groups: Vec::new(),
+            records: Vec::new(),
+            next_id: 1,
+            attachments: AttachmentStore::default(),
+        }
+    }
+
+    fn group<'a>(data: &'a PasswordData, name: &str) -> &'a Group {
+        data.groups.iter().find(|g| g.name == name).unwrap()
+    }
+
+    fn record<'a>(data: &'a PasswordData, name: &str) -> &'a Record {
+        data.records.iter().find(|r| r.name == name).unwrap()
+    }
+
+    /// Проверяет, что импортированное хранилище совпадает с `sample_vault` с точностью до ID
+    fn assert_round_trip(imported: &PasswordData) {
+        let original = sample_vault();
+        assert_eq!(imported.groups.len(), 2);
+        let root = group(imported, "Личное");
+        let work = group(imported, "Работа");
+        assert_eq!((root.pid, work.pid), (0, root.id));
+
+        let bank = record(imported, "Банк");
+        assert_eq!(bank.pid, root.id);
+        assert_eq!(bank.password.expose(), "s3cr3t");
+
+        let (expected, mail) = (record(&original, "Почта"), record(imported, "Почта"));
+        assert_eq!(mail.pid, work.id);
+        assert_eq!((mail.login.as_str(), mail.url.as_str(), mail.notes.as_str()), ("alice", "https://mail.example.com", "строка 1\nстрока 2"));
+        assert_eq!(mail.password.expose(), "текущий");
+        let symbols = |r: &Record| serde_json::to_value((&r.login_symbol, &r.password_symbol, &r.url_symbol)).unwrap();
+        assert_eq!(symbols(mail), symbols(expected));
+        assert_eq!((mail.times.created_at, mail.times.modified_at), (Some(1_600_000_000), Some(1_700_000_000)));
+        assert_eq!(mail.otp, expected.otp);
+        assert_eq!(mail.tags, expected.tags);
+
+        let fields: Vec<_> = mail.custom_fields.iter().map(|f| (f.name.as_str(), f.value.expose(), f.hidden)).collect();
+        assert_eq!(fields, [("PIN", "1234", true), ("Вопрос", "Ответ", false), ("Password (2)", "конфликт имени", false)]);
+
+        let history: Vec<_> = mail.history.iter().map(|h| (h.password.expose(), h.changed_at)).collect();
+        assert_eq!(history, [("старый-2", 1_650_000_000), ("старый-1", 1_620_000_000)]);
+
+        let [attachment]: &[Attachment; 1] = mail.attachments.as_slice().try_into().unwrap();
+        assert_eq!((attachment.name.as_str(), attachment.size), ("note.txt", 18));
+        assert_eq!(imported.attachments.get(&attachment.id), Some(&b"attachment content"[..]));
+    }
+
+    #[test]
+    fn round_trip_keeps_vault_content_with_every_kdf_and_cipher() {
+        let kdfs = [
+            cheap_argon2(Algorithm::Argon2d),
+            cheap_argon2(Algorithm::Argon2id),
+            KdfSettings::Aes { seed: [7u8; 32], rounds: 100 },
+        ];
+        for kdf in &kdfs {
+            for cipher in [KdbxCipher::Aes256, KdbxCipher::ChaCha20] {
+                let file = write_kdbx(&sample_vault(), &key(), cipher, kdf).unwrap();
+
+                let mut imported = empty_vault();
+                let summary = import_kdbx(&mut imported, &file, &key(), None).unwrap();
+                assert_eq!((summary.groups, summary.records, summary.attachments), (2, 2, 1), "{:?} {:?}", kdf, cipher);
+                assert_round_trip(&imported);
+            }
+        }
+    }
+
+    #[test]
+    fn export_with_default_options_round_trips() {
+        let file = export_kdbx(&sample_vault(), &key(), KdbxOptions { kdf: KdbxKdf::AesKdf, ..Default::default() }).unwrap();
+        let mut imported = empty_vault();
+        import_kdbx(&mut imported, &file, &key(), None).unwrap();
+        assert_round_trip(&imported);
+    }
+
+    #[test]
+    fn import_into_existing_group_keeps_vault_on_wrong_password() {
+        let file = write_kdbx(&sample_vault(), &key(), KdbxCipher::ChaCha20, &cheap_argon2(Algorithm::Argon2id)).unwrap();
+        let mut data = empty_vault();
+        data.groups.push(Group::new(1, 0, "Импорт".to_string()));
+        data.next_id = 2;
+
+        let wrong = KdbxKey::new(Some("другой"), None).unwrap();
+        assert!(matches!(import_kdbx(&mut data, &file, &wrong, Some(1)), Err(KakaduError::WrongPassword)));
+        assert_eq!((data.groups.len(), data.records.len()), (1, 0));
+
+        import_kdbx(&mut data, &file, &key(), Some(1)).unwrap();
+        let work = group(&data, "Работа");
+        assert_eq!(work.pid, 1);
+        assert_eq!(record(&data, "Банк").pid, 1);
+        assert!(data.groups.iter().all(|g| g.name != "Личное"));
+    }
+
+    /// Образцы из `tests/fixtures/kdbx`, собранные скриптом `generate.py`
+    /// в стиле KeePass 2.x и KeePassXC (см. README там же)
+    fn sample(name: &str) -> Vec<u8> {
+        let path = format!("{}/tests/fixtures/kdbx/{}.kdbx", env!("CARGO_MANIFEST_DIR"), name);
+        std::fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+    }
+
+    fn import_sample(name: &str) -> PasswordData {
+        let mut data = empty_vault();
+        let key = KdbxKey::new(Some(SAMPLE_PASSWORD), None).unwrap();
+        let summary = import_kdbx(&mut data, &sample(name), &key, None).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        assert_eq!((summary.groups, summary.records, summary.attachments), (2, 2, 1), "{}", name);
+        data
+    }
+
+    /// Содержимое образцов, общее для стилей KeePass 2.x и KeePassXC
+    fn assert_sample(data: &PasswordData, name: &str) {
+        let root = group(data, "Sample");
+        let banking = group(data, "Banking");
+        assert_eq!((root.pid, banking.pid), (0, root.id), "{}", name);
+        // Корзина и удаленная запись не импортируются
+        assert!(data.records.iter().all(|r| r.name != "Deleted"), "{}", name);
+
+        let bank = record(data, "Bank");
+        assert_eq!((bank.pid, bank.login.as_str(), bank.password.expose()), (banking.id, "alice.b", "s3cr3t"));
+
+        let mail = record(data, "Mail");
+        assert_eq!(mail.pid, root.id);
+        assert_eq!((mail.login.as_str(), mail.password.expose()), ("alice", "correct horse"), "{}", name);
+        assert_eq!((mail.url.as_str(), mail.notes.as_str()), ("https://mail.example.com", "Primary mailbox"));
+        assert_eq!(mail.tags, ["mail", "work"].map(String::from).into(), "{}", name);
+
+        let history: Vec<_> = mail.history.iter().map(|h| h.password.expose()).collect();
+        assert_eq!(history, ["old-2", "old-1"], "{}", name);
+
+        let [attachment]: &[Attachment; 1] = mail.attachments.as_slice().try_into().unwrap();
+        assert_eq!(attachment.name, "note.txt");
+        assert_eq!(data.attachments.get(&attachment.id), Some(&b"Kakadu sample attachment\n"[..]));
+    }
+
+    #[test]
+    fn generated_keepass2_style_samples_import_with_every_kdf_and_cipher() {
+        for kdf in ["aeskdf", "argon2d", "argon2id"] {
+            for cipher in ["aes", "chacha20"] {
+                let name = format!("generated-keepass2-{}-{}", kdf, cipher);
+                let data = import_sample(&name);
+                assert_sample(&data, &name);
+
+                // OTP KeePass 2.x хранится в своих строках и переносится как скрытое поле
+                let mail = record(&data, "Mail");
+                assert_eq!(mail.otp, None);
+                let fields: Vec<_> = mail.custom_fields.iter().map(|f| (f.name.as_str(), f.value.expose(), f.hidden)).collect();
+                assert_eq!(
+                    fields,
+                    [("PIN", "1234", true), ("Recovery", "blue-falcon", false), ("TimeOtp-Secret-Base32", "JBSWY3DPEHPK3PXP", true)],
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn generated_keepassxc_style_samples_import_with_every_kdf_and_cipher() {
+        for kdf in ["aeskdf", "argon2d", "argon2id"] {
+            for cipher in ["aes", "chacha20"] {
+                let name = format!("generated-keepassxc-{}-{}", kdf, cipher);
+                let data = import_sample(&name);
+                assert_sample(&data, &name);
+
+                let mail = record(&data, "Mail");
+                let otp: &OtpConfig = mail.otp.as_ref().unwrap();
+                assert_eq!((otp.kind, otp.secret.expose(), otp.digits, otp.period), (OtpKind::Totp, "JBSWY3DPEHPK3PXP", 6, 30));
+                assert_eq!(otp.issuer.as_deref(), Some("Example"));
+                let fields: Vec<_> = mail.custom_fields.iter().map(|f| f.name.as_str()).collect();
+                assert_eq!(fields, ["PIN", "Recovery"], "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_samples_reject_wrong_password() {
+        let key = KdbxKey::new(Some("wrong"), None).unwrap();
+        for name in ["generated-keepass2-aeskdf-aes", "generated-keepassxc-aeskdf-chacha20"] {
+            let mut data = empty_vault();
+            assert!(matches!(import_kdbx(&mut data, &sample(name), &key, None), Err(KakaduError::WrongPassword)), "{}", name);
+            assert!(data.groups.is_empty() && data.records.is_empty());
+        }
+    }
+
+    /// Ключ, который тесты записывают в файлы-ключи разных форматов
+    const FILE_KEY: [u8; 32] = [
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff,
+        0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54, 0x32, 0x10,
+    ];
+
+    fn upper_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02X}", b)).collect()
+    }
+
+    /// Ожидаемый составной ключ: SHA-256 от частей по порядку
+    fn composite(parts: &[&[u8]]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().into()
+    }
+
+    fn key_from_file(content: &[u8]) -> [u8; 32] {
+        *KdbxKey::new(None, Some(content)).unwrap().0
+    }
+
+    /// XML файл-ключ версии 2.0 в том виде, как его сохраняет KeePass 2.x
+    fn xml_v2_key_file(hash: &str) -> String {
+        let hex = upper_hex(&FILE_KEY);
+        let blocks: Vec<&str> = (0..hex.len()).step_by(8).map(|i| &hex[i..i + 8]).collect();
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<KeyFile>
+    <Meta>
+        <Version>2.0</Version>
+    </Meta>
+    <Key>
+        <Data Hash="{}">
+            {}
+            {}
+        </Data>
+    </Key>
+</KeyFile>
+"#,
+            hash,
+            blocks[..4].join(" "),
+            blocks[4..].join(" ")
+        )
+    }
+
+    #[test]
+    fn xml_v1_key_file_is_base64() {
+        let file = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<KeyFile>
+    <Meta>
+        <Version>1.00</Version>
+    </Meta>
+    <Key>
+        <Data>{}</Data>
+    </Key>
+</KeyFile>
+"#,
+            STANDARD.encode(FILE_KEY)
+        );
+        assert_eq!(key_from_file(file.as_bytes()), composite(&[&FILE_KEY]));
+    }
+
+    #[test]
+    fn xml_v2_key_file_is_hex_with_hash() {
+        let hash = upper_hex(&Sha256::digest(FILE_KEY)[..4]);
+        assert_eq!(key_from_file(xml_v2_key_file(&hash).as_bytes()), composite(&[&FILE_KEY]));
+        assert_eq!(key_from_file(xml_v2_key_file(&hash.to_lowercase()).as_bytes()), composite(&[&FILE_KEY]));
+
+        let wrong = if hash == "00000000" { "00000001" } else { "00000000" };
+        let error = KdbxKey::new(None, Some(xml_v2_key_file(wrong).as_bytes())).err().unwrap();
+        assert!(matches!(error, KakaduError::Corrupted(_)));
+    }
+
+    #[test]
+    fn binary_and_hex_key_files() {
+        assert_eq!(key_from_file(&FILE_KEY), composite(&[&FILE_KEY]));
+        assert_eq!(key_from_file(upper_hex(&FILE_KEY).as_bytes()), composite(&[&FILE_KEY]));
+        assert_eq!(key_from_file(upper_hex(&FILE_KEY).to_lowercase().as_bytes()), composite(&[&FILE_KEY]));
+    }
+
+    #[test]
+    fn other_key_files_are_hashed() {
+        for content in [&b"any file at all"[..], &[b'z'; 64], &[7u8; 33], b"<KeyFile><Meta/></KeyFile>"] {
+            let hashed: [u8; 32] = Sha256::digest(content).into();
+            assert_eq!(key_from_file(content), composite(&[&hashed]), "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn password_and_key_file_are_combined() {
+        let combined = KdbxKey::new(Some("пароль"), Some(&FILE_KEY)).unwrap();
+        assert_eq!(*combined.0, composite(&[&Sha256::digest("пароль".as_bytes()), &FILE_KEY]));
+        assert_eq!(*key().0, composite(&[&Sha256::digest("пароль".as_bytes())]));
+
+        assert!(matches!(KdbxKey::new(None, None), Err(KakaduError::Crypto(_))));
+    }
+
+    #[test]
+    fn round_trip_with_key_file_only() {
+        let file_key = KdbxKey::new(None, Some(xml_v2_key_file(&upper_hex(&Sha256::digest(FILE_KEY)[..4])).as_bytes())).unwrap();
+        let file = write_kdbx(&sample_vault(), &file_key, KdbxCipher::Aes256, &cheap_argon2(Algorithm::Argon2id)).unwrap();
+
+        // Тот же ключ в другом формате файла-ключа открывает базу
+        let mut imported = empty_vault();
+        import_kdbx(&mut imported, &file, &KdbxKey::new(None, Some(&FILE_KEY)).unwrap(), None).unwrap();
+        assert_round_trip(&imported);
+
+        for wrong in [key(), KdbxKey::new(Some("пароль"), Some(&FILE_KEY)).unwrap()] {
+            let mut data = empty_vault();
+            assert!(matches!(import_kdbx(&mut data, &file, &wrong, None), Err(KakaduError::WrongPassword)));
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{generic_array::GenericArray, BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit};
+use aes::Aes256;
+use argon2::{Algorithm, Argon2, Params, Version};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+pub use chacha20::ChaCha20;
+
+use crate::modules::kakadu_file_module::KakaduError;
+
+/// Размер блока AES в байтах
+const AES_BLOCK_LEN: usize = 16;
+
+/// Потоковый шифр ChaCha20 (RFC 8439): 256-битный ключ, 96-битный nonce
+///
+/// Используется KDBX 4 и для шифрования данных, и для защиты значений внутри XML
+pub fn chacha20(key: &[u8; 32], nonce: &[u8; 12]) -> ChaCha20 {
+    ChaCha20::new(key.into(), nonce.into())
+}
+
+/// Шифрует данные AES-256-CBC с выравниванием PKCS7
+pub fn aes_cbc_encrypt(key: &[u8; 32], iv: &[u8; AES_BLOCK_LEN], plaintext: &[u8]) -> Vec<u8> {
+    cbc::Encryptor::<Aes256>::new(key.into(), iv.into()).encrypt_padded_vec_mut::<Pkcs7>(plaintext)
+}
+
+/// Расшифровывает данные AES-256-CBC и снимает выравнивание PKCS7
+///
+/// # Ошибки
+/// `Truncated`, если длина не кратна блоку, и `Corrupted` при некорректном выравнивании
+pub fn aes_cbc_decrypt(key: &[u8; 32], iv: &[u8; AES_BLOCK_LEN], ciphertext: &[u8]) -> Result<Zeroizing<Vec<u8>>, KakaduError> {
+    if ciphertext.is_empty() || !ciphertext.len().is_multiple_of(AES_BLOCK_LEN) {
+        return Err(KakaduError::Truncated);
+    }
+
+    let mut out = Zeroizing::new(ciphertext.to_vec());
+    let len = cbc::Decryptor::<Aes256>::new(key.into(), iv.into())
+        .decrypt_padded_mut::<Pkcs7>(&mut out)
+        .map_err(|_| KakaduError::Corrupted("некорректное выравнивание AES-CBC".to_string()))?
+        .len();
+    out.truncate(len);
+
+    Ok(out)
+}
+
+/// AES-KDF KeePass: `rounds` раз шифрует составной ключ AES-256-ECB на ключе `seed`,
+/// затем берет SHA-256 от результата
+pub fn aes_kdf(composite_key: &[u8; 32], seed: &[u8; 32], rounds: u64) -> Zeroizing<[u8; 32]> {
+    let cipher = Aes256::new(GenericArray::from_slice(seed));
+    let mut key = Zeroizing::new(*composite_key);
+
+    for chunk in key.chunks_exact_mut(AES_BLOCK_LEN) {
+        let block = GenericArray::from_mut_slice(chunk);
+        for _ in 0..rounds {
+            cipher.encrypt_block(block);
+        }
+    }
+
+    Zeroizing::new(Sha256::digest(key.as_slice()).into())
+}
+
+/// Argon2d или Argon2id от составного ключа
+pub fn argon2_kdf(
+    composite_key: &[u8; 32],
+    algorithm: Algorithm,
+    version: Version,
+    salt: &[u8],
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+) -> Result<Zeroizing<[u8; 32]>, KakaduError> {
+    let params = Params::new(memory_kib, iterations, parallelism, Some(32))
+        .map_err(|e| KakaduError::Corrupted(format!("некорректные параметры Argon2: {}", e)))?;
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(algorithm, version, params)
+        .hash_password_into(composite_key, salt, key.as_mut_slice())
+        .map_err(|e| KakaduError::Crypto(e.to_string()))?;
+
+    Ok(key)
+}
@@ -0,0 +1,578 @@
+use argon2::{Algorithm, Version};
+use chacha20::cipher::StreamCipher;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use zeroize::Zeroizing;
+
+use crate::modules::kakadu_file_module::KakaduError;
+
+use super::crypto::{self, ChaCha20};
+use super::KdbxCipher;
+
+/// Сигнатуры файла KeePass 2.x
+const SIGNATURE_1: u32 = 0x9AA2_D903;
+const SIGNATURE_2: u32 = 0xB54B_FB67;
+
+/// Поддерживаемая старшая версия формата и версия, записываемая при экспорте (4.0)
+const VERSION_MAJOR: u32 = 4;
+const WRITE_VERSION: u32 = 0x0004_0000;
+
+/// Поля внешнего заголовка
+const FIELD_END: u8 = 0;
+const FIELD_CIPHER_ID: u8 = 2;
+const FIELD_COMPRESSION: u8 = 3;
+const FIELD_MASTER_SEED: u8 = 4;
+const FIELD_ENCRYPTION_IV: u8 = 7;
+const FIELD_KDF_PARAMETERS: u8 = 11;
+
+/// Поля внутреннего заголовка
+const INNER_FIELD_END: u8 = 0;
+const INNER_FIELD_STREAM_ID: u8 = 1;
+const INNER_FIELD_STREAM_KEY: u8 = 2;
+const INNER_FIELD_BINARY: u8 = 3;
+
+/// Идентификатор потока защиты значений ChaCha20 во внутреннем заголовке
+const INNER_STREAM_CHACHA20: u32 = 3;
+
+/// UUID шифров и KDF
+const CIPHER_AES256: [u8; 16] = hex16("31c1f2e6bf714350be5805216afc5aff");
+const CIPHER_CHACHA20: [u8; 16] = hex16("d6038a2b8b6f4cb5a524339a31dbb59a");
+const KDF_AES: [u8; 16] = hex16("c9d9f39a628a4460bf740d08c18a4fea");
+/// AES-KDF в файлах KDBX 4 от KeePassXC: те же параметры под другим UUID
+const KDF_AES_KDBX4: [u8; 16] = hex16("7c02bb8279a74ac0927d114a00648238");
+const KDF_ARGON2D: [u8; 16] = hex16("ef636ddf8c29444b91f7a9a403e30a0c");
+const KDF_ARGON2ID: [u8; 16] = hex16("9e298b1956db4773b23dfc3ec6f0a1e6");
+
+/// Верхние границы параметров KDF при чтении,
+/// чтобы поврежденный или враждебный файл не мог исчерпать память или время
+const MAX_ARGON2_MEMORY: u64 = 1024 * 1024 * 1024;
+const MAX_ARGON2_ITERATIONS: u64 = 1000;
+const MAX_AES_KDF_ROUNDS: u64 = 100_000_000;
+
+/// Размер блока HMAC-потока при записи (как в KeePass)
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Версия формата VariantDictionary
+const VARIANT_DICTIONARY_VERSION: u16 = 0x0100;
+
+/// Значение словаря параметров KDF (VariantDictionary)
+#[derive(Debug, Clone)]
+enum Variant {
+    U32(u32),
+    U64(u64),
+    Bytes(Vec<u8>),
+    /// Bool, Int32, Int64 и String: в параметрах KDF KeePass не используются
+    Other,
+}
+
+/// Параметры KDF из заголовка KDBX 4
+#[derive(Debug, Clone)]
+pub enum KdfSettings {
+    /// AES-KDF: многократное шифрование составного ключа AES-256
+    Aes { seed: [u8; 32], rounds: u64 },
+    /// Argon2d или Argon2id
+    Argon2 {
+        algorithm: Algorithm,
+        version: Version,
+        salt: Vec<u8>,
+        memory: u64,      // Объем памяти в байтах
+        iterations: u64,  // Количество итераций
+        parallelism: u32, // Степень параллелизма
+    },
+}
+
+/// Расшифрованное содержимое файла KDBX 4
+pub struct Payload {
+    pub xml: Zeroizing<Vec<u8>>,           // XML базы данных
+    pub binaries: Vec<Zeroizing<Vec<u8>>>, // Вложения из внутреннего заголовка
+    pub stream: ChaCha20,                  // Поток для расшифровки защищенных значений XML
+}
+
+impl KdfSettings {
+    /// Получает ключ из составного ключа
+    fn transform(&self, composite_key: &[u8; 32]) -> Result<Zeroizing<[u8; 32]>, KakaduError> {
+        match self {
+            KdfSettings::Aes { seed, rounds } => Ok(crypto::aes_kdf(composite_key, seed, *rounds)),
+            KdfSettings::Argon2 {
+                algorithm,
+                version,
+                salt,
+                memory,
+                iterations,
+                parallelism,
+            } => crypto::argon2_kdf(
+                composite_key,
+                *algorithm,
+                *version,
+                salt,
+                (*memory / 1024) as u32,
+                *iterations as u32,
+                *parallelism,
+            ),
+        }
+    }
+
+    /// Разбирает словарь параметров KDF
+    fn decode(data: &[u8]) -> Result<Self, KakaduError> {
+        let params = read_variant_dictionary(data)?;
+        let bytes = |name: &str| match params.get(name) {
+            Some(Variant::Bytes(b)) => Ok(b.clone()),
+            _ => Err(corrupted(&format!("отсутствует параметр KDF {}", name))),
+        };
+        let number = |name: &str| match params.get(name) {
+            Some(Variant::U64(v)) => Ok(*v),
+            Some(Variant::U32(v)) => Ok(u64::from(*v)),
+            _ => Err(corrupted(&format!("отсутствует параметр KDF {}", name))),
+        };
+
+        let uuid: [u8; 16] = bytes("$UUID")?
+            .try_into()
+            .map_err(|_| corrupted("некорректный идентификатор KDF"))?;
+
+        match uuid {
+            KDF_AES | KDF_AES_KDBX4 => {
+                let seed = bytes("S")?.try_into().map_err(|_| corrupted("некорректная соль AES-KDF"))?;
+                let rounds = number("R")?;
+                if rounds > MAX_AES_KDF_ROUNDS {
+                    return Err(corrupted("количество раундов AES-KDF превышает допустимое"));
+                }
+                Ok(KdfSettings::Aes { seed, rounds })
+            }
+            KDF_ARGON2D | KDF_ARGON2ID => {
+                if params.contains_key("K") || params.contains_key("A") {
+                    return Err(corrupted("секретный ключ и дополнительные данные Argon2 не поддерживаются"));
+                }
+                let version = match number("V")? {
+                    0x10 => Version::V0x10,
+                    0x13 => Version::V0x13,
+                    other => return Err(corrupted(&format!("неизвестная версия Argon2: {:#x}", other))),
+                };
+                let (memory, iterations) = (number("M")?, number("I")?);
+                let parallelism = number("P")?;
+                if memory > MAX_ARGON2_MEMORY || iterations > MAX_ARGON2_ITERATIONS || parallelism > u64::from(u32::MAX) {
+                    return Err(corrupted("параметры Argon2 превышают допустимые значения"));
+                }
+
+                Ok(KdfSettings::Argon2 {
+                    algorithm: if uuid == KDF_ARGON2D { Algorithm::Argon2d } else { Algorithm::Argon2id },
+                    version,
+                    salt: bytes("S")?,
+                    memory,
+                    iterations,
+                    parallelism: parallelism as u32,
+                })
+            }
+            _ => Err(corrupted("неподдерживаемая KDF")),
+        }
+    }
+
+    /// Кодирует параметры KDF в словарь для заголовка
+    fn encode(&self) -> Vec<u8> {
+        let params = match self {
+            KdfSettings::Aes { seed, rounds } => vec![
+                ("$UUID", Variant::Bytes(KDF_AES.to_vec())),
+                ("R", Variant::U64(*rounds)),
+                ("S", Variant::Bytes(seed.to_vec())),
+            ],
+            KdfSettings::Argon2 {
+                algorithm,
+                version,
+                salt,
+                memory,
+                iterations,
+                parallelism,
+            } => {
+                let uuid = if *algorithm == Algorithm::Argon2d { KDF_ARGON2D } else { KDF_ARGON2ID };
+                vec![
+                    ("$UUID", Variant::Bytes(uuid.to_vec())),
+                    ("S", Variant::Bytes(salt.clone())),
+                    ("P", Variant::U32(*parallelism)),
+                    ("M", Variant::U64(*memory)),
+                    ("I", Variant::U64(*iterations)),
+                    ("V", Variant::U32(*version as u32)),
+                ]
+            }
+        };
+        write_variant_dictionary(&params)
+    }
+}
+
+/// Поток защиты значений XML: ChaCha20 с ключом и nonce из SHA-512 от ключа потока
+pub fn inner_stream(stream_key: &[u8]) -> ChaCha20 {
+    let hash: Zeroizing<[u8; 64]> = Zeroizing::new(Sha512::digest(stream_key).into());
+    crypto::chacha20(
+        hash[..32].try_into().expect("SHA-512 длиннее 32 байт"),
+        hash[32..44].try_into().expect("SHA-512 длиннее 44 байт"),
+    )
+}
+
+/// Проверяет, начинаются ли данные с сигнатуры KeePass 2.x
+fn is_kdbx(data: &[u8]) -> bool {
+    data.len() >= 8
+        && u32::from_le_bytes(data[..4].try_into().unwrap()) == SIGNATURE_1
+        && u32::from_le_bytes(data[4..8].try_into().unwrap()) == SIGNATURE_2
+}
+
+/// Расшифровывает файл KDBX 4
+///
+/// # Порядок проверки
+/// 1. SHA-256 заголовка - целостность заголовка
+/// 2. HMAC-SHA256 заголовка - верность ключа (`WrongPassword`)
+/// 3. HMAC каждого блока данных - целостность данных
+///
+/// # Ошибки
+/// `UnsupportedVersion` для KDBX 3.x и старше 4.x, `WrongPassword`, `Truncated`, `Corrupted`
+pub fn read(data: &[u8], composite_key: &[u8; 32]) -> Result<Payload, KakaduError> {
+    if !is_kdbx(data) {
+        return Err(corrupted("отсутствует сигнатура KeePass"));
+    }
+    let mut input = &data[8..];
+    let version = u32::from_le_bytes(take_array(&mut input)?);
+    if version >> 16 != VERSION_MAJOR {
+        return Err(KakaduError::UnsupportedVersion((version >> 16) as u8));
+    }
+
+    // Внешний заголовок
+    let mut cipher_id = None;
+    let mut compressed = false;
+    let mut master_seed = None;
+    let mut iv = None;
+    let mut kdf = None;
+    loop {
+        let id = take_array::<1>(&mut input)?[0];
+        let len = u32::from_le_bytes(take_array(&mut input)?) as usize;
+        let value = take(&mut input, len)?;
+        match id {
+            FIELD_END => break,
+            FIELD_CIPHER_ID => cipher_id = Some(value),
+            FIELD_COMPRESSION => compressed = value.first().is_some_and(|&c| c != 0),
+            FIELD_MASTER_SEED => master_seed = Some(value),
+            FIELD_ENCRYPTION_IV => iv = Some(value),
+            FIELD_KDF_PARAMETERS => kdf = Some(KdfSettings::decode(value)?),
+            // Пользовательские данные и прочие поля не влияют на расшифровку
+            _ => {}
+        }
+    }
+    let header = &data[..data.len() - input.len()];
+
+    let cipher = match cipher_id {
+        Some(id) if id == CIPHER_AES256 => KdbxCipher::Aes256,
+        Some(id) if id == CIPHER_CHACHA20 => KdbxCipher::ChaCha20,
+        _ => return Err(corrupted("неподдерживаемый шифр")),
+    };
+    let master_seed = master_seed.ok_or_else(|| corrupted("отсутствует master seed"))?;
+    let iv = iv.ok_or_else(|| corrupted("отсутствует вектор инициализации"))?;
+    let kdf = kdf.ok_or_else(|| corrupted("отсутствуют параметры KDF"))?;
+
+    if take(&mut input, 32)? != Sha256::digest(header).as_slice() {
+        return Err(corrupted("не совпадает хеш заголовка"));
+    }
+
+    let (key, hmac_base) = derive_keys(composite_key, master_seed, &kdf)?;
+    if take(&mut input, 32)? != header_hmac(&hmac_base, header).as_slice() {
+        return Err(KakaduError::WrongPassword);
+    }
+
+    // Поток блоков с HMAC
+    let mut encrypted = Vec::with_capacity(input.len());
+    for index in 0u64.. {
+        let expected: [u8; 32] = take_array(&mut input)?;
+        let len_bytes: [u8; 4] = take_array(&mut input)?;
+        let block = take(&mut input, u32::from_le_bytes(len_bytes) as usize)?;
+
+        let mut message = Vec::with_capacity(4 + block.len());
+        message.extend_from_slice(&len_bytes);
+        message.extend_from_slice(block);
+        if block_hmac(&hmac_base, index, &message).as_slice() != expected {
+            return Err(corrupted("не совпадает HMAC блока данных"));
+        }
+        if block.is_empty() {
+            break;
+        }
+        encrypted.extend_from_slice(block);
+    }
+
+    let decrypted = match cipher {
+        KdbxCipher::Aes256 => {
+            let iv = iv.try_into().map_err(|_| corrupted("некорректный вектор инициализации"))?;
+            crypto::aes_cbc_decrypt(&key, iv, &encrypted)?
+        }
+        KdbxCipher::ChaCha20 => {
+            let iv = iv.try_into().map_err(|_| corrupted("некорректный вектор инициализации"))?;
+            let mut plain = Zeroizing::new(encrypted);
+            crypto::chacha20(&key, iv).apply_keystream(&mut plain);
+            plain
+        }
+    };
+
+    let plain = if compressed {
+        let mut out = Zeroizing::new(Vec::new());
+        GzDecoder::new(decrypted.as_slice())
+            .read_to_end(&mut out)
+            .map_err(|_| corrupted("ошибка распаковки данных"))?;
+        out
+    } else {
+        decrypted
+    };
+
+    // Внутренний заголовок
+    let mut input = plain.as_slice();
+    let mut stream_id = None;
+    let mut stream_key = None;
+    let mut binaries = Vec::new();
+    loop {
+        let id = take_array::<1>(&mut input)?[0];
+        let len = u32::from_le_bytes(take_array(&mut input)?) as usize;
+        let value = take(&mut input, len)?;
+        match id {
+            INNER_FIELD_END => break,
+            INNER_FIELD_STREAM_ID => {
+                stream_id = Some(u32::from_le_bytes(value.try_into().map_err(|_| corrupted("некорректный поток защиты"))?))
+            }
+            INNER_FIELD_STREAM_KEY => stream_key = Some(value),
+            // Первый байт вложения - флаги
+            INNER_FIELD_BINARY => binaries.push(Zeroizing::new(value.get(1..).unwrap_or_default().to_vec())),
+            _ => {}
+        }
+    }
+
+    if stream_id != Some(INNER_STREAM_CHACHA20) {
+        return Err(corrupted("неподдерживаемый поток защиты значений"));
+    }
+    let stream = inner_stream(stream_key.ok_or_else(|| corrupted("отсутствует ключ потока защиты"))?);
+
+    Ok(Payload {
+        xml: Zeroizing::new(input.to_vec()),
+        binaries,
+        stream,
+    })
+}
+
+/// Шифрует XML и вложения в файл KDBX 4.0 со сжатием gzip
+///
+/// # Аргументы
+/// * `xml` - XML базы, защищенные значения которого уже зашифрованы потоком от `stream_key`
+/// * `binaries` - вложения в порядке ссылок `Ref` из XML
+/// * `stream_key` - ключ потока защиты значений ChaCha20
+/// * `composite_key` - составной ключ из пароля и/или файла-ключа
+pub fn write(
+    xml: &[u8],
+    binaries: &[&[u8]],
+    stream_key: &[u8; 64],
+    composite_key: &[u8; 32],
+    cipher: KdbxCipher,
+    kdf: &KdfSettings,
+) -> Result<Vec<u8>, KakaduError> {
+    let mut rng = rand::thread_rng();
+    let mut master_seed = [0u8; 32];
+    rng.fill_bytes(&mut master_seed);
+    let (cipher_id, mut iv) = match cipher {
+        KdbxCipher::Aes256 => (CIPHER_AES256, vec![0u8; 16]),
+        KdbxCipher::ChaCha20 => (CIPHER_CHACHA20, vec![0u8; 12]),
+    };
+    rng.fill_bytes(&mut iv);
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&SIGNATURE_1.to_le_bytes());
+    header.extend_from_slice(&SIGNATURE_2.to_le_bytes());
+    header.extend_from_slice(&WRITE_VERSION.to_le_bytes());
+    push_field(&mut header, FIELD_CIPHER_ID, &cipher_id);
+    push_field(&mut header, FIELD_COMPRESSION, &1u32.to_le_bytes());
+    push_field(&mut header, FIELD_MASTER_SEED, &master_seed);
+    push_field(&mut header, FIELD_ENCRYPTION_IV, &iv);
+    push_field(&mut header, FIELD_KDF_PARAMETERS, &kdf.encode());
+    push_field(&mut header, FIELD_END, b"\r\n\r\n");
+
+    let (key, hmac_base) = derive_keys(composite_key, &master_seed, kdf)?;
+
+    // Внутренний заголовок и XML
+    let mut plain = Zeroizing::new(Vec::with_capacity(xml.len() + 128));
+    push_field(&mut plain, INNER_FIELD_STREAM_ID, &INNER_STREAM_CHACHA20.to_le_bytes());
+    push_field(&mut plain, INNER_FIELD_STREAM_KEY, stream_key);
+    for binary in binaries {
+        let mut value = Zeroizing::new(Vec::with_capacity(binary.len() + 1));
+        value.push(0);
+        value.extend_from_slice(binary);
+        push_field(&mut plain, INNER_FIELD_BINARY, &value);
+    }
+    push_field(&mut plain, INNER_FIELD_END, &[]);
+    plain.extend_from_slice(xml);
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&plain)?;
+    let compressed = Zeroizing::new(encoder.finish()?);
+
+    let encrypted = match cipher {
+        KdbxCipher::Aes256 => crypto::aes_cbc_encrypt(&key, iv.as_slice().try_into().unwrap(), &compressed),
+        KdbxCipher::ChaCha20 => {
+            let mut data = compressed.to_vec();
+            crypto::chacha20(&key, iv.as_slice().try_into().unwrap()).apply_keystream(&mut data);
+            data
+        }
+    };
+
+    let mut out = header.clone();
+    out.extend_from_slice(&Sha256::digest(&header));
+    out.extend_from_slice(&header_hmac(&hmac_base, &header));
+
+    // Последний блок пустой: он отмечает конец потока
+    for (index, block) in encrypted.chunks(BLOCK_SIZE).chain([&[][..]]).enumerate() {
+        let mut message = Vec::with_capacity(4 + block.len());
+        message.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        message.extend_from_slice(block);
+        out.extend_from_slice(&block_hmac(&hmac_base, index as u64, &message));
+        out.extend_from_slice(&message);
+    }
+
+    Ok(out)
+}
+
+/// Ключ шифрования данных и базовый ключ HMAC блоков
+type DerivedKeys = (Zeroizing<[u8; 32]>, Zeroizing<[u8; 64]>);
+
+/// Ключ шифрования и базовый ключ HMAC из составного ключа и master seed
+fn derive_keys(
+    composite_key: &[u8; 32],
+    master_seed: &[u8],
+    kdf: &KdfSettings,
+) -> Result<DerivedKeys, KakaduError> {
+    let transformed = kdf.transform(composite_key)?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(master_seed);
+    hasher.update(transformed.as_slice());
+    let key = Zeroizing::new(hasher.finalize().into());
+
+    let mut hasher = Sha512::new();
+    hasher.update(master_seed);
+    hasher.update(transformed.as_slice());
+    hasher.update([1u8]);
+    let hmac_base = Zeroizing::new(hasher.finalize().into());
+
+    Ok((key, hmac_base))
+}
+
+/// HMAC-SHA256 блока данных: сообщение - номер блока и данные
+fn block_hmac(hmac_base: &[u8; 64], index: u64, data: &[u8]) -> [u8; 32] {
+    hmac(hmac_base, index, &[&index.to_le_bytes(), data])
+}
+
+/// HMAC-SHA256 заголовка: ключ для номера блока `u64::MAX`, сообщение - заголовок целиком
+fn header_hmac(hmac_base: &[u8; 64], header: &[u8]) -> [u8; 32] {
+    hmac(hmac_base, u64::MAX, &[header])
+}
+
+/// HMAC-SHA256 с ключом из SHA-512 от номера блока и базового ключа
+fn hmac(hmac_base: &[u8; 64], index: u64, message: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha512::new();
+    hasher.update(index.to_le_bytes());
+    hasher.update(hmac_base);
+    let block_key: Zeroizing<[u8; 64]> = Zeroizing::new(hasher.finalize().into());
+
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(block_key.as_slice()).expect("HMAC принимает ключ любой длины");
+    for part in message {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Разбирает VariantDictionary: версия (2) и записи `тип (1) | длина имени (4) | имя | длина (4) | значение`
+fn read_variant_dictionary(data: &[u8]) -> Result<BTreeMap<String, Variant>, KakaduError> {
+    let mut input = data;
+    let version = u16::from_le_bytes(take_array(&mut input)?);
+    if version >> 8 != VARIANT_DICTIONARY_VERSION >> 8 {
+        return Err(corrupted("неподдерживаемая версия словаря параметров"));
+    }
+
+    let mut items = BTreeMap::new();
+    loop {
+        let kind = take_array::<1>(&mut input)?[0];
+        if kind == 0 {
+            break;
+        }
+        let name_len = u32::from_le_bytes(take_array(&mut input)?) as usize;
+        let name = String::from_utf8_lossy(take(&mut input, name_len)?).into_owned();
+        let value_len = u32::from_le_bytes(take_array(&mut input)?) as usize;
+        let value = take(&mut input, value_len)?;
+
+        let fixed = |len: usize| value.get(..len).filter(|_| value.len() == len).ok_or(KakaduError::Truncated);
+        let variant = match kind {
+            0x04 => Variant::U32(u32::from_le_bytes(fixed(4)?.try_into().unwrap())),
+            0x05 => Variant::U64(u64::from_le_bytes(fixed(8)?.try_into().unwrap())),
+            0x42 => Variant::Bytes(value.to_vec()),
+            0x08 | 0x0C | 0x0D | 0x18 => Variant::Other,
+            other => return Err(corrupted(&format!("неизвестный тип значения словаря: {:#x}", other))),
+        };
+        items.insert(name, variant);
+    }
+
+    Ok(items)
+}
+
+/// Кодирует VariantDictionary
+fn write_variant_dictionary(items: &[(&str, Variant)]) -> Vec<u8> {
+    let mut out = VARIANT_DICTIONARY_VERSION.to_le_bytes().to_vec();
+    for (name, value) in items {
+        let (kind, bytes) = match value {
+            Variant::U32(v) => (0x04, v.to_le_bytes().to_vec()),
+            Variant::U64(v) => (0x05, v.to_le_bytes().to_vec()),
+            Variant::Bytes(v) => (0x42, v.clone()),
+            Variant::Other => continue,
+        };
+        out.push(kind);
+        out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        out.extend_from_slice(&bytes);
+    }
+    out.push(0);
+    out
+}
+
+/// Добавляет поле заголовка `id (1) | длина (4, LE) | значение`
+fn push_field(out: &mut Vec<u8>, id: u8, value: &[u8]) {
+    out.push(id);
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+/// Отделяет `len` байт от начала данных
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], KakaduError> {
+    let (head, rest) = input.split_at_checked(len).ok_or(KakaduError::Truncated)?;
+    *input = rest;
+    Ok(head)
+}
+
+fn take_array<const N: usize>(input: &mut &[u8]) -> Result<[u8; N], KakaduError> {
+    Ok(take(input, N)?.try_into().unwrap())
+}
+
+fn corrupted(message: &str) -> KakaduError {
+    KakaduError::Corrupted(message.to_string())
+}
+
+/// Разбирает UUID из hex-строки на этапе компиляции
+const fn hex16(hex: &str) -> [u8; 16] {
+    const fn digit(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            _ => panic!("некорректный hex"),
+        }
+    }
+
+    let bytes = hex.as_bytes();
+    let mut out = [0u8; 16];
+    let mut i = 0;
+    while i < 16 {
+        out[i] = (digit(bytes[i * 2]) << 4) | digit(bytes[i * 2 + 1]);
+        i += 1;
+    }
+    out
+}
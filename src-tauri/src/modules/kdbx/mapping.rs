@@ -0,0 +1,558 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::RngCore;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use zeroize::Zeroizing;
+
+use crate::modules::kakadu_file_module::attachments::{content_id, Attachment, MAX_VAULT_ATTACHMENTS_SIZE};
+use crate::modules::kakadu_file_module::kinds::RecordKind;
+use crate::modules::kakadu_file_module::secret::SecretField;
+use crate::modules::kakadu_file_module::tree::GroupTree;
+use crate::modules::kakadu_file_module::{
+    unix_time, CustomField, Group, InputSymbol, KakaduError, PasswordData, PasswordHistoryEntry, Record, Timestamps,
+    MAX_PASSWORD_HISTORY,
+};
+use crate::modules::totp::OtpConfig;
+
+use super::crypto::ChaCha20;
+use super::xml::{Element, XmlWriter};
+
+/// Секунд между 0001-01-01 (начало отсчета времени KDBX 4) и UNIX-эпохой
+const KDBX_EPOCH_OFFSET: i64 = 62_135_596_800;
+
+/// Стандартные строки записи KeePass
+const TITLE: &str = "Title";
+const USER_NAME: &str = "UserName";
+const PASSWORD: &str = "Password";
+const URL: &str = "URL";
+const NOTES: &str = "Notes";
+
+/// Строка с URI `otpauth://`, как ее хранит KeePassXC
+const OTP: &str = "otp";
+
+/// Ключи `CustomData` записи для полей, которых нет в KeePass
+const DATA_LOGIN_SYMBOL: &str = "Kakadu.LoginSymbol";
+const DATA_PASSWORD_SYMBOL: &str = "Kakadu.PasswordSymbol";
+const DATA_URL_SYMBOL: &str = "Kakadu.UrlSymbol";
+const DATA_KIND: &str = "Kakadu.Kind";
+
+/// Значок папки KeePass для групп
+const GROUP_ICON: &str = "48";
+
+/// Итоги импорта базы KeePass
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct KdbxImportSummary {
+    pub groups: usize,      // Создано групп
+    pub records: usize,     // Создано записей
+    pub attachments: usize, // Импортировано вложений
+}
+
+/// Переносит содержимое XML KeePass в хранилище
+///
+/// Содержимое корневой группы KeePass попадает в группу `group_id`, а если она не задана -
+/// в новую корневую группу с именем корня KeePass. Вложенные группы создаются с новыми ID.
+/// Корзина пропускается
+pub fn import(
+    data: &mut PasswordData,
+    document: &Element,
+    binaries: &[Zeroizing<Vec<u8>>],
+    group_id: Option<u32>,
+) -> Result<KdbxImportSummary, KakaduError> {
+    if document.name != "KeePassFile" {
+        return Err(KakaduError::Corrupted("XML не является базой KeePass".to_string()));
+    }
+    let root = document
+        .child("Root")
+        .and_then(|r| r.child("Group"))
+        .ok_or_else(|| KakaduError::Corrupted("база KeePass не содержит корневой группы".to_string()))?;
+
+    let recycle_bin = document
+        .child("Meta")
+        .filter(|m| m.child_text("RecycleBinEnabled").eq_ignore_ascii_case("true"))
+        .map(|m| m.child_text("RecycleBinUUID").to_string());
+
+    let mut importer = Importer {
+        data,
+        binaries,
+        recycle_bin,
+        now: unix_time(),
+        summary: KdbxImportSummary::default(),
+    };
+    let group_id = match group_id {
+        Some(id) => id,
+        None => importer.group(root, 0)?,
+    };
+    importer.group_contents(root, group_id)?;
+
+    let data = importer.data;
+    data.attachments.retain_referenced(&data.records);
+    if data.attachments.total_size() > MAX_VAULT_ATTACHMENTS_SIZE {
+        return Err(KakaduError::Corrupted(format!(
+            "вложения превышают допустимый размер хранилища ({} МБ)",
+            MAX_VAULT_ATTACHMENTS_SIZE / (1024 * 1024)
+        )));
+    }
+
+    Ok(importer.summary)
+}
+
+/// Состояние импорта
+struct Importer<'a> {
+    data: &'a mut PasswordData,
+    binaries: &'a [Zeroizing<Vec<u8>>],
+    recycle_bin: Option<String>,
+    now: u64,
+    summary: KdbxImportSummary,
+}
+
+impl Importer<'_> {
+    /// Импортирует записи и вложенные группы группы KeePass в группу `pid`
+    fn group_contents(&mut self, group: &Element, pid: u32) -> Result<(), KakaduError> {
+        for entry in group.children("Entry") {
+            let record = self.record(entry, pid)?;
+            self.data.records.push(record);
+            self.summary.records += 1;
+        }
+
+        for child in group.children("Group") {
+            if self.recycle_bin.as_deref() == Some(child.child_text("UUID")) {
+                continue;
+            }
+
+            let id = self.group(child, pid)?;
+            self.group_contents(child, id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Создает группу с именем и метками времени группы KeePass
+    fn group(&mut self, group: &Element, pid: u32) -> Result<u32, KakaduError> {
+        let id = self.data.allocate_id()?;
+        let mut imported = Group::new(id, pid, group.child_text("Name").to_string());
+        if let Some(times) = group.child("Times") {
+            imported.times = timestamps(times);
+        }
+        self.data.groups.push(imported);
+        self.summary.groups += 1;
+        Ok(id)
+    }
+
+    /// Создает запись из записи KeePass
+    fn record(&mut self, entry: &Element, pid: u32) -> Result<Record, KakaduError> {
+        let strings = entry_strings(entry);
+        let string = |key: &str| {
+            strings
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, (value, _))| value.as_str())
+                .unwrap_or_default()
+        };
+
+        let mut otp = None;
+        let mut custom_fields = Vec::new();
+        for (key, (value, protected)) in &strings {
+            match key.as_str() {
+                TITLE | USER_NAME | PASSWORD | URL | NOTES => {}
+                OTP => match OtpConfig::from_uri(value) {
+                    Ok(config) => otp = Some(config),
+                    Err(_) => custom_fields.push(custom_field(key, value, *protected)),
+                },
+                _ => custom_fields.push(custom_field(key, value, *protected)),
+            }
+        }
+
+        let custom_data: BTreeMap<&str, &str> = entry
+            .child("CustomData")
+            .map(|d| d.children("Item").map(|i| (i.child_text("Key"), i.child_text("Value"))).collect())
+            .unwrap_or_default();
+        let symbol = |key: &str, default: InputSymbol| {
+            custom_data.get(key).and_then(|s| s.parse().ok()).unwrap_or(default)
+        };
+
+        let mut attachments = Vec::new();
+        for binary in entry.children("Binary") {
+            let content = binary
+                .child("Value")
+                .and_then(|v| v.attribute("Ref"))
+                .and_then(|r| r.parse::<usize>().ok())
+                .and_then(|index| self.binaries.get(index))
+                .ok_or_else(|| KakaduError::Corrupted("ссылка на несуществующее вложение".to_string()))?;
+
+            let id = content_id(content);
+            if attachments.iter().any(|a: &Attachment| a.id == id) {
+                continue;
+            }
+            self.data.attachments.insert(content.clone());
+            attachments.push(Attachment {
+                id,
+                name: binary.child_text("Key").to_string(),
+                size: content.len() as u64,
+                added_at: self.now,
+            });
+            self.summary.attachments += 1;
+        }
+
+        let password = string(PASSWORD);
+        let times = entry.child("Times").map(timestamps).unwrap_or_else(Timestamps::created);
+
+        Ok(Record {
+            id: self.data.allocate_id()?,
+            pid,
+            name: string(TITLE).to_string(),
+            login: string(USER_NAME).to_string(),
+            password: SecretField::from(password.to_string()),
+            url: string(URL).to_string(),
+            // Порядок ввода как в автонаборе KeePass по умолчанию: {USERNAME}{TAB}{PASSWORD}{ENTER}
+            login_symbol: symbol(DATA_LOGIN_SYMBOL, InputSymbol::Tab),
+            password_symbol: symbol(DATA_PASSWORD_SYMBOL, InputSymbol::Enter),
+            url_symbol: symbol(DATA_URL_SYMBOL, InputSymbol::None),
+            kind: custom_data
+                .get(DATA_KIND)
+                .and_then(|json| serde_json::from_str::<RecordKind>(json).ok())
+                .unwrap_or_default(),
+            times,
+            otp,
+            custom_fields,
+            notes: string(NOTES).to_string(),
+            tags: entry
+                .child_text("Tags")
+                .split([';', ','])
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect::<BTreeSet<_>>(),
+            attachments,
+            history: password_history(entry, password),
+        })
+    }
+}
+
+/// Строки записи KeePass в порядке документа: (ключ, (значение, защищено))
+fn entry_strings(entry: &Element) -> Vec<(String, (Zeroizing<String>, bool))> {
+    entry
+        .children("String")
+        .map(|s| {
+            let value = s.child("Value");
+            (
+                s.child_text("Key").to_string(),
+                (
+                    Zeroizing::new(value.map(|v| v.text.to_string()).unwrap_or_default()),
+                    value.is_some_and(Element::is_protected),
+                ),
+            )
+        })
+        .collect()
+}
+
+fn custom_field(name: &str, value: &str, hidden: bool) -> CustomField {
+    CustomField {
+        name: name.to_string(),
+        value: SecretField::from(value.to_string()),
+        hidden,
+    }
+}
+
+/// История паролей из версий записи KeePass
+///
+/// Версии, в которых пароль не менялся, и пустые пароли пропускаются.
+/// Время замены - время изменения версии
+fn password_history(entry: &Element, current: &str) -> Vec<PasswordHistoryEntry> {
+    let mut versions: Vec<(u64, Zeroizing<String>)> = entry
+        .child("History")
+        .map(|h| {
+            h.children("Entry")
+                .map(|version| {
+                    let changed_at = version
+                        .child("Times")
+                        .and_then(|t| parse_time(t.child_text("LastModificationTime")))
+                        .unwrap_or(0);
+                    let password = entry_strings(version)
+                        .into_iter()
+                        .find(|(key, _)| key == PASSWORD)
+                        .map(|(_, (value, _))| value)
+                        .unwrap_or_default();
+                    (changed_at, password)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    versions.sort_by_key(|(changed_at, _)| std::cmp::Reverse(*changed_at));
+
+    let mut history: Vec<PasswordHistoryEntry> = Vec::new();
+    let mut newer = Zeroizing::new(current.to_string());
+    for (changed_at, password) in versions {
+        if password.is_empty() || password == newer {
+            continue;
+        }
+        history.push(PasswordHistoryEntry {
+            password: SecretField::from(password.to_string()),
+            changed_at,
+        });
+        newer = password;
+    }
+
+    history.truncate(MAX_PASSWORD_HISTORY);
+    history
+}
+
+/// Метки времени из элемента `Times`
+fn timestamps(times: &Element) -> Timestamps {
+    let time = |name: &str| parse_time(times.child_text(name));
+    Timestamps {
+        created_at: time("CreationTime"),
+        modified_at: time("LastModificationTime"),
+        last_used_at: time("LastAccessTime"),
+        expires_at: time("ExpiryTime").filter(|_| times.child_text("Expires").eq_ignore_ascii_case("true")),
+    }
+}
+
+/// Время KDBX 4: Base64 от i64 LE - секунд с 0001-01-01
+fn parse_time(text: &str) -> Option<u64> {
+    let bytes: [u8; 8] = STANDARD.decode(text.trim()).ok()?.try_into().ok()?;
+    u64::try_from(i64::from_le_bytes(bytes).checked_sub(KDBX_EPOCH_OFFSET)?).ok()
+}
+
+fn format_time(unix: u64) -> String {
+    STANDARD.encode((unix as i64).saturating_add(KDBX_EPOCH_OFFSET).to_le_bytes())
+}
+
+/// Формирует XML KeePass из хранилища
+///
+/// # Поведение
+/// Единственная корневая группа становится корнем KeePass, иначе корни
+/// объединяются в общую группу. Записи несуществующих групп попадают в корень.
+/// Символы ввода и вид записи сохраняются в `CustomData` записи
+///
+/// # Возвращает
+/// XML с защищенными паролями и скрытыми полями и вложения в порядке ссылок `Ref`
+pub fn export(data: &PasswordData, stream: ChaCha20) -> (Zeroizing<Vec<u8>>, Vec<&[u8]>) {
+    let tree = GroupTree::new(data);
+
+    // Каждое уникальное содержимое попадает во внутренний заголовок один раз
+    let mut binaries = Vec::new();
+    let mut binary_refs = BTreeMap::new();
+    for attachment in data.records.iter().flat_map(|r| &r.attachments) {
+        if let Some(content) = data.attachments.get(&attachment.id) {
+            binary_refs.entry(attachment.id.as_str()).or_insert_with(|| {
+                binaries.push(content);
+                binaries.len() - 1
+            });
+        }
+    }
+
+    let mut exporter = Exporter {
+        writer: XmlWriter::new(stream),
+        data,
+        tree: &tree,
+        binary_refs,
+        now: unix_time(),
+    };
+    let roots = tree.roots();
+    let root_name = match roots {
+        [root] => tree.group(*root).map(|g| g.name.as_str()).unwrap_or_default(),
+        _ => "Root",
+    };
+
+    let w = &mut exporter.writer;
+    w.start("KeePassFile");
+    w.start("Meta");
+    w.text("Generator", "Kakadu");
+    w.text("DatabaseName", root_name);
+    w.start("MemoryProtection");
+    w.text("ProtectTitle", "False");
+    w.text("ProtectUserName", "False");
+    w.text("ProtectPassword", "True");
+    w.text("ProtectURL", "False");
+    w.text("ProtectNotes", "False");
+    w.end("MemoryProtection");
+    w.text("RecycleBinEnabled", "False");
+    w.end("Meta");
+    w.start("Root");
+
+    let orphans = |r: &&Record| tree.group(r.pid).is_none();
+    match roots {
+        [root] => exporter.group(*root, &orphans),
+        _ => {
+            let now = exporter.now;
+            exporter.group_start(root_name, &Timestamps { created_at: Some(now), ..Default::default() });
+            for record in data.records.iter().filter(|r| orphans(r)) {
+                exporter.entry(record);
+            }
+            for &root in roots {
+                exporter.group(root, &|_| false);
+            }
+            exporter.writer.end("Group");
+        }
+    }
+
+    let w = &mut exporter.writer;
+    w.end("Root");
+    w.end("KeePassFile");
+
+    (exporter.writer.finish(), binaries)
+}
+
+/// Состояние экспорта
+struct Exporter<'a> {
+    writer: XmlWriter,
+    data: &'a PasswordData,
+    tree: &'a GroupTree<'a>,
+    binary_refs: BTreeMap<&'a str, usize>,
+    now: u64,
+}
+
+impl Exporter<'_> {
+    /// Записывает группу с записями и вложенными группами
+    ///
+    /// `extra` отбирает дополнительные записи группы (записи без существующей группы для корня)
+    fn group(&mut self, id: u32, extra: &dyn Fn(&&Record) -> bool) {
+        let Some(group) = self.tree.group(id) else {
+            return;
+        };
+        self.group_start(&group.name, &group.times);
+
+        let data = self.data;
+        for record in data.records.iter().filter(|r| r.pid == id || extra(r)) {
+            self.entry(record);
+        }
+        for &child in self.tree.children(id) {
+            self.group(child, &|_| false);
+        }
+
+        self.writer.end("Group");
+    }
+
+    fn group_start(&mut self, name: &str, times: &Timestamps) {
+        let w = &mut self.writer;
+        w.start("Group");
+        w.text("UUID", &new_uuid());
+        w.text("Name", name);
+        w.text("IconID", GROUP_ICON);
+        self.times(times);
+        self.writer.text("IsExpanded", "True");
+    }
+
+    /// Записывает запись со строками, вложениями и историей паролей
+    fn entry(&mut self, record: &Record) {
+        let uuid = new_uuid();
+        let w = &mut self.writer;
+        w.start("Entry");
+        w.text("UUID", &uuid);
+        w.text("IconID", "0");
+        w.text("Tags", &record.tags.iter().map(String::as_str).collect::<Vec<_>>().join(";"));
+        self.times(&record.times);
+
+        let kind = (!record.kind.is_login()).then(|| serde_json::to_string(&record.kind).unwrap_or_default());
+        let symbols = [
+            (DATA_LOGIN_SYMBOL, &record.login_symbol),
+            (DATA_PASSWORD_SYMBOL, &record.password_symbol),
+            (DATA_URL_SYMBOL, &record.url_symbol),
+        ];
+        let w = &mut self.writer;
+        w.start("CustomData");
+        for (key, symbol) in symbols {
+            let value = serde_json::to_value(symbol).ok();
+            custom_data_item(w, key, value.as_ref().and_then(|v| v.as_str()).unwrap_or_default());
+        }
+        if let Some(kind) = &kind {
+            custom_data_item(w, DATA_KIND, kind);
+        }
+        w.end("CustomData");
+
+        // Ключи строк уникальны: дополнительное поле со стандартным или повторяющимся
+        // названием получает суффикс
+        let mut used: HashSet<String> = [TITLE, USER_NAME, PASSWORD, URL, NOTES].map(String::from).into();
+        string(w, TITLE, &record.name, false);
+        string(w, USER_NAME, &record.login, false);
+        string(w, PASSWORD, record.password.expose(), true);
+        string(w, URL, &record.url, false);
+        string(w, NOTES, &record.notes, false);
+        if let Some(otp) = &record.otp {
+            used.insert(OTP.to_string());
+            string(w, OTP, &otp.to_uri(), true);
+        }
+        for field in &record.custom_fields {
+            let mut key = field.name.clone();
+            let mut n = 2;
+            while !used.insert(key.clone()) {
+                key = format!("{} ({})", field.name, n);
+                n += 1;
+            }
+            string(w, &key, field.value.expose(), field.hidden);
+        }
+
+        for attachment in &record.attachments {
+            if let Some(index) = self.binary_refs.get(attachment.id.as_str()) {
+                w.start("Binary");
+                w.text("Key", &attachment.name);
+                w.empty("Value", &[("Ref", &index.to_string())]);
+                w.end("Binary");
+            }
+        }
+
+        // Версии KeePass упорядочены от старых к новым
+        if !record.history.is_empty() {
+            w.start("History");
+            for entry in record.history.iter().rev() {
+                let w = &mut self.writer;
+                w.start("Entry");
+                w.text("UUID", &uuid);
+                self.times(&Timestamps {
+                    modified_at: Some(entry.changed_at),
+                    ..record.times.clone()
+                });
+                let w = &mut self.writer;
+                string(w, TITLE, &record.name, false);
+                string(w, USER_NAME, &record.login, false);
+                string(w, PASSWORD, entry.password.expose(), true);
+                string(w, URL, &record.url, false);
+                w.end("Entry");
+            }
+            self.writer.end("History");
+        }
+
+        self.writer.end("Entry");
+    }
+
+    /// Записывает элемент `Times`; отсутствующие метки заменяются текущим временем
+    fn times(&mut self, times: &Timestamps) {
+        let now = self.now;
+        let created = times.created_at.unwrap_or(now);
+        let modified = times.modified_at.unwrap_or(created);
+
+        let w = &mut self.writer;
+        w.start("Times");
+        w.text("CreationTime", &format_time(created));
+        w.text("LastModificationTime", &format_time(modified));
+        w.text("LastAccessTime", &format_time(times.last_used_at.unwrap_or(modified)));
+        w.text("ExpiryTime", &format_time(times.expires_at.unwrap_or(now)));
+        w.text("Expires", if times.expires_at.is_some() { "True" } else { "False" });
+        w.text("UsageCount", "0");
+        w.text("LocationChanged", &format_time(modified));
+        w.end("Times");
+    }
+}
+
+/// Строка записи `<String><Key/><Value/></String>`
+fn string(w: &mut XmlWriter, key: &str, value: &str, protected: bool) {
+    w.start("String");
+    w.text("Key", key);
+    w.value(value, protected);
+    w.end("String");
+}
+
+fn custom_data_item(w: &mut XmlWriter, key: &str, value: &str) {
+    w.start("Item");
+    w.text("Key", key);
+    w.text("Value", value);
+    w.end("Item");
+}
+
+/// Случайный UUID в Base64, как его хранит KeePass
+fn new_uuid() -> String {
+    let mut uuid = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut uuid);
+    STANDARD.encode(uuid)
+}
@@ -0,0 +1,202 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20::cipher::StreamCipher;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use zeroize::Zeroizing;
+
+use crate::modules::kakadu_file_module::KakaduError;
+
+use super::crypto::ChaCha20;
+
+/// Элемент XML-документа KeePass
+///
+/// Документ небольшой, поэтому разбирается целиком в дерево;
+/// текст элементов затирается в памяти при освобождении
+#[derive(Debug, Default)]
+pub struct Element {
+    pub name: String,                      // Имя элемента
+    pub attributes: Vec<(String, String)>, // Атрибуты
+    pub text: Zeroizing<String>,           // Текст (для элементов без дочерних)
+    pub children: Vec<Element>,            // Дочерние элементы по порядку
+}
+
+impl Element {
+    /// Первый дочерний элемент с указанным именем
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// Все дочерние элементы с указанным именем
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// Текст дочернего элемента или пустая строка, если его нет
+    pub fn child_text(&self, name: &str) -> &str {
+        self.child(name).map(|c| c.text.as_str()).unwrap_or_default()
+    }
+
+    /// Значение атрибута
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Значение помечено как защищенное (`Protected="True"`)
+    pub fn is_protected(&self) -> bool {
+        self.attribute("Protected").is_some_and(|v| v.eq_ignore_ascii_case("true"))
+    }
+}
+
+/// Разбирает XML в дерево элементов
+///
+/// # Аргументы
+/// * `stream` - поток защиты значений: защищенные `<Value Protected="True">` расшифровываются
+///   в порядке следования в документе. `None` - для XML без защищенных значений (файл-ключ)
+pub fn parse(xml: &[u8], mut stream: Option<&mut ChaCha20>) -> Result<Element, KakaduError> {
+    let xml = std::str::from_utf8(xml).map_err(|_| corrupted("XML содержит некорректный UTF-8"))?;
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+
+    loop {
+        let closed = match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) => {
+                stack.push(element(&e)?);
+                None
+            }
+            Event::Empty(e) => Some(element(&e)?),
+            Event::End(_) => stack.pop(),
+            Event::Text(t) => {
+                if let Some(top) = stack.last_mut() {
+                    top.text.push_str(&t.unescape().map_err(xml_error)?);
+                }
+                None
+            }
+            Event::CData(c) => {
+                if let Some(top) = stack.last_mut() {
+                    top.text.push_str(std::str::from_utf8(&c).map_err(|_| corrupted("CDATA содержит некорректный UTF-8"))?);
+                }
+                None
+            }
+            Event::Eof => break,
+            _ => None,
+        };
+
+        if let Some(mut element) = closed {
+            if element.name == "Value" && element.is_protected() {
+                if let Some(stream) = stream.as_deref_mut() {
+                    element.text = unprotect(&element.text, stream)?;
+                }
+            }
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => root = Some(element),
+            }
+        }
+    }
+
+    root.ok_or_else(|| corrupted("XML не содержит корневого элемента"))
+}
+
+/// Запись XML KeePass с защитой значений потоком ChaCha20
+pub struct XmlWriter {
+    writer: Writer<Vec<u8>>,
+    stream: ChaCha20,
+}
+
+impl XmlWriter {
+    /// Начинает документ с объявлением XML
+    pub fn new(stream: ChaCha20) -> Self {
+        let mut writer = Self {
+            writer: Writer::new_with_indent(Vec::new(), b'\t', 1),
+            stream,
+        };
+        writer.write(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), Some("yes"))));
+        writer
+    }
+
+    /// Открывающий тег
+    pub fn start(&mut self, name: &str) {
+        self.write(Event::Start(BytesStart::new(name)));
+    }
+
+    /// Закрывающий тег
+    pub fn end(&mut self, name: &str) {
+        self.write(Event::End(BytesEnd::new(name)));
+    }
+
+    /// Элемент с текстом: `<name>value</name>`
+    pub fn text(&mut self, name: &str, value: &str) {
+        self.start(name);
+        self.write(Event::Text(BytesText::new(value)));
+        self.end(name);
+    }
+
+    /// Пустой элемент с атрибутами
+    pub fn empty(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.write(Event::Empty(BytesStart::new(name).with_attributes(attributes.iter().copied())));
+    }
+
+    /// Значение строки записи; защищенное шифруется потоком и кодируется в Base64
+    pub fn value(&mut self, value: &str, protected: bool) {
+        if !protected {
+            return self.text("Value", value);
+        }
+
+        let mut bytes = Zeroizing::new(value.as_bytes().to_vec());
+        self.stream.apply_keystream(&mut bytes);
+        self.write(Event::Start(BytesStart::new("Value").with_attributes([("Protected", "True")])));
+        self.write(Event::Text(BytesText::new(&STANDARD.encode(bytes.as_slice()))));
+        self.end("Value");
+    }
+
+    /// Завершает документ
+    pub fn finish(self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(self.writer.into_inner())
+    }
+
+    fn write(&mut self, event: Event) {
+        self.writer.write_event(event).expect("запись XML в память не завершается ошибкой");
+    }
+}
+
+/// Создает элемент из открывающего тега
+fn element(start: &BytesStart) -> Result<Element, KakaduError> {
+    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+    let attributes = start
+        .attributes()
+        .map(|attr| {
+            let attr = attr.map_err(|e| corrupted(&format!("ошибка атрибута XML: {}", e)))?;
+            let value = attr.unescape_value().map_err(xml_error)?.into_owned();
+            Ok((String::from_utf8_lossy(attr.key.as_ref()).into_owned(), value))
+        })
+        .collect::<Result<_, KakaduError>>()?;
+
+    Ok(Element {
+        name,
+        attributes,
+        ..Default::default()
+    })
+}
+
+/// Расшифровывает защищенное значение: Base64, затем XOR с потоком
+fn unprotect(text: &str, stream: &mut ChaCha20) -> Result<Zeroizing<String>, KakaduError> {
+    let mut bytes = Zeroizing::new(
+        STANDARD
+            .decode(text.trim())
+            .map_err(|_| corrupted("защищенное значение не в Base64"))?,
+    );
+    stream.apply_keystream(&mut bytes);
+    String::from_utf8(bytes.to_vec())
+        .map(Zeroizing::new)
+        .map_err(|_| corrupted("защищенное значение содержит некорректный UTF-8"))
+}
+
+fn xml_error(e: quick_xml::Error) -> KakaduError {
+    corrupted(&format!("ошибка XML: {}", e))
+}
+
+fn corrupted(message: &str) -> KakaduError {
+    KakaduError::Corrupted(message.to_string())
+}
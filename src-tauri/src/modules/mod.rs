@@ -1,2 +1,7 @@
+pub mod audit;
+pub mod auto_lock;
 pub mod com_port;
+pub mod generator;
 pub mod kakadu_file_module;
+pub mod kdbx;
+pub mod totp;
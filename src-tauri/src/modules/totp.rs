@@ -0,0 +1,485 @@
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use zeroize::Zeroizing;
+
+use crate::modules::kakadu_file_module::secret::SecretField;
+
+/// Схема URI, используемая в QR-кодах приложений-аутентификаторов
+const OTPAUTH_SCHEME: &str = "otpauth://";
+
+/// Алфавит Base32 (RFC 4648)
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Символы, которые не кодируются в метке и параметрах URI
+const URI_SAFE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// Допустимое количество цифр кода
+const MIN_DIGITS: u32 = 6;
+const MAX_DIGITS: u32 = 8;
+
+/// Вид одноразового пароля
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OtpKind {
+    /// По времени (RFC 6238)
+    #[default]
+    Totp,
+    /// По счетчику (RFC 4226)
+    Hotp,
+}
+
+/// Хеш-функция HMAC
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OtpAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// Параметры генерации одноразовых паролей записи
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OtpConfig {
+    #[serde(rename = "type", default)]
+    pub kind: OtpKind, // TOTP или HOTP
+
+    pub secret: SecretField, // Секрет в Base32
+
+    #[serde(default)]
+    pub algorithm: OtpAlgorithm, // Хеш-функция HMAC
+
+    #[serde(default = "default_digits")]
+    pub digits: u32, // Количество цифр кода
+
+    #[serde(default = "default_period")]
+    pub period: u64, // Период TOTP в секундах
+
+    #[serde(default)]
+    pub counter: u64, // Следующее значение счетчика HOTP
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>, // Сервис, выдавший секрет
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>, // Метка аккаунта (обычно `Сервис:логин`)
+}
+
+fn default_digits() -> u32 {
+    6
+}
+
+fn default_period() -> u64 {
+    30
+}
+
+/// Параметры одноразовых паролей, приходящие с фронтенда:
+/// строка `otpauth://...` или явно заданные параметры
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum OtpInput {
+    Uri(SecretField),
+    Config(OtpConfig),
+}
+
+impl OtpInput {
+    /// Проверяет ввод и приводит его к параметрам записи
+    pub fn into_config(self) -> Result<OtpConfig, String> {
+        match self {
+            OtpInput::Uri(uri) => OtpConfig::from_uri(uri.expose()),
+            OtpInput::Config(config) => {
+                config.validate()?;
+                Ok(config)
+            }
+        }
+    }
+}
+
+/// Текущий одноразовый пароль
+#[derive(Debug, Serialize, Clone)]
+pub struct OtpCode {
+    pub code: String,                   // Код
+    pub remaining_seconds: Option<u64>, // Секунд до смены кода (только TOTP)
+    pub period: Option<u64>,            // Период TOTP в секундах
+}
+
+impl OtpConfig {
+    /// Проверяет параметры: корректный непустой секрет, число цифр и период
+    pub fn validate(&self) -> Result<(), String> {
+        if base32_decode(self.secret.expose())?.is_empty() {
+            return Err("Секрет OTP не может быть пустым".to_string());
+        }
+        if !(MIN_DIGITS..=MAX_DIGITS).contains(&self.digits) {
+            return Err(format!("Количество цифр OTP должно быть от {} до {}", MIN_DIGITS, MAX_DIGITS));
+        }
+        if self.period == 0 {
+            return Err("Период TOTP должен быть больше нуля".to_string());
+        }
+        Ok(())
+    }
+
+    /// Разбирает URI `otpauth://TYPE/LABEL?secret=...&issuer=...&algorithm=...&digits=...&period=...&counter=...`
+    ///
+    /// Сервис берется из параметра `issuer`, а если его нет - из префикса метки
+    /// `Сервис:логин`. Метка сохраняется целиком
+    ///
+    /// # Ошибки
+    /// Возвращает ошибку если схема или тип не поддерживаются, нет секрета
+    /// или параметры некорректны
+    pub fn from_uri(uri: &str) -> Result<Self, String> {
+        let rest = uri
+            .get(..OTPAUTH_SCHEME.len())
+            .filter(|scheme| scheme.eq_ignore_ascii_case(OTPAUTH_SCHEME))
+            .map(|_| &uri[OTPAUTH_SCHEME.len()..])
+            .ok_or_else(|| "URI должен начинаться с otpauth://".to_string())?;
+
+        let (kind, rest) = rest.split_once('/').unwrap_or((rest, ""));
+        let kind = match kind.to_ascii_lowercase().as_str() {
+            "totp" => OtpKind::Totp,
+            "hotp" => OtpKind::Hotp,
+            other => return Err(format!("Неподдерживаемый тип OTP: {}", other)),
+        };
+
+        let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let label = decode_component(label)?;
+
+        let mut config = OtpConfig {
+            kind,
+            secret: SecretField::default(),
+            algorithm: OtpAlgorithm::default(),
+            digits: default_digits(),
+            period: default_period(),
+            counter: 0,
+            issuer: None,
+            label: (!label.is_empty()).then_some(label),
+        };
+
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = Zeroizing::new(decode_component(value)?);
+
+            match key.to_ascii_lowercase().as_str() {
+                "secret" => config.secret = SecretField::from(value.to_string()),
+                "issuer" => config.issuer = Some(value.to_string()),
+                "algorithm" => {
+                    config.algorithm = match value.to_ascii_uppercase().as_str() {
+                        "SHA1" => OtpAlgorithm::Sha1,
+                        "SHA256" => OtpAlgorithm::Sha256,
+                        "SHA512" => OtpAlgorithm::Sha512,
+                        other => return Err(format!("Неподдерживаемый алгоритм OTP: {}", other)),
+                    }
+                }
+                "digits" => config.digits = parse_number(&value, "digits")?,
+                "period" => config.period = parse_number(&value, "period")?,
+                "counter" => config.counter = parse_number(&value, "counter")?,
+                // Неизвестные параметры (например, `image`) игнорируются
+                _ => {}
+            }
+        }
+
+        if config.issuer.is_none() {
+            config.issuer = config
+                .label
+                .as_deref()
+                .and_then(|label| label.split_once(':'))
+                .map(|(issuer, _)| issuer.trim().to_string())
+                .filter(|issuer| !issuer.is_empty());
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Формирует URI `otpauth://` для экспорта и QR-кода
+    ///
+    /// Секрет записывается в Base32 без выравнивания `=`, параметры по умолчанию
+    /// (SHA1, 6 цифр, период 30 секунд) тоже указываются явно для совместимости
+    pub fn to_uri(&self) -> Zeroizing<String> {
+        let kind = match self.kind {
+            OtpKind::Totp => "totp",
+            OtpKind::Hotp => "hotp",
+        };
+        let algorithm = match self.algorithm {
+            OtpAlgorithm::Sha1 => "SHA1",
+            OtpAlgorithm::Sha256 => "SHA256",
+            OtpAlgorithm::Sha512 => "SHA512",
+        };
+        let secret: Zeroizing<String> = Zeroizing::new(
+            self.secret
+                .expose()
+                .chars()
+                .filter(|c| !c.is_whitespace() && *c != '-' && *c != '=')
+                .map(|c| c.to_ascii_uppercase())
+                .collect(),
+        );
+
+        let mut uri = Zeroizing::new(format!(
+            "{}{}/{}?secret={}",
+            OTPAUTH_SCHEME,
+            kind,
+            utf8_percent_encode(self.label.as_deref().unwrap_or_default(), URI_SAFE),
+            *secret
+        ));
+        if let Some(issuer) = &self.issuer {
+            uri.push_str(&format!("&issuer={}", utf8_percent_encode(issuer, URI_SAFE)));
+        }
+        uri.push_str(&format!("&algorithm={}&digits={}", algorithm, self.digits));
+        match self.kind {
+            OtpKind::Totp => uri.push_str(&format!("&period={}", self.period)),
+            OtpKind::Hotp => uri.push_str(&format!("&counter={}", self.counter)),
+        }
+
+        uri
+    }
+
+    /// Вычисляет код на момент `now` (секунды с UNIX-эпохи)
+    ///
+    /// Для HOTP используется текущий `counter`; увеличивать его после выдачи кода
+    /// должен вызывающий код
+    pub fn code_at(&self, now: u64) -> Result<OtpCode, String> {
+        self.validate()?;
+        let key = base32_decode(self.secret.expose())?;
+
+        match self.kind {
+            OtpKind::Totp => Ok(OtpCode {
+                code: totp(&key, self.algorithm, self.digits, self.period, now),
+                remaining_seconds: Some(self.period - now % self.period),
+                period: Some(self.period),
+            }),
+            OtpKind::Hotp => Ok(OtpCode {
+                code: hotp(&key, self.algorithm, self.digits, self.counter),
+                remaining_seconds: None,
+                period: None,
+            }),
+        }
+    }
+}
+
+/// HOTP (RFC 4226): HMAC от счетчика, динамическое усечение и остаток от деления на 10^digits
+pub fn hotp(key: &[u8], algorithm: OtpAlgorithm, digits: u32, counter: u64) -> String {
+    let message = counter.to_be_bytes();
+    let hash = match algorithm {
+        OtpAlgorithm::Sha1 => hmac::<Hmac<Sha1>>(key, &message),
+        OtpAlgorithm::Sha256 => hmac::<Hmac<Sha256>>(key, &message),
+        OtpAlgorithm::Sha512 => hmac::<Hmac<Sha512>>(key, &message),
+    };
+
+    let offset = usize::from(hash[hash.len() - 1] & 0x0f);
+    let binary = u32::from_be_bytes(hash[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+    let code = u64::from(binary) % 10u64.pow(digits);
+
+    format!("{:0width$}", code, width = digits as usize)
+}
+
+/// TOTP (RFC 6238): HOTP от номера периода `now / period`
+pub fn totp(key: &[u8], algorithm: OtpAlgorithm, digits: u32, period: u64, now: u64) -> String {
+    hotp(key, algorithm, digits, now / period)
+}
+
+/// Вычисляет HMAC указанного типа
+fn hmac<M: Mac + KeyInit>(key: &[u8], message: &[u8]) -> Zeroizing<Vec<u8>> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC принимает ключ любой длины");
+    mac.update(message);
+    Zeroizing::new(mac.finalize().into_bytes().to_vec())
+}
+
+/// Декодирует Base32 (RFC 4648) без учета регистра, пробелов, дефисов и выравнивания `=`
+pub fn base32_decode(input: &str) -> Result<Zeroizing<Vec<u8>>, String> {
+    let mut output = Zeroizing::new(Vec::with_capacity(input.len() * 5 / 8));
+    let mut buffer = 0u32;
+    let mut bits = 0u32;
+
+    for c in input.chars().filter(|c| !c.is_whitespace() && *c != '-' && *c != '=') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| char::from(a) == c.to_ascii_uppercase())
+            .ok_or_else(|| format!("Недопустимый символ Base32: {}", c))?;
+
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Ok(output)
+}
+
+/// Декодирует компонент URI (`%XX`)
+fn decode_component(value: &str) -> Result<String, String> {
+    percent_decode_str(value)
+        .decode_utf8()
+        .map(|v| v.into_owned())
+        .map_err(|_| "URI содержит некорректную последовательность UTF-8".to_string())
+}
+
+/// Разбирает числовой параметр URI
+fn parse_number<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Некорректное значение параметра {}: {}", name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ключи из приложений RFC 4226 и RFC 6238 для SHA1, SHA256 и SHA512
+    const SEED_SHA1: &[u8] = b"12345678901234567890";
+    const SEED_SHA256: &[u8] = b"12345678901234567890123456789012";
+    const SEED_SHA512: &[u8] = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+    /// `SEED_SHA1` в Base32
+    const SECRET_SHA1: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    fn config(kind: OtpKind, secret: &str) -> OtpConfig {
+        OtpConfig {
+            kind,
+            secret: SecretField::from(secret.to_string()),
+            algorithm: OtpAlgorithm::Sha1,
+            digits: 6,
+            period: 30,
+            counter: 0,
+            issuer: None,
+            label: None,
+        }
+    }
+
+    #[test]
+    fn rfc4226_hotp_vectors() {
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(SEED_SHA1, OtpAlgorithm::Sha1, 6, counter as u64), *code, "counter {}", counter);
+        }
+    }
+
+    #[test]
+    fn rfc6238_totp_vectors() {
+        let vectors: [(u64, &str, &str, &str); 6] = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+        for (time, sha1, sha256, sha512) in vectors {
+            assert_eq!(totp(SEED_SHA1, OtpAlgorithm::Sha1, 8, 30, time), sha1, "SHA1 {}", time);
+            assert_eq!(totp(SEED_SHA256, OtpAlgorithm::Sha256, 8, 30, time), sha256, "SHA256 {}", time);
+            assert_eq!(totp(SEED_SHA512, OtpAlgorithm::Sha512, 8, 30, time), sha512, "SHA512 {}", time);
+        }
+    }
+
+    #[test]
+    fn code_at_uses_base32_secret_and_counter() {
+        let totp_code = config(OtpKind::Totp, SECRET_SHA1).code_at(59).unwrap();
+        assert_eq!(totp_code.code, "287082");
+        assert_eq!(totp_code.remaining_seconds, Some(1));
+        assert_eq!(totp_code.period, Some(30));
+
+        let mut hotp_config = config(OtpKind::Hotp, &SECRET_SHA1.to_lowercase());
+        hotp_config.counter = 9;
+        let hotp_code = hotp_config.code_at(0).unwrap();
+        assert_eq!(hotp_code.code, "520489");
+        assert_eq!(hotp_code.remaining_seconds, None);
+    }
+
+    #[test]
+    fn uri_round_trip() {
+        let uri = "otpauth://totp/ACME%20Co%3Ajohn%40example.com?secret=GEZDGNBVGY3TQOJQ&issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60";
+        let parsed = OtpConfig::from_uri(uri).unwrap();
+
+        assert_eq!(parsed.kind, OtpKind::Totp);
+        assert_eq!(parsed.secret.expose(), "GEZDGNBVGY3TQOJQ");
+        assert_eq!(parsed.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!(parsed.digits, 8);
+        assert_eq!(parsed.period, 60);
+        assert_eq!(parsed.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(parsed.label.as_deref(), Some("ACME Co:john@example.com"));
+
+        assert_eq!(parsed.to_uri().as_str(), uri);
+        assert_eq!(OtpConfig::from_uri(&parsed.to_uri()).unwrap(), parsed);
+
+        let mut hotp_config = config(OtpKind::Hotp, "gezd-gnbv gy3t====");
+        hotp_config.counter = 42;
+        let hotp_uri = hotp_config.to_uri();
+        assert_eq!(hotp_uri.as_str(), "otpauth://hotp/?secret=GEZDGNBVGY3T&algorithm=SHA1&digits=6&counter=42");
+        let reparsed = OtpConfig::from_uri(&hotp_uri).unwrap();
+        assert_eq!(reparsed.counter, 42);
+        assert_eq!(reparsed.label, None);
+    }
+
+    #[test]
+    fn issuer_parameter_takes_precedence_over_label_prefix() {
+        let both = OtpConfig::from_uri("otpauth://totp/Old%20Name:alice?secret=GEZDGNBV&issuer=New%20Name").unwrap();
+        assert_eq!(both.issuer.as_deref(), Some("New Name"));
+        assert_eq!(both.label.as_deref(), Some("Old Name:alice"));
+
+        let label_only = OtpConfig::from_uri("otpauth://totp/Example:alice%40example.com?secret=GEZDGNBV").unwrap();
+        assert_eq!(label_only.issuer.as_deref(), Some("Example"));
+
+        let no_prefix = OtpConfig::from_uri("otpauth://totp/alice?secret=GEZDGNBV").unwrap();
+        assert_eq!(no_prefix.issuer, None);
+        assert_eq!(no_prefix.label.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn uri_parameters_are_percent_encoded() {
+        let mut config = config(OtpKind::Totp, SECRET_SHA1);
+        config.label = Some("Почта:bob&co?x=1".to_string());
+        config.issuer = Some("A/B #1".to_string());
+
+        let uri = config.to_uri();
+        assert!(uri.contains("/%D0%9F%D0%BE%D1%87%D1%82%D0%B0%3Abob%26co%3Fx%3D1?"));
+        assert!(uri.contains("&issuer=A%2FB%20%231&"));
+        assert_eq!(OtpConfig::from_uri(&uri).unwrap(), config);
+
+        assert!(OtpConfig::from_uri("otpauth://totp/%FF?secret=GEZDGNBV").is_err());
+    }
+
+    #[test]
+    fn invalid_input_is_rejected() {
+        for secret in ["GEZD1NBV", "GEZD8NBV", "GEZD0NBV", "ГЕЗД", "="] {
+            assert!(config(OtpKind::Totp, secret).validate().is_err(), "secret {}", secret);
+        }
+        assert!(base32_decode("MZXW6===").is_ok());
+
+        for uri in [
+            "https://totp/x?secret=GEZDGNBV",
+            "otpauth://motp/x?secret=GEZDGNBV",
+            "otpauth://totp/x",
+            "otpauth://totp/x?secret=GEZD1NBV",
+            "otpauth://totp/x?secret=GEZDGNBV&algorithm=MD5",
+            "otpauth://totp/x?secret=GEZDGNBV&digits=5",
+            "otpauth://totp/x?secret=GEZDGNBV&digits=abc",
+            "otpauth://totp/x?secret=GEZDGNBV&period=0",
+            "otpauth://hotp/x?secret=GEZDGNBV&counter=-1",
+        ] {
+            assert!(OtpConfig::from_uri(uri).is_err(), "{}", uri);
+        }
+    }
+
+    #[test]
+    fn base32_decodes_rfc4648_vectors() {
+        for (encoded, decoded) in [
+            ("", ""),
+            ("MY======", "f"),
+            ("MZXQ====", "fo"),
+            ("MZXW6===", "foo"),
+            ("MZXW6YQ=", "foob"),
+            ("MZXW6YTB", "fooba"),
+            ("MZXW6YTBOI======", "foobar"),
+        ] {
+            assert_eq!(base32_decode(encoded).unwrap().as_slice(), decoded.as_bytes());
+        }
+    }
+}
@@ -1,8 +1,17 @@
-use crate::modules::kakadu_file_module::{Group, PasswordData};
-use crate::utils::emit_event;
-use std::sync::Mutex;
+use crate::modules::kakadu_file_module::attachments::AttachmentStore;
+use crate::modules::kakadu_file_module::{Group, KakaduProvider, PasswordData, SessionKey, DEFAULT_BACKUP_COUNT};
+use crate::utils::{emit_event, CommandError};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 use tauri::AppHandle;
 
+/// Время бездействия по умолчанию, после которого хранилище блокируется
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Сколько ждать после неудачной автоблокировки, прежде чем затереть данные без сохранения
+pub const LOCK_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
 /// Глобальное состояние приложения для хранения и управления данными паролей
 ///
 /// # Структура
@@ -10,8 +19,20 @@ use tauri::AppHandle;
 /// - Потокобезопасного доступа из разных частей приложения
 /// - Явного отслеживания состояния инициализации данных
 /// - Атомарных операций с данными
+///
+/// Мастер-пароль в состоянии не хранится: после открытия или сохранения
+/// файла держится только полученный из него ключ сессии и путь к хранилищу.
+/// При захвате нескольких Mutex порядок всегда `password_data` → `session_key` → `vault_path`
+/// → `saved_digest`; `last_activity` и `lock_failed_at` захватываются последними и по одному
 pub struct AppState {
     pub password_data: Mutex<Option<PasswordData>>,
+    pub session_key: Mutex<Option<SessionKey>>, // Ключ для сохранения без пароля
+    pub vault_path: Mutex<Option<String>>,      // Путь к открытому файлу хранилища
+    pub saved_digest: Mutex<Option<[u8; 32]>>,  // Отпечаток данных на момент открытия/сохранения
+    pub last_activity: Mutex<Instant>,          // Время последней активности пользователя
+    pub lock_timeout: Mutex<Option<Duration>>,  // Таймаут автоблокировки (None - отключена)
+    pub backup_count: Mutex<usize>,             // Сколько резервных копий хранить при сохранении
+    pub lock_failed_at: Mutex<Option<Instant>>, // Время первой неудачной автоблокировки
 }
 
 impl AppState {
@@ -21,17 +42,146 @@ impl AppState {
             password_data: Mutex::new(Some(PasswordData {
                 groups: vec![Self::create_root_group()],
                 records: Vec::new(),
+                next_id: 2,
+                attachments: AttachmentStore::default(),
             })),
+            session_key: Mutex::new(None),
+            vault_path: Mutex::new(None),
+            saved_digest: Mutex::new(None),
+            last_activity: Mutex::new(Instant::now()),
+            lock_timeout: Mutex::new(Some(DEFAULT_LOCK_TIMEOUT)),
+            backup_count: Mutex::new(DEFAULT_BACKUP_COUNT),
+            lock_failed_at: Mutex::new(None),
+        }
+    }
+
+    /// Захватывает данные открытого хранилища
+    ///
+    /// Любое обращение команды к данным считается активностью пользователя
+    /// и сбрасывает таймер автоблокировки
+    ///
+    /// # Ошибки
+    /// `no_data`, если хранилище не открыто или заблокировано
+    pub fn vault(&self) -> Result<VaultGuard<'_>, CommandError> {
+        self.touch();
+        let guard = self.password_data.lock().unwrap();
+        if guard.is_none() {
+            return Err(CommandError::new("no_data", "Данные не загружены в систему"));
+        }
+        Ok(VaultGuard(guard))
+    }
+
+    /// Провайдер файлов с текущими настройками резервного копирования
+    pub fn provider(&self) -> KakaduProvider {
+        KakaduProvider {
+            backup_count: *self.backup_count.lock().unwrap(),
+        }
+    }
+
+    /// Запоминает отпечаток данных, совпадающих с содержимым файла на диске
+    pub fn mark_saved(&self, data: &PasswordData) {
+        *self.saved_digest.lock().unwrap() = data.digest().ok();
+    }
+
+    /// Отмечает активность пользователя и сбрасывает таймер автоблокировки
+    ///
+    /// Отменяет и отложенное затирание данных после неудачной автоблокировки
+    pub fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+        *self.lock_failed_at.lock().unwrap() = None;
+    }
+
+    /// Проверяет, истек ли таймаут бездействия для открытого хранилища
+    ///
+    /// Относится и к хранилищу, не связанному с файлом: его данные тоже не должны
+    /// оставаться в памяти бесконечно (см. `discard_vault`)
+    pub fn is_idle_expired(&self) -> bool {
+        let timeout = match *self.lock_timeout.lock().unwrap() {
+            Some(timeout) => timeout,
+            None => return false,
+        };
+
+        self.password_data.lock().unwrap().is_some() && self.last_activity.lock().unwrap().elapsed() >= timeout
+    }
+
+    /// Отмечает неудачную автоблокировку
+    ///
+    /// # Возвращает
+    /// `true` для первой неудачи после активности пользователя: о ней нужно сообщить
+    /// на фронтенд, дальше идет отсчет `LOCK_GRACE_PERIOD`
+    pub fn record_lock_failure(&self) -> bool {
+        let mut failed_at = self.lock_failed_at.lock().unwrap();
+        if failed_at.is_some() {
+            return false;
         }
+        *failed_at = Some(Instant::now());
+        true
+    }
+
+    /// Истек ли `LOCK_GRACE_PERIOD` с первой неудачной автоблокировки
+    pub fn lock_grace_expired(&self) -> bool {
+        self.lock_failed_at.lock().unwrap().is_some_and(|t| t.elapsed() >= LOCK_GRACE_PERIOD)
+    }
+
+    /// Затирает расшифрованные данные и ключ сессии без сохранения
+    ///
+    /// Используется автоблокировкой, когда изменения сохранить нельзя (файла нет или нет
+    /// ключа сессии), а пользователь не вернулся за `LOCK_GRACE_PERIOD`. Несохраненные
+    /// изменения теряются; путь к файлу остается для `unlock_vault`
+    pub fn discard_vault(&self) {
+        let mut data_guard = self.password_data.lock().unwrap();
+        *data_guard = None;
+        *self.session_key.lock().unwrap() = None;
+        *self.saved_digest.lock().unwrap() = None;
+        *self.lock_failed_at.lock().unwrap() = None;
+    }
+
+    /// Блокирует хранилище: затирает расшифрованные данные и ключ сессии
+    ///
+    /// # Поведение
+    /// Несохраненные изменения сначала записываются в файл ключом сессии, поэтому
+    /// блокировка не теряет данные. Путь к файлу сохраняется, чтобы `unlock_vault`
+    /// мог открыть его повторно. Отправляет на фронтенд событие `vault_locked`.
+    /// Уже заблокированное хранилище не изменяется
+    ///
+    /// # Ошибки
+    /// Хранилище остается открытым, если:
+    /// * `no_path` - хранилище еще не сохранялось в файл
+    /// * `no_session` - есть несохраненные изменения, но нет ключа сессии (устаревший формат)
+    /// * коды `KakaduError` - не удалось сохранить изменения
+    pub fn lock_vault(&self, app: &AppHandle) -> Result<(), CommandError> {
+        let mut data_guard = self.password_data.lock().unwrap();
+        let data = match data_guard.as_ref() {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+        let mut session_key = self.session_key.lock().unwrap();
+        let path = self
+            .vault_path
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| CommandError::new("no_path", "Хранилище не сохранено в файл"))?;
+
+        if Some(data.digest()?) != *self.saved_digest.lock().unwrap() {
+            let key = session_key.as_ref().ok_or_else(|| {
+                CommandError::new("no_session", "Несохраненные изменения нельзя записать без пароля")
+            })?;
+            self.provider().save_with_key(path, key, data)?;
+        }
+
+        // Drop данных и ключа затирает секреты в памяти
+        *data_guard = None;
+        *session_key = None;
+        *self.saved_digest.lock().unwrap() = None;
+        *self.lock_failed_at.lock().unwrap() = None;
+
+        Ok(emit_event(app, "vault_locked", &(), "Ошибка отправки события блокировки")?)
     }
 
     /// Создает корневую группу
     fn create_root_group() -> Group {
-        Group {
-            id: 1,
-            pid: 0,
-            name: "NewDatabase".to_string()
-        }
+        Group::new(1, 0, "NewDatabase".to_string())
     }
 
     /// Очищает список групп, оставляя только корневую, и отправляет список на фронтенд
@@ -39,8 +189,8 @@ impl AppState {
         let mut password_data = self.password_data.lock()
             .map_err(|e| format!("Ошибка блокировки Mutex: {}", e))?;
 
-        // Теперь мы уверены, что данные есть, так как инициализируются в new()
-        let data = password_data.as_mut().unwrap();
+        // Данных нет, если хранилище заблокировано
+        let data = password_data.as_mut().ok_or_else(|| "Хранилище заблокировано".to_string())?;
         data.groups.clear();
         data.groups.push(Self::create_root_group());
 
@@ -50,3 +200,89 @@ impl AppState {
         Ok(())
     }
 }
+
+/// Захваченные данные открытого хранилища (см. `AppState::vault`)
+///
+/// Гарантирует, что данные загружены, и освобождает `password_data` при выходе из области
+pub struct VaultGuard<'a>(MutexGuard<'a, Option<PasswordData>>);
+
+impl Deref for VaultGuard<'_> {
+    type Target = PasswordData;
+
+    fn deref(&self) -> &PasswordData {
+        self.0.as_ref().expect("данные проверены в AppState::vault")
+    }
+}
+
+impl DerefMut for VaultGuard<'_> {
+    fn deref_mut(&mut self) -> &mut PasswordData {
+        self.0.as_mut().expect("данные проверены в AppState::vault")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vault_access_resets_idle_timer() {
+        let state = AppState::new();
+        *state.last_activity.lock().unwrap() = Instant::now() - Duration::from_secs(3600);
+
+        let _data = state.vault().unwrap();
+
+        assert!(state.last_activity.lock().unwrap().elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn lock_timeout_applies_to_vault_without_file() {
+        let state = AppState::new();
+        *state.lock_timeout.lock().unwrap() = Some(Duration::ZERO);
+        assert!(state.is_idle_expired());
+
+        *state.lock_timeout.lock().unwrap() = None;
+        assert!(!state.is_idle_expired());
+    }
+
+    #[test]
+    fn unsaved_vault_is_discarded_after_grace_period() {
+        let state = AppState::new();
+        *state.session_key.lock().unwrap() = None;
+
+        // Первая неудача сообщается, повторные - нет, пока не истечет отсрочка
+        assert!(state.record_lock_failure());
+        assert!(!state.record_lock_failure());
+        assert!(!state.lock_grace_expired());
+
+        *state.lock_failed_at.lock().unwrap() = Some(Instant::now() - LOCK_GRACE_PERIOD);
+        assert!(state.lock_grace_expired());
+
+        state.discard_vault();
+        assert!(state.password_data.lock().unwrap().is_none());
+        assert!(state.saved_digest.lock().unwrap().is_none());
+        assert_eq!(state.vault().err().unwrap().code, "no_data");
+        assert!(!state.is_idle_expired());
+        assert!(!state.lock_grace_expired());
+    }
+
+    #[test]
+    fn activity_cancels_pending_discard() {
+        let state = AppState::new();
+        state.record_lock_failure();
+        *state.lock_failed_at.lock().unwrap() = Some(Instant::now() - LOCK_GRACE_PERIOD);
+
+        state.touch();
+
+        assert!(!state.lock_grace_expired());
+        assert!(state.record_lock_failure());
+        assert!(state.password_data.lock().unwrap().is_some());
+    }
+
+    #[test]
+    fn locked_vault_reports_no_data() {
+        let state = AppState::new();
+        *state.password_data.lock().unwrap() = None;
+
+        assert_eq!(state.vault().err().unwrap().code, "no_data");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::modules::kakadu_file_module::{KakaduError, TreeError};
 use serde::Serialize;
 use tauri::{AppHandle, Emitter};
 
@@ -11,4 +12,57 @@ pub fn emit_event<T: Serialize>(
         .map_err(|e| format!("{}: {}", error_text, e))
 }
 
+/// Ошибка команды со стабильным кодом, по которому фронтенд выбирает локализованный текст
+///
+/// Сериализуется в `{ "code": "...", "message": "..." }`
+#[derive(Debug, Serialize)]
+pub struct CommandError {
+    pub code: String,    // Стабильный код ошибки
+    pub message: String, // Сообщение для логов и отладки
+}
+
+impl CommandError {
+    /// Создает ошибку с указанным кодом
+    pub fn new(code: &str, message: &str) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Ошибка проверки данных, пришедших с фронтенда (`invalid_input`)
+    pub fn invalid_input(message: String) -> Self {
+        Self {
+            code: "invalid_input".to_string(),
+            message,
+        }
+    }
+}
+
+impl From<KakaduError> for CommandError {
+    fn from(e: KakaduError) -> Self {
+        Self {
+            code: e.code().to_string(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<TreeError> for CommandError {
+    fn from(e: TreeError) -> Self {
+        Self {
+            code: e.code().to_string(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self {
+            code: "internal".to_string(),
+            message,
+        }
+    }
+}
 
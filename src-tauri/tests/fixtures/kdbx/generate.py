@@ -0,0 +1,259 @@
+#!/usr/bin/env python3
+"""Генератор образцов баз KDBX 4.0 для тестов импорта.
+
+Файлы собираются независимо от кода приложения (Python + cryptography),
+повторяя раскладку KeePass 2.x и KeePassXC, но этими программами не создавались,
+поэтому их имена начинаются с `generated-`. Все случайные значения выводятся
+из имени файла, поэтому повторный запуск дает те же байты.
+
+Запуск: python3 generate.py (из этого каталога)
+"""
+
+import base64
+import gzip
+import hashlib
+import hmac
+import struct
+from datetime import datetime, timezone
+
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+from cryptography.hazmat.primitives.kdf.argon2 import Argon2d, Argon2id
+from cryptography.hazmat.primitives import padding
+
+PASSWORD = b"kakadu-sample"
+
+CIPHER_AES256 = bytes.fromhex("31c1f2e6bf714350be5805216afc5aff")
+CIPHER_CHACHA20 = bytes.fromhex("d6038a2b8b6f4cb5a524339a31dbb59a")
+KDF_AES_KEEPASS = bytes.fromhex("c9d9f39a628a4460bf740d08c18a4fea")
+KDF_AES_KEEPASSXC = bytes.fromhex("7c02bb8279a74ac0927d114a00648238")
+KDF_ARGON2D = bytes.fromhex("ef636ddf8c29444b91f7a9a403e30a0c")
+KDF_ARGON2ID = bytes.fromhex("9e298b1956db4773b23dfc3ec6f0a1e6")
+
+# Затраты KDF занижены, чтобы тесты шли быстро
+AES_KDF_ROUNDS = 1000
+ARGON2_MEMORY_KIB = 1024
+ARGON2_ITERATIONS = 2
+ARGON2_PARALLELISM = 2
+
+ATTACHMENT = b"Kakadu sample attachment\n"
+
+
+def seeded(name, label, length):
+    out = b""
+    counter = 0
+    while len(out) < length:
+        out += hashlib.sha256(f"{name}/{label}/{counter}".encode()).digest()
+        counter += 1
+    return out[:length]
+
+
+def kdbx_time(year, month, day):
+    seconds = int((datetime(year, month, day, tzinfo=timezone.utc) - datetime(1, 1, 1, tzinfo=timezone.utc)).total_seconds())
+    return base64.b64encode(struct.pack("<q", seconds)).decode()
+
+
+def field(field_id, value):
+    return struct.pack("<BI", field_id, len(value)) + value
+
+
+def variant_dictionary(items):
+    out = struct.pack("<H", 0x0100)
+    for key, kind, value in items:
+        key = key.encode()
+        out += struct.pack("<BI", kind, len(key)) + key + struct.pack("<I", len(value)) + value
+    return out + b"\x00"
+
+
+class Stream:
+    """Поток защиты значений ChaCha20 (идентификатор 3)"""
+
+    def __init__(self, key):
+        digest = hashlib.sha512(key).digest()
+        self.cipher = Cipher(algorithms.ChaCha20(digest[:32], b"\x00" * 4 + digest[32:44]), None).encryptor()
+
+    def protect(self, value):
+        return base64.b64encode(self.cipher.update(value.encode())).decode()
+
+
+def string(stream, key, value, protected=False):
+    if protected:
+        return f"<String><Key>{key}</Key><Value Protected=\"True\">{stream.protect(value)}</Value></String>"
+    return f"<String><Key>{key}</Key><Value>{value}</Value></String>"
+
+
+def times(modified):
+    return (
+        f"<Times><CreationTime>{kdbx_time(2023, 1, 2)}</CreationTime>"
+        f"<LastModificationTime>{modified}</LastModificationTime>"
+        f"<LastAccessTime>{modified}</LastAccessTime>"
+        f"<ExpiryTime>{kdbx_time(2023, 1, 2)}</ExpiryTime><Expires>False</Expires>"
+        f"<UsageCount>0</UsageCount><LocationChanged>{modified}</LocationChanged></Times>"
+    )
+
+
+def document(name, generator, stream):
+    """XML базы: группа с записью, подгруппа и корзина с удаленной записью
+
+    Защищенные значения шифруются потоком в порядке документа
+    """
+    uuid = lambda label: base64.b64encode(seeded(name, label, 16)).decode()
+    keepassxc = generator == "KeePassXC"
+
+    mail = [
+        f"<Entry><UUID>{uuid('mail')}</UUID><IconID>0</IconID>",
+        f"<Tags>{'work,mail' if keepassxc else 'work;mail'}</Tags>",
+        times(kdbx_time(2024, 3, 1)),
+        string(stream, "Title", "Mail"),
+        string(stream, "UserName", "alice"),
+        string(stream, "Password", "correct horse", True),
+        string(stream, "URL", "https://mail.example.com"),
+        string(stream, "Notes", "Primary mailbox"),
+        string(stream, "PIN", "1234", True),
+        string(stream, "Recovery", "blue-falcon"),
+    ]
+    if keepassxc:
+        mail.append(string(stream, "otp", "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&period=30&digits=6&issuer=Example", True))
+    else:
+        mail.append(string(stream, "TimeOtp-Secret-Base32", "JBSWY3DPEHPK3PXP", True))
+    mail += [
+        "<Binary><Key>note.txt</Key><Value Ref=\"0\" /></Binary>",
+        "<History>",
+        f"<Entry><UUID>{uuid('mail')}</UUID>{times(kdbx_time(2023, 6, 1))}",
+        string(stream, "Title", "Mail"),
+        string(stream, "Password", "old-1", True),
+        "</Entry>",
+        f"<Entry><UUID>{uuid('mail')}</UUID>{times(kdbx_time(2023, 9, 1))}",
+        string(stream, "Title", "Mail"),
+        string(stream, "Password", "old-2", True),
+        "</Entry>",
+        "</History></Entry>",
+    ]
+
+    bank = [
+        f"<Group><UUID>{uuid('banking')}</UUID><Name>Banking</Name><IconID>48</IconID>",
+        times(kdbx_time(2023, 1, 2)),
+        f"<Entry><UUID>{uuid('bank')}</UUID><IconID>0</IconID><Tags></Tags>",
+        times(kdbx_time(2023, 1, 2)),
+        string(stream, "Title", "Bank"),
+        string(stream, "UserName", "alice.b"),
+        string(stream, "Password", "s3cr3t", True),
+        string(stream, "URL", ""),
+        string(stream, "Notes", ""),
+        "</Entry></Group>",
+    ]
+
+    recycle_bin = [
+        f"<Group><UUID>{uuid('recycle')}</UUID><Name>Recycle Bin</Name><IconID>43</IconID>",
+        times(kdbx_time(2023, 1, 2)),
+        f"<Entry><UUID>{uuid('deleted')}</UUID><IconID>0</IconID>",
+        times(kdbx_time(2023, 1, 2)),
+        string(stream, "Title", "Deleted"),
+        string(stream, "Password", "gone", True),
+        "</Entry></Group>",
+    ]
+
+    xml = [
+        "<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>",
+        "<KeePassFile><Meta>",
+        f"<Generator>{generator}</Generator><DatabaseName>Sample</DatabaseName>",
+        "<MemoryProtection><ProtectTitle>False</ProtectTitle><ProtectUserName>False</ProtectUserName>"
+        "<ProtectPassword>True</ProtectPassword><ProtectURL>False</ProtectURL><ProtectNotes>False</ProtectNotes></MemoryProtection>",
+        f"<RecycleBinEnabled>True</RecycleBinEnabled><RecycleBinUUID>{uuid('recycle')}</RecycleBinUUID>",
+        "</Meta><Root>",
+        f"<Group><UUID>{uuid('root')}</UUID><Name>Sample</Name><IconID>48</IconID>",
+        times(kdbx_time(2023, 1, 2)),
+        *mail,
+        *bank,
+        *recycle_bin,
+        "</Group><DeletedObjects /></Root></KeePassFile>",
+    ]
+    return "".join(xml).encode()
+
+
+def transform_key(kdf, composite, seed):
+    if kdf == "aeskdf":
+        encryptor = Cipher(algorithms.AES(seed), modes.ECB()).encryptor()
+        key = composite
+        for _ in range(AES_KDF_ROUNDS):
+            key = encryptor.update(key)
+        return hashlib.sha256(key).digest()
+
+    argon2 = Argon2d if kdf == "argon2d" else Argon2id
+    return argon2(
+        salt=seed,
+        length=32,
+        iterations=ARGON2_ITERATIONS,
+        lanes=ARGON2_PARALLELISM,
+        memory_cost=ARGON2_MEMORY_KIB,
+    ).derive(composite)
+
+
+def kdf_parameters(kdf, generator, seed):
+    if kdf == "aeskdf":
+        uuid = KDF_AES_KEEPASSXC if generator == "KeePassXC" else KDF_AES_KEEPASS
+        return variant_dictionary([
+            ("$UUID", 0x42, uuid),
+            ("R", 0x05, struct.pack("<Q", AES_KDF_ROUNDS)),
+            ("S", 0x42, seed),
+        ])
+
+    uuid = KDF_ARGON2D if kdf == "argon2d" else KDF_ARGON2ID
+    return variant_dictionary([
+        ("$UUID", 0x42, uuid),
+        ("S", 0x42, seed),
+        ("P", 0x04, struct.pack("<I", ARGON2_PARALLELISM)),
+        ("M", 0x05, struct.pack("<Q", ARGON2_MEMORY_KIB * 1024)),
+        ("I", 0x05, struct.pack("<Q", ARGON2_ITERATIONS)),
+        ("V", 0x04, struct.pack("<I", 0x13)),
+    ])
+
+
+def write(name, generator, kdf, cipher):
+    master_seed = seeded(name, "master-seed", 32)
+    kdf_seed = seeded(name, "kdf-seed", 32)
+    iv = seeded(name, "iv", 16 if cipher == "aes" else 12)
+    stream_key = seeded(name, "stream-key", 64)
+
+    header = bytes.fromhex("03d9a29a67fb4bb5") + struct.pack("<I", 0x00040000)
+    header += field(2, CIPHER_AES256 if cipher == "aes" else CIPHER_CHACHA20)
+    header += field(3, struct.pack("<I", 1))
+    header += field(4, master_seed)
+    header += field(7, iv)
+    header += field(11, kdf_parameters(kdf, generator, kdf_seed))
+    header += field(0, b"\r\n\r\n")
+
+    composite = hashlib.sha256(hashlib.sha256(PASSWORD).digest()).digest()
+    transformed = transform_key(kdf, composite, kdf_seed)
+    key = hashlib.sha256(master_seed + transformed).digest()
+    hmac_base = hashlib.sha512(master_seed + transformed + b"\x01").digest()
+    block_key = lambda index: hashlib.sha512(struct.pack("<Q", index) + hmac_base).digest()
+
+    inner = field(1, struct.pack("<I", 3)) + field(2, stream_key) + field(3, b"\x01" + ATTACHMENT) + field(0, b"")
+    plain = gzip.compress(inner + document(name, generator, Stream(stream_key)), mtime=0)
+
+    if cipher == "aes":
+        padder = padding.PKCS7(128).padder()
+        padded = padder.update(plain) + padder.finalize()
+        encrypted = Cipher(algorithms.AES(key), modes.CBC(iv)).encryptor().update(padded)
+    else:
+        encrypted = Cipher(algorithms.ChaCha20(key, b"\x00" * 4 + iv), None).encryptor().update(plain)
+
+    out = header + hashlib.sha256(header).digest()
+    out += hmac.new(block_key(0xFFFF_FFFF_FFFF_FFFF), header, "sha256").digest()
+    for index, block in enumerate([encrypted, b""]):
+        message = struct.pack("<I", len(block)) + block
+        out += hmac.new(block_key(index), struct.pack("<Q", index) + message, "sha256").digest() + message
+
+    with open(name, "wb") as f:
+        f.write(out)
+
+
+def main():
+    for generator, prefix in [("KeePass", "keepass2"), ("KeePassXC", "keepassxc")]:
+        for kdf in ["aeskdf", "argon2d", "argon2id"]:
+            for cipher in ["aes", "chacha20"]:
+                write(f"generated-{prefix}-{kdf}-{cipher}.kdbx", generator, kdf, cipher)
+
+
+if __name__ == "__main__":
+    main()